@@ -5,6 +5,7 @@ fn bench_can_proxy(c: &mut Criterion) {
     use std::{
         sync::{
             atomic::{AtomicBool, Ordering},
+            mpsc::channel,
             Arc,
         },
         time::Instant,
@@ -16,7 +17,7 @@ fn bench_can_proxy(c: &mut Criterion) {
         canframe::CANRequest,
     };
 
-    let mut can_proxy = CANProxy::new("fakecan");
+    let mut can_proxy = CANProxy::new("fakecan").unwrap();
 
     c.bench_function("send request", |b| {
         b.iter_custom(|_iters| {
@@ -25,14 +26,14 @@ fn bench_can_proxy(c: &mut Criterion) {
             let is_done_clone = is_done.clone();
 
             can_proxy.register_rw("thread 1", move |can_read_write| {
-                let frames = (0..NUM_MESSAGES).into_iter().map(|axis| black_box(CANRequest {
+                let frames = (0..NUM_MESSAGES).map(|axis| black_box(CANRequest {
                     axis: axis as u32,
                     cmd: ODriveCommand::Read(ReadComm::EncoderError),
                     data: [0;8]
                 })).collect();
                 can_read_write.request_many(frames);
                 is_done_clone.store(true, Ordering::SeqCst);
-            });
+            }).unwrap();
 
             let start = Instant::now();
             while !is_done.load(Ordering::SeqCst) {
@@ -41,7 +42,56 @@ fn bench_can_proxy(c: &mut Criterion) {
 
             let elapsed = start.elapsed().div_f32(NUM_MESSAGES as f32);
             can_proxy.unregister("thread 1").unwrap();
-            return elapsed;
+            elapsed
+        });
+    });
+
+    // Round trip time of a single request at a time, comparing the proxy being polled
+    // in a loop with the event driven loop started by `CANProxy::begin()`
+    let request = CANRequest {
+        axis: 1,
+        cmd: ODriveCommand::Read(ReadComm::EncoderError),
+        data: [0; 8],
+    };
+
+    c.bench_function("request latency (polling)", |b| {
+        b.iter_custom(|iters| {
+            let (send, rcv) = channel();
+            can_proxy.register_rw("thread 1", move |can_read_write| {
+                let start = Instant::now();
+                for _ in 0..iters {
+                    black_box(can_read_write.request(request)).unwrap();
+                }
+                send.send(start.elapsed()).unwrap();
+            }).unwrap();
+
+            let elapsed = loop {
+                can_proxy.process_messages();
+                if let Ok(elapsed) = rcv.try_recv() {
+                    break elapsed;
+                }
+            };
+            can_proxy.unregister("thread 1").unwrap();
+            elapsed
+        });
+    });
+
+    c.bench_function("request latency (event driven)", |b| {
+        b.iter_custom(|iters| {
+            let mut can_proxy = CANProxy::new("fakecan").unwrap();
+            let (send, rcv) = channel();
+            can_proxy.register_rw("thread 1", move |can_read_write| {
+                let start = Instant::now();
+                for _ in 0..iters {
+                    black_box(can_read_write.request(request)).unwrap();
+                }
+                send.send(start.elapsed()).unwrap();
+            }).unwrap();
+
+            let stop_proxy = can_proxy.begin();
+            let elapsed = rcv.recv().unwrap();
+            stop_proxy().unwrap();
+            elapsed
         });
     });
 }
@@ -0,0 +1,325 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures::{channel::oneshot, future::join_all};
+
+use crate::{
+    axis::{Axis, AxisID},
+    canframe::{CANRequest, CANResponse, ODriveCANFrame, Reply, ThreadCANFrame, ThreadMessage},
+    limits::SoftLimits,
+    odrivegroup::{GroupMembers, ODriveGroup},
+    response::{ErrorResponse, ODriveResponse, Success},
+    Error,
+};
+
+/// A handle with read and write access to CAN whose requests return futures that the
+/// [`CANProxy`](crate::canproxy::CANProxy) resolves once the request was sent or answered.
+/// It is created with [`CANProxy::register_async()`](crate::canproxy::CANProxy::register_async)
+/// and is only available with the `async` feature.
+///
+/// Unlike [`ReadWriteCANThread`](crate::threads::ReadWriteCANThread) this does not need a
+/// thread of its own. It can be cloned and used from as many tasks as needed, with any executor.
+#[derive(Clone, Debug)]
+pub struct AsyncCANThread {
+    thread_name: &'static str,
+    requester: Sender<ThreadMessage>,
+    threads_alive: Arc<AtomicBool>,
+}
+
+impl AsyncCANThread {
+    pub(crate) fn new(thread_name: &'static str, requester: Sender<ThreadMessage>, threads_alive: Arc<AtomicBool>) -> Self {
+        Self { thread_name, requester, threads_alive }
+    }
+
+    /// This sends a CANFrame and resolves to the response once it is back
+    pub async fn request(&self, msg: CANRequest) -> ODriveResponse {
+        self.request_inner(msg, None).await
+    }
+
+    /// This sends all the messages specified at once and resolves once all of them
+    /// have been responded to. Responses are returned in the order they were sent
+    pub async fn request_many(&self, messages: Vec<CANRequest>) -> Vec<ODriveResponse> {
+        join_all(messages.into_iter().map(|msg| self.request_inner(msg, None))).await
+    }
+
+    /// Same as [`AsyncCANThread::request()`], but `Read` requests are answered with
+    /// [`ODriveError::NoResponse`] if no response arrives within `timeout`
+    /// (and any retries configured on the `CANProxy`)
+    pub async fn request_with_timeout(&self, msg: CANRequest, timeout: Duration) -> ODriveResponse {
+        self.request_inner(msg, Some(timeout)).await
+    }
+
+    /// Same as [`AsyncCANThread::request_many()`], but with the timeout
+    /// applied to every request individually
+    pub async fn request_many_with_timeout(&self, messages: Vec<CANRequest>, timeout: Duration) -> Vec<ODriveResponse> {
+        join_all(messages.into_iter().map(|msg| self.request_inner(msg, Some(timeout)))).await
+    }
+
+    fn request_inner(&self, msg: CANRequest, timeout: Option<Duration>) -> impl std::future::Future<Output = ODriveResponse> {
+        // The request is sent right away rather than when the future is first polled
+        let (sender, receiver) = oneshot::channel();
+        let frame = ThreadCANFrame::new(self.thread_name, msg, timeout);
+        if let Err(error) = self.requester.send(ThreadMessage::Request(frame, Reply::Future(sender))) {
+            panic!("Lost connection to CANManager thread: \n{}", error);
+        }
+
+        let thread_name = self.thread_name;
+        async move {
+            match receiver.await {
+                Ok(response) => response,
+                Err(error) => panic!("Thread {} disconnected: \n{}", thread_name, error),
+            }
+        }
+    }
+
+    /// This should look at the shared reference of whether the threads should be running,
+    pub fn is_alive(&self) -> bool {
+        self.threads_alive.load(Ordering::SeqCst)
+    }
+}
+
+/// The async equivalent of [`ODriveGroup`], for sending requests to a group of axes
+/// through an [`AsyncCANThread`]. Axes, sub-groups and soft limits are managed the same way
+/// as on an `ODriveGroup`, and every request is checked against the soft limits of its axis
+/// before it is sent.
+///
+/// # Example
+/// ```
+/// use rustodrive::asyncthread::AsyncODriveGroup;
+/// use rustodrive::canproxy::CANProxy;
+/// use rustodrive::state::AxisState::FullCalibrationSequence;
+///
+/// let mut can_proxy = CANProxy::new("can0").unwrap();
+/// let odrives = AsyncODriveGroup::new(can_proxy.register_async("async tasks").unwrap(), &[1, 2, 3, 4]);
+/// let stop_threads = can_proxy.begin();
+///
+/// futures::executor::block_on(async {
+///     odrives.all_axes::<(), _>(|ax| ax.set_state(FullCalibrationSequence)).await;
+/// });
+/// stop_threads().unwrap();
+/// ```
+pub struct AsyncODriveGroup {
+    can: AsyncCANThread,
+    members: GroupMembers,
+}
+
+impl AsyncODriveGroup {
+    pub fn new(can: AsyncCANThread, axis_ids: &[AxisID]) -> Self {
+        AsyncODriveGroup {
+            members: GroupMembers::new(axis_ids),
+            can,
+        }
+    }
+
+    /// Returns the IDs of all axes in the group in ascending order
+    pub fn axis_ids(&self) -> Vec<AxisID> {
+        self.members.axis_ids()
+    }
+
+    /// Adds an axis to the group. See [`ODriveGroup::add_axis()`]
+    pub fn add_axis(&mut self, axis_id: AxisID) {
+        self.members.add_axis(axis_id)
+    }
+
+    /// Removes an axis from the group. See [`ODriveGroup::remove_axis()`]
+    pub fn remove_axis(&mut self, axis_id: AxisID) -> Result<(), Error> {
+        self.members.remove_axis(axis_id)
+    }
+
+    /// Sets the soft limits of an axis. See [`ODriveGroup::set_limits()`]
+    pub fn set_limits(&mut self, axis_id: &AxisID, limits: SoftLimits) -> Result<(), Error> {
+        self.members.set_limits(axis_id, limits)
+    }
+
+    /// Returns the soft limits of an axis, which check nothing unless they were set
+    pub fn limits(&self, axis_id: &AxisID) -> Result<SoftLimits, Error> {
+        self.members.limits(axis_id)
+    }
+
+    /// Defines a named sub-group of axes. See [`ODriveGroup::define_subgroup()`]
+    pub fn define_subgroup(&mut self, name: &str, axis_ids: &[AxisID]) -> Result<(), Error> {
+        self.members.define_subgroup(name, axis_ids)
+    }
+
+    /// Removes a named sub-group. The axes stay part of the group
+    pub fn remove_subgroup(&mut self, name: &str) -> Result<(), Error> {
+        self.members.remove_subgroup(name)
+    }
+
+    /// Returns the IDs of the axes in a named sub-group in ascending order
+    pub fn subgroup_ids(&self, name: &str) -> Result<Vec<AxisID>, Error> {
+        self.members.subgroup_ids(name)
+    }
+
+    /// This sends the request specified by the closure to all the axes simultaneously and
+    /// resolves once they all come back. See [`ODriveGroup::all_axes()`]
+    pub async fn all_axes<T: TryFrom<CANResponse, Error = Error>, F>(
+        &self,
+        f: F,
+    ) -> Vec<Result<Success<T>, ErrorResponse>>
+    where
+        F: FnMut(&Axis) -> CANRequest,
+    {
+        self.request_checked(self.members.requests(f)).await
+    }
+
+    /// This sends the request specified by the closure to every axis of a named sub-group
+    /// and resolves once they all come back. See [`ODriveGroup::subgroup()`]
+    pub async fn subgroup<T: TryFrom<CANResponse, Error = Error>, F>(
+        &self,
+        name: &str,
+        f: F,
+    ) -> Result<Vec<Result<Success<T>, ErrorResponse>>, Error>
+    where
+        F: FnMut(&Axis) -> CANRequest,
+    {
+        let checked = self.members.subgroup_requests(name, f)?;
+        Ok(self.request_checked(checked).await)
+    }
+
+    async fn request_checked<T: TryFrom<CANResponse, Error = Error>>(
+        &self,
+        checked: Vec<Result<CANRequest, ErrorResponse>>,
+    ) -> Vec<Result<Success<T>, ErrorResponse>> {
+        let allowed = checked.iter().filter_map(|res| res.as_ref().ok()).copied().collect();
+        let responses = self.can.request_many(allowed).await;
+        ODriveGroup::merge_responses(checked, responses)
+    }
+
+    /// This sends the request specified by the closure to the axis specified and
+    /// resolves once it comes back. See [`ODriveGroup::axis()`]
+    pub async fn axis<T: TryFrom<ODriveCANFrame, Error = Error>, F: FnOnce(&Axis) -> CANRequest>(
+        &self,
+        axis_id: &AxisID,
+        f: F,
+    ) -> Result<Success<T>, Error> {
+        let request = self.members.check_limits(f(self.members.get_axis(axis_id)?))?;
+        Ok(ODriveGroup::convert_response(self.can.request(request).await)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, future::join};
+
+    use crate::{
+        canframe::{read_can, ticket, CANRequest},
+        canproxy::CANProxy,
+        casts::Temperature,
+        limits::{Limit, SoftLimits},
+        response::{ErrorResponse, ODriveError, ResponseType, Success},
+        state::{AxisState, ODriveCommand, ReadComm, WriteComm},
+        utils::ResultAll,
+        Error,
+    };
+
+    use super::AsyncODriveGroup;
+
+    #[test]
+    fn test_async_requests() {
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        let can_async = can_proxy.register_async("async").unwrap();
+        let stop_proxy = can_proxy.begin();
+
+        let read = read_can(1, ReadComm::GetIQ);
+        let write = ticket(2, ODriveCommand::Write(WriteComm::SetInputVelocity), [0; 8]);
+
+        // Both requests are in flight at the same time on a single thread
+        let (read_response, write_response) = block_on(join(can_async.request(read), can_async.request(write)));
+        let many_responses = block_on(can_async.request_many(vec![read, write])).unwrap_all();
+        stop_proxy().unwrap();
+
+        let mut response = read;
+        response.data = [99; 8];
+        assert_eq!(read_response, Ok(ResponseType::Body { request: read, response }));
+        assert_eq!(write_response, Ok(ResponseType::Bodyless { req: write }));
+        assert_eq!(many_responses[0].clone().request(), read);
+        assert_eq!(many_responses[1].clone().request(), write);
+    }
+
+    #[test]
+    fn test_async_timeout() {
+        let mut can_proxy = CANProxy::with_transport(crate::simulator::ODriveSimulator::new(&[0]));
+        let can_async = can_proxy.register_async("async").unwrap();
+        let stop_proxy = can_proxy.begin();
+
+        // There is no axis 5 on the bus to answer
+        let request = read_can(5, ReadComm::GetIQ);
+        let response = block_on(can_async.request_with_timeout(request, std::time::Duration::from_millis(20)));
+        stop_proxy().unwrap();
+
+        assert_eq!(response, Err(ErrorResponse { request, err: ODriveError::NoResponse }));
+    }
+
+    #[test]
+    fn test_async_group() {
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        let odrives = AsyncODriveGroup::new(can_proxy.register_async("async").unwrap(), &[0, 1, 2]);
+        let stop_proxy = can_proxy.begin();
+
+        let (states, temperature) = block_on(async {
+            let states = odrives.all_axes::<(), _>(|ax| ax.set_state(AxisState::ClosedLoop)).await;
+            let temperature: Success<Temperature> = odrives.axis(&1, |ax| ax.get_temperatures()).await.unwrap();
+            (states.unwrap_all(), temperature)
+        });
+        stop_proxy().unwrap();
+
+        for (axis, state) in states.into_iter().enumerate() {
+            assert_eq!(state.axis, axis);
+        }
+        assert_eq!(temperature.sent_request, CANRequest {
+            axis: 1,
+            cmd: ODriveCommand::Read(ReadComm::GetTemperature),
+            data: [0; 8],
+        });
+    }
+
+    #[test]
+    fn test_register_async_name() {
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        can_proxy.register_ro("thread 1", |_| {}).unwrap();
+        assert!(matches!(can_proxy.register_async("thread 1"), Err(Error::DuplicateThread("thread 1"))));
+
+        can_proxy.register_async("async").unwrap();
+        assert!(matches!(can_proxy.register_ro("async", |_| {}), Err(Error::DuplicateThread("async"))));
+        assert!(matches!(can_proxy.register_rw("thread 2", |_| {}), Err(Error::ReadWriteTaken)));
+
+        // Unregistering the handle frees its place for a read-write thread
+        can_proxy.unregister("async").unwrap();
+        assert!(matches!(can_proxy.unregister("async"), Err(Error::UnknownThread(_))));
+        can_proxy.register_rw("thread 2", |_| {}).unwrap();
+        can_proxy.join_registered().unwrap();
+    }
+
+    #[test]
+    fn test_async_group_limits() {
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        let mut odrives = AsyncODriveGroup::new(can_proxy.register_async("async").unwrap(), &[0, 1, 2]);
+        odrives.set_limits(&1, SoftLimits { position: Some((-0.5, 0.5)), ..Default::default() }).unwrap();
+        odrives.define_subgroup("leg", &[1, 2]).unwrap();
+        odrives.remove_axis(2).unwrap();
+        let stop_proxy = can_proxy.begin();
+
+        let (all, leg, single) = block_on(async {
+            let all = odrives.all_axes::<(), _>(|ax| ax.motor.set_input_pos(1.0)).await;
+            let leg = odrives.subgroup::<(), _>("leg", |ax| ax.motor.set_input_pos(0.25)).await.unwrap();
+            let single = odrives.axis::<(), _>(&1, |ax| ax.motor.set_input_pos(-1.0)).await;
+            (all, leg, single)
+        });
+        stop_proxy().unwrap();
+
+        // Only axis 1 has limits, and axis 2 is no longer part of the sub-group
+        assert_eq!(all.len(), 2);
+        assert!(all[0].is_ok());
+        assert_eq!(all[1].as_ref().unwrap_err().err, ODriveError::LimitExceeded(Limit::Position));
+        assert_eq!(leg.len(), 1);
+        assert!(leg[0].is_ok());
+        assert!(matches!(single, Err(Error::Request(ErrorResponse { err: ODriveError::LimitExceeded(Limit::Position), .. }))));
+    }
+}
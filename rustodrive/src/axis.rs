@@ -15,97 +15,135 @@ use crate::{
 pub type AxisID = usize;
 
 /// This struct contains methods that can generate common `ODriveCANFrame` configurations.
-/// The [`Motor`] and [`Encoder`] objects are publicly accessible and define their own
-/// frame-generating methods.
-pub struct Axis<'a> {
-    id: &'a AxisID,
-    pub motor: Motor<'a>,
-    pub encoder: Encoder<'a>,
+/// The [`Motor`], [`Encoder`] and [`Trajectory`] objects are publicly accessible and define
+/// their own frame-generating methods.
+pub struct Axis {
+    id: AxisID,
+    pub motor: Motor,
+    pub encoder: Encoder,
+    pub trajectory: Trajectory,
 }
 
-impl<'a> Axis<'a> {
-    pub fn new(id: &'a AxisID) -> Self {
+impl Axis {
+    pub fn new(id: AxisID) -> Self {
         Axis {
             id,
             motor: Motor::new(id),
             encoder: Encoder::new(id),
+            trajectory: Trajectory::new(id),
         }
     }
 
+    /// The CAN node ID of the axis
+    pub fn id(&self) -> AxisID {
+        self.id
+    }
+
     pub fn get_heartbeat(&self) -> CANRequest {
-        ticket(*self.id, Read(GetHeartbeat), [0; 8])
+        ticket(self.id, Read(GetHeartbeat), [0; 8])
     }
 
     /// This generates the command to set the state for the `Axis` object in question
     pub fn set_state(&self, state: AxisState) -> CANRequest {
         ticket(
-            *self.id,
+            self.id,
             Write(SetAxisRequestedState),
             [state as u8, 0, 0, 0, 0, 0, 0, 0],
         )
     }
 
     pub fn get_temperatures(&self) -> CANRequest {
-        ticket(*self.id, Read(GetTemperature), [0; 8])
+        ticket(self.id, Read(GetTemperature), [0; 8])
+    }
+
+    /// This generates the command to stop the axis immediately. The axis is disarmed and
+    /// `AxisError::EStopRequested` is set until the errors are cleared
+    pub fn estop(&self) -> CANRequest {
+        ticket(self.id, Write(EStop), [0; 8])
+    }
+
+    /// This generates the command to save the configuration of the ODrive and reboot it.
+    /// Firmware older than v0.6 does not support the save action and only reboots
+    pub fn save_configuration(&self) -> CANRequest {
+        ticket(self.id, Write(RebootODrive), [1, 0, 0, 0, 0, 0, 0, 0])
     }
 }
 
-pub struct Encoder<'a> {
-    id: &'a AxisID,
+pub struct Encoder {
+    id: AxisID,
 }
-impl<'a> Encoder<'a> {
-    pub fn new(id: &'a AxisID) -> Self {
+impl Encoder {
+    pub fn new(id: AxisID) -> Self {
         Encoder { id }
     }
     pub fn get_error(&self) -> CANRequest {
-        ticket(*self.id, Read(EncoderError), [0; 8])
+        ticket(self.id, Read(EncoderError), [0; 8])
     }
 
     pub fn get_count(&self) -> CANRequest {
-        ticket(*self.id, Read(GetEncoderCount), [0; 8])
+        ticket(self.id, Read(GetEncoderCount), [0; 8])
     }
     pub fn get_estimates(&self) -> CANRequest {
-        ticket(*self.id, Read(GetEncoderEstimates), [0; 8])
+        ticket(self.id, Read(GetEncoderEstimates), [0; 8])
     }
-    fn set_linear_count() {
-        unimplemented!()
+    /// This generates the command to set the encoder position in counts.
+    /// This is best done while the axis is idle, otherwise it moves to catch up with the change
+    pub fn set_linear_count(&self, count: i32) -> CANRequest {
+        ticket(self.id, Write(SetLinearCount), RData::combine_32(count.to_le_bytes(), [0; 4]))
     }
 }
 
-struct Trajectory;
+/// Configures the trapezoidal trajectory planner of the ODrive, used with `InputMode::TrapTraj`
+pub struct Trajectory {
+    id: AxisID,
+}
 impl Trajectory {
-    fn set_traj_vel_limit() {
-        unimplemented!()
+    pub fn new(id: AxisID) -> Self {
+        Trajectory { id }
     }
-    fn set_traj_accel_limit() {
-        unimplemented!()
+    /// This generates the command to set the cruising velocity of trajectories in turns/s
+    pub fn set_traj_vel_limit(&self, velocity: f32) -> CANRequest {
+        ticket(self.id, Write(SetTrajVelocityLim), RData::combine_32(velocity.to_le_bytes(), [0; 4]))
     }
-    fn set_traj_inertia() {
-        unimplemented!()
+    /// This generates the command to set the acceleration and deceleration of trajectories in turns/s²
+    pub fn set_traj_accel_limit(&self, accel: f32, decel: f32) -> CANRequest {
+        ticket(self.id, Write(SetTrajAccelLim), RData::combine_32(accel.to_le_bytes(), decel.to_le_bytes()))
+    }
+    /// This generates the command to set the inertia in Nm/(turn/s²) used for the torque feedforward of trajectories
+    pub fn set_traj_inertia(&self, inertia: f32) -> CANRequest {
+        ticket(self.id, Write(SetTrajInertia), RData::combine_32(inertia.to_le_bytes(), [0; 4]))
     }
 }
 
-pub struct Motor<'a> {
-    id: &'a AxisID,
+pub struct Motor {
+    id: AxisID,
 }
-impl<'a> Motor<'a> {
-    pub fn new(id: &'a AxisID) -> Self {
+impl Motor {
+    pub fn new(id: AxisID) -> Self {
         Motor { id }
     }
 
     pub fn get_errors(&self) -> CANRequest {
-        ticket(*self.id, Read(MotorError), [0; 8])
+        ticket(self.id, Read(MotorError), [0; 8])
     }
     pub fn get_sensorless_error(&self) -> CANRequest {
-        ticket(*self.id, Read(SensorlessError), [0; 8])
+        ticket(self.id, Read(SensorlessError), [0; 8])
     }
 
-    fn set_node_id() {
-        unimplemented!()
+    /// This generates the command to move the axis to a different CAN node ID.
+    /// The new ID only lasts until the ODrive reboots unless the configuration is saved
+    /// with [`Axis::save_configuration()`]
+    pub fn set_node_id(&self, node_id: AxisID) -> CANRequest {
+        ticket(
+            self.id,
+            Write(SetAxisNodeID),
+            RData::combine_32((node_id as u32).to_le_bytes(), [0; 4]),
+        )
     }
+
     pub fn set_control_mode(&self, control: ControlMode, input: InputMode) -> CANRequest {
         ticket(
-            *self.id,
+            self.id,
             Write(SetControllerMode),
             [control as u8, 0, 0, 0, input as u8, 0, 0, 0],
         )
@@ -113,22 +151,31 @@ impl<'a> Motor<'a> {
 
     pub fn set_input_pos(&self, rot: f32) -> CANRequest {
         let data = RData::combine_32(rot.to_le_bytes(), [0; 4]);
-        ticket(*self.id, Write(SetInputPosition), data)
+        ticket(self.id, Write(SetInputPosition), data)
+    }
+    /// Same as [`Motor::set_input_pos()`] with a velocity feedforward in turns/s and a torque
+    /// feedforward in Nm. Both are sent in thousandths, so they are limited to ±32.767
+    pub fn set_input_pos_ff(&self, rot: f32, vel_ff: f32, torque_ff: f32) -> CANRequest {
+        let to_thousandths = |value: f32| ((value * 1000.0).round() as i16).to_le_bytes();
+        let data = RData::combine_32(rot.to_le_bytes(), RData::combine_16(to_thousandths(vel_ff), to_thousandths(torque_ff)));
+        ticket(self.id, Write(SetInputPosition), data)
     }
     pub fn set_input_vel(&self, speed: f32) -> CANRequest {
         let data = RData::combine_32(speed.to_le_bytes(), [0; 4]);
-        ticket(*self.id, Write(SetInputVelocity), data)
+        ticket(self.id, Write(SetInputVelocity), data)
     }
-    fn set_input_torque() {
-        unimplemented!()
+    pub fn set_input_torque(&self, torque: f32) -> CANRequest {
+        let data = RData::combine_32(torque.to_le_bytes(), [0; 4]);
+        ticket(self.id, Write(SetInputTorque), data)
     }
 
     fn set_limits() {
         unimplemented!()
     } // velocity and current limit
 
-    fn get_iq_setpoint() {
-        unimplemented!()
+    /// This generates the request for the commanded and measured motor current, see [`IQ`](crate::casts::IQ)
+    pub fn get_iq_setpoint(&self) -> CANRequest {
+        ticket(self.id, Read(GetIQ), [0; 8])
     }
 
     fn set_position_gain() {
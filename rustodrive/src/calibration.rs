@@ -0,0 +1,268 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    axis::{Axis, AxisID},
+    error::{EncoderError, MotorError},
+    state::AxisState,
+    threads::ReadWriteCANThread,
+    transition::{self, TransitionOutcome},
+};
+
+/// Configures how [`ODriveGroup::calibrate()`](crate::odrivegroup::ODriveGroup::calibrate) runs
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalibrationOptions {
+    /// Search for the encoder index before the offset calibration.
+    /// This is needed if the encoder is configured with `use_index`
+    pub index_search: bool,
+    /// How many axes calibrate at the same time, to limit the current drawn from the bus.
+    /// `None` calibrates every axis at once
+    pub max_concurrent: Option<usize>,
+    /// How long a single calibration step may take on an axis before it counts as failed
+    pub step_timeout: Duration,
+}
+
+impl Default for CalibrationOptions {
+    fn default() -> Self {
+        Self {
+            index_search: true,
+            max_concurrent: None,
+            step_timeout: Duration::from_secs(15),
+        }
+    }
+}
+
+impl CalibrationOptions {
+    /// The states an axis goes through, in the order they have to run in
+    pub fn steps(&self) -> Vec<AxisState> {
+        let mut steps = vec![AxisState::MotorCalibration];
+        if self.index_search {
+            steps.push(AxisState::EncoderIndexSearch);
+        }
+        steps.push(AxisState::EncoderOffsetCalib);
+        steps
+    }
+}
+
+/// Progress of the calibration, reported for each axis and step as it happens
+#[derive(Debug, PartialEq)]
+pub enum CalibrationEvent {
+    StepStarted { axis_id: AxisID, step: AxisState },
+    StepFinished { axis_id: AxisID, step: AxisState, outcome: TransitionOutcome },
+}
+
+/// Why an axis failed to calibrate
+#[derive(Debug, PartialEq)]
+pub struct CalibrationFailure {
+    pub step: AxisState,
+    pub outcome: TransitionOutcome,
+    /// The motor errors read from the axis after it failed.
+    /// This is empty if none were set or they could not be read
+    pub motor_errors: Vec<MotorError>,
+    /// The encoder errors read from the axis after it failed.
+    /// This is empty if none were set or they could not be read
+    pub encoder_errors: Vec<EncoderError>,
+}
+
+/// The result of calibrating a single axis
+#[derive(Debug, PartialEq)]
+pub struct AxisCalibration {
+    pub axis_id: AxisID,
+    /// The steps that finished successfully, in order
+    pub completed: Vec<AxisState>,
+    /// This is `None` if every step finished successfully
+    pub failure: Option<CalibrationFailure>,
+    pub duration: Duration,
+}
+
+/// A summary of calibrating a group of axes. Printing it lists the result of every axis
+#[derive(Debug, PartialEq)]
+pub struct CalibrationReport {
+    pub axes: BTreeMap<AxisID, AxisCalibration>,
+    pub duration: Duration,
+}
+
+impl CalibrationReport {
+    /// Returns true if every axis calibrated successfully
+    pub fn succeeded(&self) -> bool {
+        self.axes.values().all(|axis| axis.failure.is_none())
+    }
+
+    /// Returns the IDs of the axes that failed to calibrate
+    pub fn failed_axes(&self) -> Vec<AxisID> {
+        self.axes.values().filter(|axis| axis.failure.is_some()).map(|axis| axis.axis_id).collect()
+    }
+}
+
+impl fmt::Display for CalibrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.failed_axes().len();
+        writeln!(
+            f,
+            "calibrated {} of {} axes in {:.1}s",
+            self.axes.len() - failed,
+            self.axes.len(),
+            self.duration.as_secs_f32()
+        )?;
+        for axis in self.axes.values() {
+            match &axis.failure {
+                None => writeln!(f, "  axis {}: ok ({:.1}s)", axis.axis_id, axis.duration.as_secs_f32())?,
+                Some(failure) => writeln!(
+                    f,
+                    "  axis {}: failed during {} ({:?}), motor errors: {:?}, encoder errors: {:?}",
+                    axis.axis_id, failure.step, failure.outcome, failure.motor_errors, failure.encoder_errors
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs every calibration step on the axes, batch by batch if `max_concurrent` is set.
+/// An axis that fails a step skips the rest of its steps.
+pub(crate) fn calibrate<F: FnMut(&CalibrationEvent)>(
+    can: &ReadWriteCANThread,
+    axis_ids: &[AxisID],
+    options: &CalibrationOptions,
+    mut on_progress: F,
+) -> CalibrationReport {
+    let start = Instant::now();
+    let batch_size = options.max_concurrent.unwrap_or(axis_ids.len()).max(1);
+
+    let mut axes = BTreeMap::new();
+    for batch in axis_ids.chunks(batch_size) {
+        let batch_start = Instant::now();
+        let mut results: BTreeMap<AxisID, AxisCalibration> = batch
+            .iter()
+            .map(|id| (*id, AxisCalibration { axis_id: *id, completed: vec![], failure: None, duration: Duration::ZERO }))
+            .collect();
+
+        for step in options.steps() {
+            let running: Vec<AxisID> = results.values().filter(|axis| axis.failure.is_none()).map(|axis| axis.axis_id).collect();
+            if running.is_empty() {
+                break;
+            }
+            for axis_id in &running {
+                on_progress(&CalibrationEvent::StepStarted { axis_id: *axis_id, step: step.clone() });
+            }
+
+            let outcomes = transition::transition(can, &running, step.clone(), options.step_timeout);
+            for (axis_id, outcome) in outcomes {
+                let result = results.get_mut(&axis_id).unwrap();
+                result.duration = batch_start.elapsed();
+                match outcome {
+                    TransitionOutcome::Reached => result.completed.push(step.clone()),
+                    ref failed => {
+                        result.failure = Some(CalibrationFailure {
+                            step: step.clone(),
+                            outcome: failed.clone(),
+                            motor_errors: vec![],
+                            encoder_errors: vec![],
+                        })
+                    }
+                }
+                on_progress(&CalibrationEvent::StepFinished { axis_id, step: step.clone(), outcome });
+            }
+        }
+
+        read_errors(can, &mut results);
+        axes.append(&mut results);
+    }
+
+    CalibrationReport { axes, duration: start.elapsed() }
+}
+
+/// Fills in the motor and encoder errors of every axis that failed
+fn read_errors(can: &ReadWriteCANThread, results: &mut BTreeMap<AxisID, AxisCalibration>) {
+    for result in results.values_mut() {
+        let failure = match &mut result.failure {
+            Some(failure) => failure,
+            None => continue,
+        };
+
+        let axis = Axis::new(result.axis_id);
+        let mut responses = can.request_many(vec![axis.motor.get_errors(), axis.encoder.get_error()]).into_iter();
+        if let Some(Ok(response)) = responses.next() {
+            let (_, motor) = response.body();
+            failure.motor_errors = MotorError::from_bits(u64::from_le_bytes(motor.data));
+        }
+        if let Some(Ok(response)) = responses.next() {
+            let (_, encoder) = response.body();
+            failure.encoder_errors = EncoderError::from_bits(u32::from_le_bytes(encoder.data[0..4].try_into().unwrap()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, time::Duration};
+
+    use crate::{
+        canproxy::CANProxy,
+        error::{AxisError, MotorError},
+        odrivegroup::ODriveGroup,
+        simulator::{ODriveSimulator, SimulationParams},
+        state::AxisState::*,
+        tests::wait_for_msgs,
+        transition::TransitionOutcome,
+    };
+
+    use super::{CalibrationEvent, CalibrationOptions};
+
+    #[test]
+    fn test_calibrate_group() {
+        let params = SimulationParams {
+            calibration_time: Duration::from_millis(100),
+            heartbeat_interval: Some(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let simulator = ODriveSimulator::with_params(&[0, 1, 2], params);
+        simulator.with_node(2, |node| {
+            node.axis_error = AxisError::DrvFault as u32;
+            node.motor_error = MotorError::DRVFault as u64 | MotorError::PhaseResistanceOFR as u64;
+        });
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1, 2]);
+            let options = CalibrationOptions {
+                max_concurrent: Some(2),
+                step_timeout: Duration::from_secs(1),
+                ..Default::default()
+            };
+            let mut events = vec![];
+            let report = odrives.calibrate(&options, |event| match event {
+                CalibrationEvent::StepStarted { axis_id, step } => events.push((*axis_id, step.clone())),
+                CalibrationEvent::StepFinished { .. } => {}
+            });
+            send.send((report, events)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let (report, events) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        // The first two axes run through every step before the last one starts
+        assert_eq!(events, vec![
+            (0, MotorCalibration), (1, MotorCalibration),
+            (0, EncoderIndexSearch), (1, EncoderIndexSearch),
+            (0, EncoderOffsetCalib), (1, EncoderOffsetCalib),
+            (2, MotorCalibration),
+        ]);
+
+        assert!(!report.succeeded());
+        assert_eq!(report.failed_axes(), vec![2]);
+        assert_eq!(report.axes[&0].completed, vec![MotorCalibration, EncoderIndexSearch, EncoderOffsetCalib]);
+        assert!(simulator.node(1).unwrap().encoder_ready);
+
+        let failure = report.axes[&2].failure.as_ref().unwrap();
+        assert_eq!(failure.step, MotorCalibration);
+        assert!(matches!(failure.outcome, TransitionOutcome::Failed { .. }));
+        assert_eq!(failure.motor_errors, vec![MotorError::PhaseResistanceOFR, MotorError::DRVFault]);
+        assert!(failure.encoder_errors.is_empty());
+        assert!(report.to_string().starts_with("calibrated 2 of 3 axes"));
+    }
+}
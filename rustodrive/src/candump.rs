@@ -0,0 +1,409 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use socketcan::CANFrame;
+
+use crate::cansocket::CanTransport;
+
+/// The largest CAN ID that fits in a standard (non extended) frame
+const SFF_MASK: u32 = 0x7FF;
+
+/// A single line of a `candump -l` log file, for example
+/// `(1436509052.249713) can0 02C#0000803F00000000`
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    /// Time since the unix epoch that the frame was seen on the bus
+    pub timestamp: Duration,
+    pub interface: String,
+    pub frame: CANFrame,
+}
+
+impl LogEntry {
+    /// Formats the entry as a line in the `candump -l` log format (without a newline)
+    pub fn to_line(&self) -> String {
+        let id = match self.frame.is_extended() || self.frame.id() > SFF_MASK {
+            true => format!("{:08X}", self.frame.id()),
+            false => format!("{:03X}", self.frame.id()),
+        };
+
+        // Remote frames have no data, but candump keeps track of the requested length
+        let payload = match self.frame.is_rtr() {
+            true if self.frame.data().is_empty() => "R".to_string(),
+            true => format!("R{:X}", self.frame.data().len()),
+            false => self.frame.data().iter().map(|byte| format!("{:02X}", byte)).collect(),
+        };
+
+        format!(
+            "({}.{:06}) {} {}#{}",
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_micros(),
+            self.interface,
+            id,
+            payload
+        )
+    }
+
+    /// Parses a line in the `candump -l` log format
+    pub fn from_line(line: &str) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid candump line: {}", line));
+
+        let mut parts = line.split_whitespace();
+        let (timestamp, interface, frame) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(timestamp), Some(interface), Some(frame)) => (timestamp, interface, frame),
+            _ => return Err(invalid()),
+        };
+
+        // (seconds.microseconds)
+        let timestamp = timestamp
+            .strip_prefix('(')
+            .and_then(|ts| ts.strip_suffix(')'))
+            .and_then(|ts| ts.split_once('.'))
+            .and_then(|(secs, micros)| Some((secs.parse::<u64>().ok()?, micros.parse::<u32>().ok()?)))
+            .map(|(secs, micros)| Duration::new(secs, micros * 1000))
+            .ok_or_else(invalid)?;
+
+        // ID#DATA or ID#R for remote frames
+        let (id, payload) = frame.split_once('#').ok_or_else(invalid)?;
+        let id = u32::from_str_radix(id, 16).map_err(|_| invalid())?;
+
+        let (data, rtr) = match payload.strip_prefix('R') {
+            Some(len) => {
+                let len = match len {
+                    "" => 0,
+                    len => usize::from_str_radix(len, 16).map_err(|_| invalid())?,
+                };
+                (vec![0; len.min(8)], true)
+            }
+            None if payload.len() % 2 == 0 => {
+                let data = (0..payload.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&payload[i..i + 2], 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .map_err(|_| invalid())?;
+                (data, false)
+            }
+            None => return Err(invalid()),
+        };
+
+        Ok(Self {
+            timestamp,
+            interface: interface.to_string(),
+            frame: CANFrame::new(id, &data, rtr, false).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A [`CanTransport`] that passes everything through to another transport and writes
+/// every frame that is sent and received to a log in the `candump -l` format.
+///
+/// The resulting file can be inspected with the can-utils (`canplayer`, `log2asc`, ...)
+/// or fed back into a [`CANProxy`](crate::canproxy::CANProxy) with a [`ReplayTransport`].
+///
+/// # Example
+/// ```no_run
+/// use rustodrive::canproxy::CANProxy;
+/// use rustodrive::candump::RecordingTransport;
+/// use rustodrive::cansocket::CanTransport;
+///
+/// let socket = socketcan::CANSocket::open("can0").unwrap();
+/// let recorder = RecordingTransport::create(socket, "can0", "candump-field-test.log").unwrap();
+/// let mut can_proxy = CANProxy::with_transport(recorder);
+/// ```
+pub struct RecordingTransport<T: CanTransport> {
+    inner: T,
+    interface: String,
+    log: Mutex<Box<dyn Write + Send>>,
+}
+
+impl<T: CanTransport> RecordingTransport<T> {
+    /// Records the traffic of `inner` into `log`, naming the interface `interface` in every line
+    pub fn new<W: Write + Send + 'static>(inner: T, interface: &str, log: W) -> Self {
+        Self {
+            inner,
+            interface: interface.to_string(),
+            log: Mutex::new(Box::new(log)),
+        }
+    }
+
+    /// Records the traffic of `inner` into a newly created file at `path`
+    pub fn create<P: AsRef<Path>>(inner: T, interface: &str, path: P) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::new(inner, interface, LineWriter::new(file)))
+    }
+
+    fn record(&self, frame: &CANFrame) -> io::Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let entry = LogEntry {
+            timestamp,
+            interface: self.interface.clone(),
+            frame: *frame,
+        };
+        writeln!(self.log.lock().unwrap(), "{}", entry.to_line())
+    }
+}
+
+impl<T: CanTransport> CanTransport for RecordingTransport<T> {
+    /// Opens `T` on the interface and records to a file named `candump-<ifname>.log`
+    fn open(ifname: &str) -> io::Result<Self> {
+        Self::create(T::open(ifname)?, ifname, format!("candump-{}.log", ifname))
+    }
+
+    // Failing to write to the log must not look like the frame failed on the bus,
+    // otherwise the proxy would send it again, so recording is best effort
+    fn write_frame(&self, frame: &CANFrame) -> io::Result<()> {
+        self.inner.write_frame(frame)?;
+        self.record(frame).ok();
+        Ok(())
+    }
+
+    fn read_frame(&self) -> io::Result<CANFrame> {
+        let frame = self.inner.read_frame()?;
+        self.record(&frame).ok();
+        Ok(frame)
+    }
+
+    fn set_read_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.inner.set_write_timeout(timeout)
+    }
+}
+
+struct ReplayState {
+    entries: VecDeque<LogEntry>,
+    /// The wall clock time and log time when the first frame was read
+    start: Option<(Instant, Duration)>,
+}
+
+/// A [`CanTransport`] that plays back a `candump -l` log, for example one written by a
+/// [`RecordingTransport`], so that past data can be used for testing without any ODrives.
+///
+/// Frames become available to read at the same pace they were logged, scaled by the
+/// replay speed, starting from the first call to `read_frame`. Remote (RTR) frames are
+/// skipped since those are requests, not data sent by the ODrives. Frames that are written
+/// are accepted but go nowhere.
+///
+/// # Example
+/// ```no_run
+/// use rustodrive::canproxy::CANProxy;
+/// use rustodrive::candump::ReplayTransport;
+///
+/// // Play back the log ten times faster than it was recorded
+/// let replay = ReplayTransport::from_file("candump-field-test.log").unwrap().with_speed(10.0).unwrap();
+/// let mut can_proxy = CANProxy::with_transport(replay);
+/// ```
+pub struct ReplayTransport {
+    speed: f64,
+    state: Mutex<ReplayState>,
+}
+
+impl ReplayTransport {
+    /// Replays the given entries in real time
+    pub fn new(entries: Vec<LogEntry>) -> Self {
+        let entries = entries.into_iter().filter(|entry| !entry.frame.is_rtr()).collect();
+        Self {
+            speed: 1.0,
+            state: Mutex::new(ReplayState { entries, start: None }),
+        }
+    }
+
+    /// Reads all entries from a `candump -l` log. Empty lines are ignored
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(LogEntry::from_line(&line)?);
+            }
+        }
+        Ok(Self::new(entries))
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Sets how many times faster than real time the log is played back.
+    /// `f64::INFINITY` makes every frame available immediately. Speeds that are not
+    /// positive would never play anything back, so they are rejected with `InvalidInput`
+    pub fn with_speed(mut self, speed: f64) -> io::Result<Self> {
+        if speed.is_nan() || speed <= 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the replay speed must be positive, got {}", speed),
+            ));
+        }
+        self.speed = speed;
+        Ok(self)
+    }
+
+    /// Whether every frame in the log has been read
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().unwrap().entries.is_empty()
+    }
+}
+
+impl CanTransport for ReplayTransport {
+    /// Opens the log file at the path `ifname`
+    fn open(ifname: &str) -> io::Result<Self> {
+        Self::from_file(ifname)
+    }
+
+    fn write_frame(&self, _frame: &CANFrame) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn read_frame(&self) -> io::Result<CANFrame> {
+        let mut state = self.state.lock().unwrap();
+        let next_timestamp = match state.entries.front() {
+            Some(entry) => entry.timestamp,
+            None => return Err(io::Error::new(io::ErrorKind::WouldBlock, "replay finished")),
+        };
+
+        let (started, first_timestamp) = *state.start.get_or_insert((Instant::now(), next_timestamp));
+        let log_elapsed = next_timestamp.saturating_sub(first_timestamp);
+        let due = self.speed.is_infinite() || started.elapsed().as_secs_f64() * self.speed >= log_elapsed.as_secs_f64();
+
+        match due {
+            true => Ok(state.entries.pop_front().unwrap().frame),
+            false => Err(io::Error::new(io::ErrorKind::WouldBlock, "no messages available")),
+        }
+    }
+
+    fn set_read_timeout(&self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_write_timeout(&self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Cursor, Write},
+        sync::{mpsc::channel, Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use socketcan::CANFrame;
+
+    use crate::{
+        canframe::CANRequest,
+        canproxy::CANProxy,
+        cansocket::{CanTransport, MockCANSocket},
+        state::{ODriveCommand, ReadComm},
+        tests::wait_for_msgs,
+    };
+
+    use super::{LogEntry, RecordingTransport, ReplayTransport};
+
+    /// A log that can still be read after it was given to the recorder
+    #[derive(Clone, Default)]
+    struct SharedLog(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedLog {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_line_format() {
+        let entry = LogEntry::from_line("(1436509052.249713) can0 02C#0000803F00000000").unwrap();
+        assert_eq!(entry.timestamp, Duration::new(1436509052, 249713000));
+        assert_eq!(entry.interface, "can0");
+        assert_eq!(entry.frame.id(), 0x2C);
+        assert_eq!(entry.frame.data(), [0, 0, 0x80, 0x3F, 0, 0, 0, 0]);
+        assert_eq!(entry.to_line(), "(1436509052.249713) can0 02C#0000803F00000000");
+
+        let remote = LogEntry::from_line("(1.000001) vcan0 029#R8").unwrap();
+        assert!(remote.frame.is_rtr());
+        assert_eq!(remote.to_line(), "(1.000001) vcan0 029#R8");
+
+        let extended = LogEntry::from_line("(0.000000) can1 12345678#").unwrap();
+        assert!(extended.frame.data().is_empty());
+        assert_eq!(extended.to_line(), "(0.000000) can1 12345678#");
+
+        assert!(LogEntry::from_line("(0.0) can0 123#ABC").is_err());
+        assert!(LogEntry::from_line("can0 123#AB").is_err());
+    }
+
+    #[test]
+    /// Every frame sent and received through the proxy is recorded
+    /// and can be played back into another proxy
+    fn test_record_and_replay() {
+        let log = SharedLog::default();
+        let recorder = RecordingTransport::new(MockCANSocket::new(), "can0", log.clone());
+        let mut can_proxy = CANProxy::with_transport(recorder);
+
+        let request = CANRequest {
+            axis: 3,
+            cmd: ODriveCommand::Read(ReadComm::GetVBusVoltage),
+            data: [0; 8],
+        };
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            send.send(can_rw.request(request)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        wait_for_msgs(rcv).unwrap();
+        stop_proxy().unwrap();
+
+        let contents = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("can0 077#R8"));
+        assert!(lines[1].ends_with("can0 077#6363636363636363"));
+
+        // Only the response is played back since the request is a remote frame
+        let replay = ReplayTransport::from_reader(Cursor::new(contents)).unwrap();
+        let frame = replay.read_frame().unwrap();
+        assert_eq!(frame.data(), [99; 8]);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn test_replay_speed() {
+        let entries: Vec<LogEntry> = (0..3u8)
+            .map(|i| LogEntry {
+                timestamp: Duration::from_millis(100) * i as u32 + Duration::from_secs(1000),
+                interface: "can0".to_string(),
+                frame: CANFrame::new(0x29, &[i; 8], false, false).unwrap(),
+            })
+            .collect();
+
+        // Twice the speed means the last frame logged 200ms after the first arrives after 100ms
+        let replay = ReplayTransport::new(entries).with_speed(2.0).unwrap();
+        let start = Instant::now();
+        let mut received = Vec::new();
+        while !replay.is_finished() {
+            if let Ok(frame) = replay.read_frame() {
+                received.push(frame.data()[0]);
+            }
+        }
+
+        let elapsed = start.elapsed();
+        assert_eq!(received, vec![0, 1, 2]);
+        assert!(elapsed >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_replay_speed_not_positive() {
+        for speed in [0.0, -1.0, f64::NAN] {
+            let err = ReplayTransport::new(Vec::new()).with_speed(speed).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
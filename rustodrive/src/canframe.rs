@@ -1,13 +1,17 @@
 use crate::state::{self, ReadComm};
 use crate::state::ODriveCommand;
+use crate::Error;
 use socketcan::CANFrame;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, SystemTime};
 
 pub type CANRequest = ODriveCANFrame;
 pub type CANResponse = ODriveCANFrame;
 
 //CAN Ticket is a CAN command factory ready to be sent to the axis
 pub fn ticket(id: usize, command: ODriveCommand, data: [u8; 8]) -> CANRequest {
-    CANRequest { axis: id as u32, cmd: command, data: data }
+    CANRequest { axis: id as u32, cmd: command, data }
 }
 
 pub fn read_can(id: usize, command: ReadComm) -> CANRequest {
@@ -24,11 +28,11 @@ pub struct ODriveCANFrame {
 }
 
 impl ODriveCANFrame {
-    const AXIS_BITS: u32 = 5;
+    pub(crate) const AXIS_BITS: u32 = 5;
 
     pub fn to_can(&self, rtr: bool) -> socketcan::CANFrame {
         let id = self.axis << Self::AXIS_BITS | self.get_cmd_id();
-        return socketcan::CANFrame::new(id, &self.data, rtr, false).unwrap(); // ODrive rquires the RTR bitset be enabled for call/response
+        socketcan::CANFrame::new(id, &self.data, rtr, false).unwrap() // ODrive rquires the RTR bitset be enabled for call/response
     }
 
     fn get_cmd_id(&self) -> u32 {
@@ -38,35 +42,33 @@ impl ODriveCANFrame {
         }
     }
 
-    fn to_cmd(can_id: u32) -> ODriveCommand {
+    fn to_cmd(can_id: u32) -> Result<ODriveCommand, Error> {
         // 0x1F is 00011111 in binary. Take the last 5 bits to get the command
         let cmd_id = can_id & 0x1F;
 
         // Then try converting to a write command
-        match TryInto::<state::WriteComm>::try_into(cmd_id) {
-            Ok(cmd) => return ODriveCommand::Write(cmd),
-            Err(_) => {}
+        if let Ok(cmd) = TryInto::<state::WriteComm>::try_into(cmd_id) {
+            return Ok(ODriveCommand::Write(cmd));
         }
 
         // Try first converting to a read command
         match TryInto::<state::ReadComm>::try_into(cmd_id) {
-            Ok(cmd) => return ODriveCommand::Read(cmd),
-            Err(_) => panic!("CAN ID {} not able to be converted to a command", can_id),
+            Ok(cmd) => Ok(ODriveCommand::Read(cmd)),
+            Err(_) => Err(Error::UnknownCommand(can_id)),
         }
     }
 
-    pub fn from_can(frame: &CANFrame) -> Self {
+    /// Converts a frame read from the CAN bus. This fails if the frame is not an ODrive message
+    pub fn from_can(frame: &CANFrame) -> Result<Self, Error> {
         // Get the first 5 bits
         let axis = frame.id() >> Self::AXIS_BITS;
-        let cmd = Self::to_cmd(frame.id());
+        let cmd = Self::to_cmd(frame.id())?;
 
-        ODriveCANFrame {
+        Ok(ODriveCANFrame {
             axis,
             cmd,
-            data: frame.data().try_into().expect(
-                "Error with CANFrame. data() returned slice that could not be coerced into [u8; 8]",
-            ),
-        }
+            data: frame.data().try_into().map_err(|_| Error::BadFrameLength(frame.data().len()))?,
+        })
     }
 
     // If the command and axis IDs match, then it must be the response
@@ -80,6 +82,102 @@ impl ODriveCANFrame {
 pub struct ThreadCANFrame {
     pub thread_name: &'static str,
     pub body: ODriveCANFrame,
+    /// How long to wait for a response before giving up. If this is `None`
+    /// the timeout configured on the `CANProxy` is used
+    pub timeout: Option<Duration>,
+    /// Identifies this request among all others sent to the `CANProxy`, for example to cancel it
+    pub id: u64,
+}
+
+impl ThreadCANFrame {
+    /// Wraps the frame and gives it an id that no other request has
+    pub fn new(thread_name: &'static str, body: ODriveCANFrame, timeout: Option<Duration>) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self { thread_name, body, timeout, id: NEXT_ID.fetch_add(1, Ordering::Relaxed) }
+    }
+}
+
+/// Selects which frames received from the CAN bus a subscription receives.
+/// A field that is `None` matches anything.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Hash)]
+pub struct FrameFilter {
+    pub axis: Option<u32>,
+    pub cmd: Option<ReadComm>,
+}
+
+impl FrameFilter {
+    /// Matches every frame that is received
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Matches every frame sent by the given axis
+    pub fn axis(axis: u32) -> Self {
+        Self { axis: Some(axis), cmd: None }
+    }
+
+    /// Matches the given message from every axis, e.g. all heartbeats
+    pub fn cmd(cmd: ReadComm) -> Self {
+        Self { axis: None, cmd: Some(cmd) }
+    }
+
+    pub fn matches(&self, frame: &ODriveCANFrame) -> bool {
+        let axis_matches = self.axis.is_none_or(|axis| axis == frame.axis);
+        let cmd_matches = self.cmd.is_none_or(|cmd| frame.cmd == ODriveCommand::Read(cmd));
+        axis_matches && cmd_matches
+    }
+}
+
+/// Whether a frame observed by a bus monitor was sent to or received from the CAN bus
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+/// A copy of a frame that passed through the `CANProxy`, as delivered to bus monitors
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BusEvent {
+    pub direction: Direction,
+    /// The thread that sent the frame, or that an incoming frame answered.
+    /// This is `None` for frames the ODrives broadcast on their own
+    pub thread_name: Option<&'static str>,
+    pub timestamp: SystemTime,
+    pub frame: ODriveCANFrame,
+}
+
+/// Where the `CANProxy` sends the response to a request
+#[derive(Debug)]
+pub enum Reply {
+    /// The response channel of the registered thread that sent the request
+    Thread,
+    /// The channel of a [`PendingRequest`](crate::threads::PendingRequest) handle
+    Channel(Sender<crate::response::ODriveResponse>),
+    /// Nobody waits for the response. Only errors are sent, to the channel the thread
+    /// collects the errors of its [`send()`](crate::threads::ReadWriteCANThread::send) calls in
+    Errors(Sender<crate::response::ErrorResponse>),
+    /// The future returned by an [`AsyncCANThread`](crate::asyncthread::AsyncCANThread) request
+    #[cfg(feature = "async")]
+    Future(futures::channel::oneshot::Sender<crate::response::ODriveResponse>),
+}
+
+/// These are the messages a registered thread sends to the `CANProxy`
+#[derive(Debug)]
+pub enum ThreadMessage {
+    /// Sends the frame to the CAN bus and replies once it was sent or answered
+    Request(ThreadCANFrame, Reply),
+    /// Forwards a copy of every received frame that matches the filter to the sender,
+    /// until the receiving end is dropped
+    Subscribe { filter: FrameFilter, sender: Sender<CANResponse> },
+    /// Forwards a [`BusEvent`] for every frame written to or read from the CAN bus
+    /// to the sender, until the receiving end is dropped
+    Monitor(Sender<BusEvent>),
+    /// Stops waiting for the request with the given id. No response is sent for it
+    Cancel(u64),
+    /// A frame read from the CAN bus by the proxy's reader thread. This is only sent
+    /// by the `CANProxy` itself so that it can wait for frames and requests at the same time
+    #[doc(hidden)]
+    Received(CANFrame),
 }
 
 
@@ -90,7 +188,8 @@ mod tests {
         canframe::{CANRequest, CANResponse},
     };
 
-    use super::ODriveCANFrame;
+    use super::{FrameFilter, ODriveCANFrame};
+    use crate::Error;
 
     #[test]
     fn test_conversion_to_frame() {
@@ -116,18 +215,20 @@ mod tests {
 
         // Test it converts back properly
         assert_eq!(msg1, msg1.clone()); // test that equals works
-        assert_eq!(msg1, ODriveCANFrame::from_can(&can_frame));
+        assert_eq!(msg1, ODriveCANFrame::from_can(&can_frame).unwrap());
 
         // test that conversion works with read messages
         // Converting from CANFrame to ODriveCANFrame ignores
         //the rtr bit so either true/false is fine
-        assert_eq!(msg2, ODriveCANFrame::from_can(&msg2.to_can(true)));
+        assert_eq!(msg2, ODriveCANFrame::from_can(&msg2.to_can(true)).unwrap());
     }
 
     #[test]
-    #[should_panic]
     fn test_command_not_found() {
-        ODriveCANFrame::to_cmd(0xFFFF);
+        assert!(matches!(ODriveCANFrame::to_cmd(0xFFFF), Err(Error::UnknownCommand(0xFFFF))));
+
+        let short_frame = socketcan::CANFrame::new(0x2C, &[0; 4], false, false).unwrap();
+        assert!(matches!(ODriveCANFrame::from_can(&short_frame), Err(Error::BadFrameLength(4))));
     }
 
     #[test]
@@ -142,6 +243,22 @@ mod tests {
             cmd: ODriveCommand::Write(WriteComm::SetInputPosition), // this is cmd id 0x0C
             data: [1; 8], // the data has changed but the rest is the same
         };
-        assert_eq!(msg1.is_response(&fake_response), true);
+        assert!(msg1.is_response(&fake_response));
+    }
+
+    #[test]
+    fn test_frame_filter() {
+        let heartbeat = CANResponse {
+            axis: 3,
+            cmd: ODriveCommand::Read(ReadComm::GetHeartbeat),
+            data: [0; 8],
+        };
+
+        assert!(FrameFilter::all().matches(&heartbeat));
+        assert!(FrameFilter::axis(3).matches(&heartbeat));
+        assert!(!FrameFilter::axis(2).matches(&heartbeat));
+        assert!(FrameFilter::cmd(ReadComm::GetHeartbeat).matches(&heartbeat));
+        assert!(!FrameFilter::cmd(ReadComm::GetIQ).matches(&heartbeat));
+        assert!(!FrameFilter { axis: Some(3), cmd: Some(ReadComm::GetIQ) }.matches(&heartbeat));
     }
 }
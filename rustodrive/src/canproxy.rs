@@ -1,65 +1,209 @@
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::cansocket::CANSocket;
+use socketcan::CANFrame;
+
+use crate::cansocket::{CanTransport, DefaultTransport};
 use crate::state::ODriveCommand;
-use crate::canframe::{CANResponse, ThreadCANFrame, ODriveCANFrame};
+use crate::canframe::{BusEvent, CANResponse, Direction, ThreadCANFrame, ODriveCANFrame, FrameFilter, Reply, ThreadMessage};
 use crate::response::{ODriveResponse, ResponseType, ErrorResponse, ODriveError};
+use crate::Error;
 use crate::threads::{ReadOnlyCANThread, ReadWriteCANThread};
+use crate::supervisor::{self, SupervisorConfig, SupervisorHandle};
+use crate::watchdog::{self, WatchdogConfig, WatchdogHandle};
+#[cfg(feature = "async")]
+use crate::asyncthread::AsyncCANThread;
 
 type ThreadConnection = (JoinHandle<()>, Sender<ODriveResponse>);
 type ThreadID = &'static str;
-pub enum ProxyError {
-    ThreadFailedJoin,
-    ThreadDuplicateID,
+
+/// The amount of time a `Read` request waits for a response from the ODrive
+/// before the proxy gives up on it, unless a timeout is given for the request itself
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// The longest the proxy loop started by [`CANProxy::begin()`] and its reader thread
+/// block while waiting for something to happen. This bounds how long it takes them
+/// to notice that the threads were stopped
+const IDLE_WAIT: Duration = Duration::from_millis(10);
+
+/// How long the reader thread waits before reading again when the transport returns
+/// immediately without a frame, so that it does not spin on transports without a read timeout
+const READ_BACKOFF: Duration = Duration::from_micros(500);
+
+/// How long the proxy waits before sending a frame again that failed to write to the CAN bus.
+/// The wait doubles with every further failure of the same frame, up to [`MAX_SEND_BACKOFF`]
+const SEND_BACKOFF: Duration = Duration::from_millis(1);
+const MAX_SEND_BACKOFF: Duration = Duration::from_millis(64);
+
+/// Configures how many times the [`CANProxy`] attempts a request again before
+/// responding to the thread with an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a frame that could not be written to the CAN bus is resent
+    /// before responding with [`ODriveError::FailedToSend`]
+    pub send_retries: u32,
+    /// How many times a `Read` request is resent after its deadline passed without
+    /// a response before responding with [`ODriveError::NoResponse`]
+    pub response_retries: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            send_retries: 2,
+            response_retries: 1,
+        }
+    }
+}
+
+/// A request from a thread that the proxy is still responsible for, either because
+/// it failed to send or because it is waiting for a response from the CAN bus
+struct QueuedRequest {
+    frame: ThreadCANFrame,
+    reply: Reply,
+    /// When the request times out while waiting for a response, or when it is
+    /// sent again after it failed to send
+    deadline: Instant,
+    failed_sends: u32,
+    timeouts: u32,
+    /// Identical requests that arrived while this one was in flight. They are
+    /// responded to with the same response instead of being sent again
+    coalesced: Vec<(ThreadCANFrame, Reply)>,
 }
 
 /// The CANProxy is in charge of handling all communication with the CAN
 /// port on behalf of all threads that are registered to it.
 pub struct CANProxy {
-    mpsc_channel: (Sender<ThreadCANFrame>, Receiver<ThreadCANFrame>),
+    mpsc_channel: (Sender<ThreadMessage>, Receiver<ThreadMessage>),
     threads: HashMap<ThreadID, ThreadConnection>,
     rw_thread: Option<ThreadID>, // There can only be one read and write thread at a time. Store the identifier in here
+    async_handle: Option<ThreadID>, // The name of the async handle, which takes the place of the read-write thread without a thread of its own
     threads_alive: Arc<AtomicBool>,
-    requests: Vec<ThreadCANFrame>,
-    socket: CANSocket,
+    requests: Vec<QueuedRequest>,
+    failed_requests: Vec<QueuedRequest>,
+    subscriptions: Vec<(FrameFilter, Sender<CANResponse>)>,
+    monitors: Vec<Sender<BusEvent>>,
+    timeout: Duration,
+    retry_policy: RetryPolicy,
+    socket: Arc<dyn CanTransport>,
 }
 
 impl CANProxy {
     /// Instantiates a new CANProxy. Only one CANProxy should be instantiated at a time
+    ///
+    /// This opens a [`socketcan::CANSocket`], or a [`MockCANSocket`](crate::cansocket::MockCANSocket)
+    /// if the `mock-socket` feature is enabled. Use [`CANProxy::with_transport()`] to
+    /// choose the transport at runtime instead.
+    /// This returns [`Error::OpenFailed`] if the CAN port could not be opened.
     /// # Arguments
     /// * `can_device` - a string slice to the CAN port name
-    pub fn new(can_device: &str) -> Self {
+    pub fn new(can_device: &str) -> Result<Self, Error> {
         // Initialize CANSocket
-        let socket = CANSocket::open(can_device).expect("Could not open CAN at can1");
+        match <DefaultTransport as CanTransport>::open(can_device) {
+            Ok(socket) => Ok(Self::with_transport(socket)),
+            Err(source) => Err(Error::OpenFailed { interface: can_device.to_string(), source }),
+        }
+    }
+
+    /// Instantiates a new CANProxy that sends and receives frames through the given
+    /// [`CanTransport`] instead of opening a CAN socket.
+    ///
+    /// ## Example
+    /// ```
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::cansocket::MockCANSocket;
+    ///
+    /// let mut can_proxy = CANProxy::with_transport(MockCANSocket::new());
+    /// can_proxy.register_ro("thread 1", |can_read| {}).unwrap();
+    /// ```
+    pub fn with_transport<T: CanTransport + 'static>(transport: T) -> Self {
+        let socket = Arc::new(transport);
 
         // Define the channel for the proxy here
-        let mpsc_channel = channel::<ThreadCANFrame>();
+        let mpsc_channel = channel::<ThreadMessage>();
 
         Self {
             mpsc_channel,
             socket,
             rw_thread: None,
+            async_handle: None,
             threads: HashMap::new(),
             requests: vec![],
+            failed_requests: vec![],
+            subscriptions: vec![],
+            monitors: vec![],
+            timeout: DEFAULT_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
             threads_alive: Arc::new(AtomicBool::new(true)),
         }
     }
 
+    /// Sets how long a `Read` request waits for a response before it is retried or
+    /// answered with [`ODriveError::NoResponse`]. This applies to every request
+    /// that was not sent with its own timeout (see [`ReadWriteCANThread::request_with_timeout()`]).
+    ///
+    /// By default this is [`DEFAULT_TIMEOUT`]
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sets how many times failed sends and requests without a response are
+    /// attempted again before the thread is responded to with an error.
+    ///
+    /// ## Example
+    /// ```
+    /// use std::time::Duration;
+    /// use rustodrive::canproxy::{CANProxy, RetryPolicy};
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.set_timeout(Duration::from_millis(50));
+    /// can_proxy.set_retry_policy(RetryPolicy { send_retries: 5, response_retries: 2 });
+    /// ```
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Returns a receiver that gets a [`BusEvent`] for every frame the proxy writes to or
+    /// reads from the CAN bus, including the commands sent by the read-write thread.
+    /// Registered threads can do the same with `monitor()` on their handle.
+    ///
+    /// ## Example
+    /// ```
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::canframe::Direction;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// let bus_events = can_proxy.monitor();
+    ///
+    /// let stop_threads = can_proxy.begin();
+    /// for event in bus_events.try_iter() {
+    ///     if event.direction == Direction::Outgoing {
+    ///         println!("{:?} sent {:?}", event.thread_name, event.frame);
+    ///     }
+    /// }
+    /// stop_threads().unwrap();
+    /// ```
+    pub fn monitor(&mut self) -> Receiver<BusEvent> {
+        let (sender, receiver) = channel();
+        self.monitors.push(sender);
+        receiver
+    }
+
     /// This registers a new thread that is given a handle with read and write
     /// access to CAN (in this case [`ReadWriteCANThread`])
     ///
-    /// Only one thread can have read and write access. This function will
-    /// panic if multiple threads are registered as read and write.
+    /// Only one thread can have read and write access. This function returns
+    /// [`Error::ReadWriteTaken`] if multiple threads are registered as read and write.
     ///
     /// # Arguments
     /// * `thread_name` - a unique identifier to refer to that thread you registered
-    /// so that you can unregister it. Registering panics if a duplicate name exists
+    ///   so that you can unregister it. Registering fails with [`Error::DuplicateThread`] if a duplicate name exists
     /// * `thread_func` - this is a closure that is the entry point to code execution
-    /// in the separate thread. What is contained must implement the `Send` trait.
+    ///   in the separate thread. What is contained must implement the `Send` trait.
     ///
     /// The closure takes one argument which is a [`ReadWriteCANThread`] object
     ///
@@ -69,7 +213,7 @@ impl CANProxy {
     /// use rustodrive::canframe::CANRequest;
     /// use rustodrive::state::{ODriveCommand::Read, ReadComm};
     ///
-    /// let mut can_proxy = CANProxy::new("can0");
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
     /// can_proxy.register_rw("thread 1", |can_read_write| {
     ///     // .request() blocks until a response is received
     ///     can_read_write.request(CANRequest {
@@ -77,7 +221,7 @@ impl CANProxy {
     ///         cmd: Read(ReadComm::GetVBusVoltage),
     ///         data: [0; 8]
     ///     });
-    /// });
+    /// }).unwrap();
     ///
     /// // start processing of messages on a separate thread
     /// let stop_threads = can_proxy.begin();
@@ -87,16 +231,13 @@ impl CANProxy {
     /// // and wait for them to join with the hook given by .begin()
     /// stop_threads().unwrap();
     /// ```
-    pub fn register_rw<F>(&mut self, thread_name: &'static str, thread_func: F)
+    pub fn register_rw<F>(&mut self, thread_name: &'static str, thread_func: F) -> Result<(), Error>
     where
         F: FnOnce(ReadWriteCANThread) + std::marker::Send + 'static,
     {
         match self.rw_thread {
-            Some(_thread_id) => {
-                panic!("Cannot register more than 1 thread to have write access to CAN device")
-            }
+            Some(_thread_id) => Err(Error::ReadWriteTaken),
             None => {
-                self.rw_thread = Some(thread_name);
                 let threads_alive_cloned = self.threads_alive.clone();
 
                 self.register(thread_name, move |thread_requester, thread_receiver| {
@@ -106,7 +247,51 @@ impl CANProxy {
                         thread_receiver,
                         threads_alive_cloned,
                     ))
-                });
+                })?;
+                self.rw_thread = Some(thread_name);
+                Ok(())
+            }
+        }
+    }
+
+    /// This returns a handle with read and write access to CAN whose requests return
+    /// futures instead of blocking, so that the ODrives can be controlled from async tasks.
+    /// No thread is started for it; the handle can be cloned and moved into any task.
+    ///
+    /// The handle takes the place of the read-write thread, so this returns [`Error::ReadWriteTaken`]
+    /// if a read-write thread or async handle was already registered. Unregistering the name
+    /// frees the place for another read-write thread.
+    ///
+    /// # Arguments
+    /// * `thread_name` - the name the requests made through the handle are tagged with. It shares
+    ///   the names of the registered threads, so this fails with [`Error::DuplicateThread`] if it is taken
+    ///
+    /// # Example
+    /// ```
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::canframe::CANRequest;
+    /// use rustodrive::state::{ODriveCommand::Read, ReadComm};
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// let can_async = can_proxy.register_async("async tasks").unwrap();
+    /// let stop_threads = can_proxy.begin();
+    ///
+    /// let response = futures::executor::block_on(can_async.request(CANRequest {
+    ///     axis: 1,
+    ///     cmd: Read(ReadComm::GetVBusVoltage),
+    ///     data: [0; 8]
+    /// }));
+    /// stop_threads().unwrap();
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn register_async(&mut self, thread_name: &'static str) -> Result<AsyncCANThread, Error> {
+        match self.rw_thread {
+            Some(_thread_id) => Err(Error::ReadWriteTaken),
+            None if self.threads.contains_key(thread_name) => Err(Error::DuplicateThread(thread_name)),
+            None => {
+                self.rw_thread = Some(thread_name);
+                self.async_handle = Some(thread_name);
+                Ok(AsyncCANThread::new(thread_name, self.mpsc_channel.0.clone(), self.threads_alive.clone()))
             }
         }
     }
@@ -118,9 +303,9 @@ impl CANProxy {
     ///
     /// # Arguments
     /// * `thread_name` - a unique identifier to refer to that thread you registered
-    /// so that you can unregister it. Registering panics if a duplicate name exists
+    ///   so that you can unregister it. Registering fails with [`Error::DuplicateThread`] if a duplicate name exists
     /// * `thread_func` - this is a closure that is the entry point to code execution
-    /// in the separate thread. What is contained must implement the `Send` trait.
+    ///   in the separate thread. What is contained must implement the `Send` trait.
     ///
     /// The closure takes one argument which is a [`ReadOnlyCANThread`] object
     /// # Example
@@ -128,13 +313,13 @@ impl CANProxy {
     /// use rustodrive::canproxy::CANProxy;
     /// use rustodrive::state::ReadComm;
     ///
-    /// let mut can_proxy = CANProxy::new("can0");
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
     /// can_proxy.register_ro("thread 1", |can_read| {
     ///     // .request() blocks until a response is received
     ///     let axis = 1;
     ///     let cmd = ReadComm::GetVBusVoltage;    
     ///     can_read.request(axis, cmd);
-    /// });
+    /// }).unwrap();
     ///
     /// // start processing of messages on a separate thread
     /// let stop_threads = can_proxy.begin();
@@ -144,7 +329,7 @@ impl CANProxy {
     /// // and wait for them to join with the hook given by .begin()
     /// stop_threads().unwrap();
     /// ```
-    pub fn register_ro<F>(&mut self, thread_name: &'static str, thread_func: F)
+    pub fn register_ro<F>(&mut self, thread_name: &'static str, thread_func: F) -> Result<(), Error>
     where
         F: FnOnce(ReadOnlyCANThread) + std::marker::Send + 'static,
     {
@@ -157,7 +342,79 @@ impl CANProxy {
                 thread_receiver,
                 threads_alive_cloned,
             ))
-        });
+        })
+    }
+
+    /// Registers a read-only thread that keeps the watchdogs of the configured axes fed by
+    /// sending each of them a keepalive every `config.interval`. The returned handle is for
+    /// the thread controlling the axes, which has to call [`WatchdogHandle::check_in()`]
+    /// regularly. If it stops checking in, the keepalives stop and the watchdogs on the
+    /// ODrives trip, putting the axes into a safe state.
+    ///
+    /// The ODrive watchdog has to be enabled with `axis.config.enable_watchdog` and
+    /// `axis.config.watchdog_timeout`. Missed deadlines are reported through
+    /// [`WatchdogHandle::events()`].
+    ///
+    /// ## Example
+    /// ```
+    /// use std::time::Duration;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::watchdog::WatchdogConfig;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// let config = WatchdogConfig::new(&[0, 1, 2, 3], Duration::from_millis(50));
+    /// let watchdog = can_proxy.register_watchdog("watchdog", config).unwrap();
+    /// can_proxy.register_rw("thread 1", move |can_rw| {
+    ///     while can_rw.is_alive() {
+    ///         watchdog.check_in();
+    ///         // control the axes
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn register_watchdog(&mut self, thread_name: &'static str, config: WatchdogConfig) -> Result<WatchdogHandle, Error> {
+        let last_check_in = Arc::new(Mutex::new(Instant::now()));
+        let (event_sender, events) = channel();
+
+        let service_check_in = last_check_in.clone();
+        self.register_ro(thread_name, move |can_read| {
+            watchdog::run(can_read, config, service_check_in, event_sender)
+        })?;
+        Ok(WatchdogHandle::new(last_check_in, events))
+    }
+
+    /// Registers a thread that watches the heartbeats and the axis, motor and encoder errors
+    /// of the configured axes. As soon as any of them faults, every axis is sent the
+    /// configured [`SafeAction`](crate::supervisor::SafeAction), `EStop` by default, and the
+    /// fault is reported through [`SupervisorHandle::faults()`].
+    ///
+    /// Unlike other read-only threads, the supervisor is allowed to send the safe action
+    /// itself, so the axes are stopped even if the read-write thread is stuck.
+    ///
+    /// ## Example
+    /// ```
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::supervisor::SupervisorConfig;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// let supervisor = can_proxy.register_supervisor("supervisor", SupervisorConfig::new(&[0, 1, 2, 3])).unwrap();
+    /// can_proxy.register_rw("thread 1", move |can_rw| {
+    ///     while can_rw.is_alive() && !supervisor.is_tripped() {
+    ///         // control the axes
+    ///     }
+    ///     if let Ok(fault) = supervisor.faults().try_recv() {
+    ///         println!("stopped all axes because of {:?}", fault);
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn register_supervisor(&mut self, thread_name: &'static str, config: SupervisorConfig) -> Result<SupervisorHandle, Error> {
+        let tripped = Arc::new(AtomicBool::new(false));
+        let (fault_sender, faults) = channel();
+
+        let service_tripped = tripped.clone();
+        self.register_ro(thread_name, move |can_read| {
+            supervisor::run(can_read, config, service_tripped, fault_sender)
+        })?;
+        Ok(SupervisorHandle::new(tripped, faults))
     }
 
     /// This is a helper function that does the bulk of the work to instantiate a
@@ -168,13 +425,13 @@ impl CANProxy {
     ///
     /// Additionally, the register function stores the join handle for the thread
     /// for joining all the threads at a later point.
-    fn register<F>(&mut self, thread_name: &'static str, func: F)
+    fn register<F>(&mut self, thread_name: &'static str, func: F) -> Result<(), Error>
     where
-        F: FnOnce(Sender<ThreadCANFrame>, Receiver<ODriveResponse>) + std::marker::Send + 'static,
+        F: FnOnce(Sender<ThreadMessage>, Receiver<ODriveResponse>) + std::marker::Send + 'static,
     {
         // Check that the thread ID does not exist already
-        if self.threads.contains_key(thread_name) {
-            panic!("Two threads cannot have the same id ({})", thread_name);
+        if self.threads.contains_key(thread_name) || self.async_handle == Some(thread_name) {
+            return Err(Error::DuplicateThread(thread_name));
         }
 
         // Thread <--- CANManager, sends ODriveResponse
@@ -184,26 +441,26 @@ impl CANProxy {
         // Give the thread the ability to send to the proxy
         // and receive from the proxy
         let thread_handle = std::thread::spawn(move || {
-            return func(thread_requester, thread_receiver);
+            func(thread_requester, thread_receiver)
         });
 
         // Add the thread and keep track of it
         self.threads
             .insert(thread_name, (thread_handle, thread_sender));
+        Ok(())
     }
 
     /// This unregisters a thread with the given identifier. If the thread fails
-    /// to `.join()`, the result gets propogated and the remaining threads
-    /// are not joined. 
+    /// to `.join()`, [`Error::ThreadPanicked`] is returned.
     /// 
-    /// This function panics if a thread that is not registered is unregistered.
+    /// This function returns [`Error::UnknownThread`] if a thread that is not registered is unregistered.
     ///
     /// ### Example 1
     /// ```
     /// use rustodrive::canproxy::CANProxy;
     ///
-    /// let mut can_proxy = CANProxy::new("can0");
-    /// can_proxy.register_ro("thread 1", |can_read| {});
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_ro("thread 1", |can_read| {}).unwrap();
     /// 
     /// can_proxy.unregister("thread 1").expect("thread 1 could did not join");
     /// ```
@@ -215,44 +472,45 @@ impl CANProxy {
     /// ```
     /// use rustodrive::canproxy::CANProxy;
     ///
-    /// let mut can_proxy = CANProxy::new("can0");
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
     /// can_proxy.register_ro("thread 1", |can_read| {
     ///     while can_read.is_alive() {
     ///         // do stuff
     ///     }
     ///     println!("Exit handled!");
-    /// });
+    /// }).unwrap();
     /// 
     /// can_proxy.stop_threads();
     /// can_proxy.unregister("thread 1").expect("thread 1 could did not join");
     /// ``` 
     /// ## Important note
     /// - Joining the thread does not return anything. This is because
-    /// a closure could potentially return anything. To rectify this, it would require
-    /// the use of generics and possibly `dyn Box` but currently this is not supported. 
-    pub fn unregister(&mut self, thread_name: &str) -> std::thread::Result<()> {
-        if self.threads.contains_key(thread_name) {
-            // unregister from the general thread connections
-            let (thread_handle, _sender) = self.threads.remove(thread_name).unwrap();
-            match thread_handle.join() {
-                Err(e) => return Err(e),
-                _ => {}
-            }
+    ///   a closure could potentially return anything. To rectify this, it would require
+    ///   the use of generics and possibly `dyn Box` but currently this is not supported.
+    pub fn unregister(&mut self, thread_name: &str) -> Result<(), Error> {
+        // The async handle has no thread to join
+        if self.async_handle == Some(thread_name) {
+            self.async_handle = None;
+            self.rw_thread = None;
+            return Ok(());
+        }
 
-            // If the thread being unregistered is the read-write thread, set it to none
-            match self.rw_thread {
-                Some(rw_thread_name) => {
-                    if rw_thread_name == thread_name {
-                        self.rw_thread = None
-                    }
-                }
-                _ => {}
-            }
+        // unregister from the general thread connections
+        let (thread_handle, _sender) = match self.threads.remove(thread_name) {
+            Some(connection) => connection,
+            None => return Err(Error::UnknownThread(thread_name.to_string())),
+        };
 
-            return Ok(());
-        } else {
-            panic!("Cannot unregister thread ID that doesn't exist")
+        // If the thread being unregistered is the read-write thread, set it to none
+        if let Some(rw_thread_name) = self.rw_thread {
+            if rw_thread_name == thread_name {
+                self.rw_thread = None
+            }
         }
+
+        thread_handle
+            .join()
+            .map_err(|_| Error::ThreadPanicked(thread_name.to_string()))
     }
 
     /// This functions takes any CAN messages that were sent by various
@@ -262,40 +520,150 @@ impl CANProxy {
     /// If it is a `ODriveCommand::Write`, then we can respond with `ODriveResponse::ReqReceived`
     /// as soon as the CAN bus accepts the message without error.
     /// 
-    /// If there is an error in connecting to the CAN bus, this immediately
-    /// responds back to the thread that sent the blocking request
-    /// that there was an error.
-    // TODO add retries for messages that are failing to send
+    /// Messages that failed to send on a previous call are sent again first, once their
+    /// backoff has passed. Once a message has failed more times than [`RetryPolicy::send_retries`]
+    /// allows, this responds back to the thread that sent the blocking request that there was an error.
     fn send_queued_msgs(&mut self) {
-        let receiver = &self.mpsc_channel.1;
+        let now = Instant::now();
+        let (retry, waiting) = std::mem::take(&mut self.failed_requests)
+            .into_iter()
+            .partition(|pending| pending.deadline <= now);
+        self.failed_requests = waiting;
+        for pending in retry {
+            self.send_request(pending);
+        }
 
         // This will try to get any messages that are available to send, otherwise the method
         // returns if there is nothing available to avoid blocking
-        for request in receiver.try_iter() {
-            // If the command is a read, it must have the RTR bit enabled
-            // since it is waiting for a response
-            let rtr_enabled = match request.body.cmd {
-                ODriveCommand::Read(_) => true,
-                ODriveCommand::Write(_) => false,
-            };
+        let received: Vec<ThreadMessage> = self.mpsc_channel.1.try_iter().collect();
+        for message in received {
+            self.handle_message(message);
+        }
+    }
 
-            match self.socket.write_frame(&request.body.to_can(rtr_enabled)) {
-                Ok(_) => {
-                    match request.body.cmd {
-                        // If the request was successfully sent and it is a Write request, notify that it was sucessfully sent
-                        ODriveCommand::Write(_) => {
-                            self.respond(request.thread_name, Ok(ResponseType::Bodyless { req: request.body }));
-                        }
-                        // otherwise add the message as a listener
-                        ODriveCommand::Read(_) => self.requests.push(request),
+    /// Acts on a single message sent to the proxy by a registered thread or the reader thread
+    fn handle_message(&mut self, message: ThreadMessage) {
+        match message {
+            ThreadMessage::Request(frame, reply) => match self.in_flight(&frame) {
+                Some(in_flight) => in_flight.coalesced.push((frame, reply)),
+                None => self.send_request(QueuedRequest {
+                    frame,
+                    reply,
+                    deadline: Instant::now(),
+                    failed_sends: 0,
+                    timeouts: 0,
+                    coalesced: vec![],
+                }),
+            },
+            ThreadMessage::Subscribe { filter, sender } => self.subscriptions.push((filter, sender)),
+            ThreadMessage::Monitor(sender) => self.monitors.push(sender),
+            ThreadMessage::Cancel(id) => {
+                Self::cancel(&mut self.requests, id);
+                Self::cancel(&mut self.failed_requests, id);
+            }
+            ThreadMessage::Received(frame) => self.handle_frame(&frame),
+        }
+    }
+
+    /// Returns the `Read` request for the same frame and with the same timeout that is already
+    /// waiting for a response or to be sent again, if there is one. `Write` requests are never
+    /// shared since each one is a separate command to the ODrive.
+    ///
+    /// A request that is waiting for a response has its deadline pushed back so that the
+    /// new request waits as long as it would have on its own
+    fn in_flight(&mut self, frame: &ThreadCANFrame) -> Option<&mut QueuedRequest> {
+        if let ODriveCommand::Write(_) = frame.body.cmd {
+            return None;
+        }
+
+        let timeout = frame.timeout.unwrap_or(self.timeout);
+        let default_timeout = self.timeout;
+        let is_same = |pending: &QueuedRequest| {
+            pending.frame.body == frame.body && pending.frame.timeout.unwrap_or(default_timeout) == timeout
+        };
+
+        if let Some(index) = self.requests.iter().position(is_same) {
+            let pending = &mut self.requests[index];
+            pending.deadline = pending.deadline.max(Instant::now() + timeout);
+            return Some(pending);
+        }
+        self.failed_requests.iter_mut().find(|pending| is_same(pending))
+    }
+
+    /// Removes the request with the given id from the queue. If identical requests were
+    /// waiting on it, the first of them takes its place so that they still get a response
+    fn cancel(queue: &mut Vec<QueuedRequest>, id: u64) {
+        for pending in queue.iter_mut() {
+            pending.coalesced.retain(|(frame, _reply)| frame.id != id);
+        }
+
+        if let Some(index) = queue.iter().position(|pending| pending.frame.id == id) {
+            let mut pending = queue.remove(index);
+            if !pending.coalesced.is_empty() {
+                (pending.frame, pending.reply) = pending.coalesced.remove(0);
+                queue.push(pending);
+            }
+        }
+    }
+
+    /// Writes a single request to the CAN bus and keeps track of it until it can be responded to
+    fn send_request(&mut self, mut pending: QueuedRequest) {
+        // If the command is a read, it must have the RTR bit enabled
+        // since it is waiting for a response
+        let rtr_enabled = match pending.frame.body.cmd {
+            ODriveCommand::Read(_) => true,
+            ODriveCommand::Write(_) => false,
+        };
+
+        match self.socket.write_frame(&pending.frame.body.to_can(rtr_enabled)) {
+            Ok(_) => {
+                self.tap(Direction::Outgoing, Some(pending.frame.thread_name), pending.frame.body);
+                match pending.frame.body.cmd {
+                    // If the request was successfully sent and it is a Write request, notify that it was sucessfully sent
+                    ODriveCommand::Write(_) => {
+                        let response = Ok(ResponseType::Bodyless { req: pending.frame.body });
+                        self.respond_to(pending, response);
+                    }
+                    // otherwise add the message as a listener until its deadline
+                    ODriveCommand::Read(_) => {
+                        pending.deadline = Instant::now() + pending.frame.timeout.unwrap_or(self.timeout);
+                        self.requests.push(pending)
                     }
                 }
-                // If there was an error with writing the frame, respond back with the
-                // the attempted request and the error
-                Err(_) => self.respond(
-                    request.thread_name,
-                    Err(ErrorResponse{ request: request.body, err: ODriveError::FailedToSend}),
-                ),
+            }
+            // Keep the message around to send again if it has any retries left
+            Err(_) if pending.failed_sends < self.retry_policy.send_retries => {
+                let backoff = SEND_BACKOFF.saturating_mul(1 << pending.failed_sends.min(16));
+                pending.deadline = Instant::now() + backoff.min(MAX_SEND_BACKOFF);
+                pending.failed_sends += 1;
+                self.failed_requests.push(pending);
+            }
+            // If there was an error with writing the frame, respond back with the
+            // the attempted request and the error
+            Err(_) => {
+                let response = Err(ErrorResponse{ request: pending.frame.body, err: ODriveError::FailedToSend});
+                self.respond_to(pending, response);
+            }
+        }
+    }
+
+    /// This function finds any requests whose deadline passed without a response.
+    /// Each one is sent again if it has retries left according to [`RetryPolicy::response_retries`],
+    /// otherwise the thread is responded to with [`ODriveError::NoResponse`]
+    fn handle_timeouts(&mut self) {
+        let now = Instant::now();
+        let (expired, waiting) = std::mem::take(&mut self.requests)
+            .into_iter()
+            .partition(|pending| pending.deadline <= now);
+        self.requests = waiting;
+
+        for mut pending in expired {
+            if pending.timeouts < self.retry_policy.response_retries {
+                pending.timeouts += 1;
+                self.send_request(pending);
+            } else {
+                let response = Err(ErrorResponse{ request: pending.frame.body, err: ODriveError::NoResponse});
+                self.respond_to(pending, response);
             }
         }
     }
@@ -304,31 +672,59 @@ impl CANProxy {
     /// request waiting for a response from the CAN bus, this function
     /// will respond to the appropriate thread with a [`ODriveResponse`]
     /// containing the data of the response. 
+    ///
+    /// Every frame read is also forwarded to the subscriptions whose filter it matches,
+    /// whether it answers a request or was broadcast by the ODrive on its own.
     fn handle_can_response(&mut self) {
         // Listen for a response
-        let can_response = match self.socket.read_frame() {
-            Ok(res) => CANResponse::from_can(&res),
+        if let Ok(frame) = self.socket.read_frame() {
+            self.handle_frame(&frame);
+        }
+    }
+
+    /// Forwards a frame read from the CAN bus to the subscriptions, monitors and the
+    /// thread whose request it answers
+    fn handle_frame(&mut self, frame: &CANFrame) {
+        // Frames that are not ODrive messages, for example from other devices on the bus, are ignored
+        let can_response = match CANResponse::from_can(frame) {
+            Ok(response) => response,
             Err(_) => return,
         };
+
+        // Subscriptions whose receiver was dropped are removed
+        self.subscriptions
+            .retain(|(filter, sender)| !filter.matches(&can_response) || sender.send(can_response).is_ok());
         //print!("{:?}", &can_response);
 
         // Find the message that is waiting for a response and send it back
-        match self.listener_index(&can_response) {
-            Some(index) => {
-                // println!("response matched with smth from odrive {:?}", can_response);
-
-                let waiting = self.requests.remove(index);
-                self.respond(
-                    waiting.thread_name,
-                    Ok(ResponseType::Body {request: waiting.body, response: can_response}),
-                )
-            }
-            None => {}
+        let listener = self.listener_index(&can_response);
+        let thread_name = listener.map(|index| self.requests[index].frame.thread_name);
+        self.tap(Direction::Incoming, thread_name, can_response);
+
+        if let Some(index) = listener {
+            // println!("response matched with smth from odrive {:?}", can_response);
+
+            let waiting = self.requests.remove(index);
+            let response = Ok(ResponseType::Body {request: waiting.frame.body, response: can_response});
+            self.respond_to(waiting, response);
+        }
+    }
+
+    /// Sends a copy of a frame that passed through the proxy to every monitor.
+    /// Monitors whose receiver was dropped are removed
+    fn tap(&mut self, direction: Direction, thread_name: Option<&'static str>, frame: ODriveCANFrame) {
+        if self.monitors.is_empty() {
+            return;
         }
+
+        let event = BusEvent { direction, thread_name, timestamp: SystemTime::now(), frame };
+        self.monitors.retain(|sender| sender.send(event).is_ok());
     }
 
-    /// This function consumes `self` and starts a separate thread that constantly
-    /// processes any messages that are received by threads. This thread responds
+    /// This function consumes `self` and starts a separate thread that processes
+    /// any messages that are received by threads as soon as they arrive. Frames are read
+    /// from the CAN bus on another thread, so a blocking read never holds up requests
+    /// and the proxy sleeps while there is nothing to do. Both threads respond
     /// to the same stop signal as all other threads.
     /// 
     /// This returns a function/hook that is capable of stopping all threads,
@@ -342,8 +738,8 @@ impl CANProxy {
     /// ```
     /// use rustodrive::canproxy::CANProxy;
     ///
-    /// let mut can_proxy = CANProxy::new("can0");
-    /// can_proxy.register_ro("thread 1", |can_read| {});
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_ro("thread 1", |can_read| {}).unwrap();
     ///
     /// // start processing of messages on a separate thread
     /// let stop_threads = can_proxy.begin(); // <--- can_proxy is consumed here
@@ -353,34 +749,89 @@ impl CANProxy {
     /// // and wait for them to join with the hook given by .begin()
     /// let can_proxy = stop_threads().unwrap(); // <--- this is the same can_proxy object as before
     /// ```
-    pub fn begin(mut self) -> impl FnOnce() -> std::thread::Result<CANProxy> {
+    pub fn begin(mut self) -> impl FnOnce() -> Result<CANProxy, Error> {
         let threads_alive_copy = self.threads_alive.clone();
 
         let proxy_handle = std::thread::spawn(move || {
-            while self.is_alive() {
-                self.process_messages();
-            }
-            return self;
+            self.run();
+            self
         });
 
         // Send the signal for the proxy_handle and all threads to finish up their work
-        let stop_all = move || {
+        move || {
             threads_alive_copy.store(false, Ordering::SeqCst);
 
             // wait for proxy thread to finish
             let mut proxy = match proxy_handle.join() {
                 Ok(p) => p,
-                Err(err) => return Err(err),
+                Err(_) => return Err(Error::ThreadPanicked("CANProxy".to_string())),
             };
 
             // Then stop and wait for all the threads that were registered
             match proxy.join_registered() {
-                Ok(()) => return Ok(proxy),
-                Err(e) => return Err(e),
+                Ok(()) => Ok(proxy),
+                Err(e) => Err(e),
             }
-        };
+        }
+    }
+
+    /// Processes messages until the threads are stopped. Frames are read from the CAN bus by a
+    /// separate reader thread and passed along the same channel the registered threads send their
+    /// requests through, so that this loop can sleep until either of them has something for it
+    /// or the next request times out or is due to be sent again, instead of spinning or blocking on the socket.
+    fn run(&mut self) {
+        let reader = self.spawn_reader();
+
+        while self.is_alive() {
+            match self.mpsc_channel.1.recv_timeout(self.time_until_deadline()) {
+                Ok(message) => self.handle_message(message),
+                Err(RecvTimeoutError::Timeout) => {}
+                // The proxy holds onto a sender itself so the channel cannot disconnect
+                Err(RecvTimeoutError::Disconnected) => unreachable!(),
+            }
+
+            self.send_queued_msgs();
+            self.handle_timeouts();
+        }
+
+        reader.join().expect("The CAN reader thread panicked");
+    }
+
+    /// Starts a thread that reads frames from the CAN bus and sends them to the proxy
+    /// until the threads are stopped
+    fn spawn_reader(&self) -> JoinHandle<()> {
+        let socket = self.socket.clone();
+        let sender = self.mpsc_channel.0.clone();
+        let threads_alive = self.threads_alive.clone();
+
+        // The read timeout lets the reader notice when the threads are stopped
+        socket.set_read_timeout(IDLE_WAIT).ok();
+
+        std::thread::spawn(move || {
+            while threads_alive.load(Ordering::SeqCst) {
+                let start = Instant::now();
+                match socket.read_frame() {
+                    Ok(frame) => {
+                        if sender.send(ThreadMessage::Received(frame)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) if start.elapsed() < READ_BACKOFF => std::thread::sleep(READ_BACKOFF),
+                    Err(_) => {}
+                }
+            }
+        })
+    }
 
-        return stop_all;
+    /// Returns how long until the earliest request times out or is due to be sent again,
+    /// but no longer than [`IDLE_WAIT`]
+    fn time_until_deadline(&self) -> Duration {
+        let now = Instant::now();
+        self.requests
+            .iter()
+            .chain(self.failed_requests.iter())
+            .map(|pending| pending.deadline.saturating_duration_since(now))
+            .fold(IDLE_WAIT, Duration::min)
     }
 
     /// This function updates a shared reference that all threads have access to
@@ -392,10 +843,12 @@ impl CANProxy {
     /// This function handles the processing of messages from registered threads
     /// and responding to threads as soon as their request has been fulfilled.
     /// 
-    /// This thread most likely needs to continuously loop in a separate thread.
-    /// This functionality has been gratiously implemented with [`CANProxy::begin()`]
+    /// Calling this in a loop processes messages by polling the socket, which is useful for
+    /// driving the proxy manually. [`CANProxy::begin()`] processes messages on a separate
+    /// thread without polling instead
     pub fn process_messages(&mut self) {
         self.send_queued_msgs();
+        self.handle_timeouts();
         self.handle_can_response();
     }
 
@@ -404,16 +857,44 @@ impl CANProxy {
     fn listener_index(&self, received: &ODriveCANFrame) -> Option<usize> {
         self.requests
             .iter()
-            .position(|msg| msg.body.is_response(received))
+            .position(|msg| msg.frame.body.is_response(received))
+    }
+
+    /// Sends the response to a request wherever the request asked for it, as well as
+    /// to every identical request that was coalesced into it
+    fn respond_to(&self, pending: QueuedRequest, response: ODriveResponse) {
+        for (frame, reply) in pending.coalesced {
+            self.reply(frame.thread_name, reply, response.clone());
+        }
+        self.reply(pending.frame.thread_name, pending.reply, response);
+    }
+
+    /// Sends a response to a single destination
+    fn reply(&self, thread_name: &'static str, reply: Reply, response: ODriveResponse) {
+        match reply {
+            Reply::Thread => self.respond(thread_name, response),
+            // The handle being dropped means nobody is waiting for the response anymore
+            Reply::Channel(sender) => sender.send(response).unwrap_or(()),
+            // The thread being gone means nobody is interested in the error anymore
+            Reply::Errors(sender) => match response {
+                Ok(_) => {}
+                Err(error) => sender.send(error).unwrap_or(()),
+            },
+            // The future being dropped means nobody is waiting for the response anymore
+            #[cfg(feature = "async")]
+            Reply::Future(sender) => sender.send(response).unwrap_or(()),
+        }
     }
 
     /// This finds the thread based on the identifier and sends the specified
     /// [`ODriveResponse`] across the response channel for the thread
+    ///
+    /// If the thread was unregistered or already finished, there is nobody to
+    /// respond to and the response is dropped
     fn respond(&self, thread_name: &'static str, response: ODriveResponse) {
-        let (_thread, proxy_responder) = self.threads.get(thread_name).expect(&format!("The thread {} was not registered and cannot be responded to", thread_name));
-        proxy_responder
-            .send(response)
-            .expect(&format!("Proxy cannot reach thread {}", thread_name));
+        if let Some((_thread, proxy_responder)) = self.threads.get(thread_name) {
+            proxy_responder.send(response).unwrap_or(());
+        }
     }
 
     /// This returns whether or not all threads are running. By default
@@ -421,7 +902,7 @@ impl CANProxy {
     /// 
     /// Once all threads have been stopped, they cannot be restarted.
     pub fn is_alive(&self) -> bool {
-        return self.threads_alive.load(Ordering::SeqCst);
+        self.threads_alive.load(Ordering::SeqCst)
     }
 
     /// This waits for all threads to join. If a single one fails to join,
@@ -434,15 +915,15 @@ impl CANProxy {
     /// ```
     /// use rustodrive::canproxy::CANProxy;
     ///
-    /// let mut can_proxy = CANProxy::new("can0");
-    /// can_proxy.register_ro("thread 1", |can_read| {});
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_ro("thread 1", |can_read| {}).unwrap();
     /// std::thread::sleep_ms(1000);
     /// 
     /// can_proxy.stop_threads();
     /// can_proxy.join_registered();
     /// ```
-    pub fn join_registered(&mut self) -> std::thread::Result<()> {
-        let thread_names: Vec<&str> = self.threads.iter().map(|(name, _else)| *name).collect();
+    pub fn join_registered(&mut self) -> Result<(), Error> {
+        let thread_names: Vec<&str> = self.threads.keys().copied().chain(self.async_handle).collect();
         for name in thread_names {
             match self.unregister(name) {
                 Ok(()) => {},
@@ -455,22 +936,25 @@ impl CANProxy {
 
 #[cfg(test)]
 mod tests {
-    use std::{sync::mpsc::channel, time::Duration};
+    use std::{collections::HashSet, io, sync::{mpsc::channel, Arc, atomic::{AtomicU32, Ordering}}, time::{Duration, Instant}};
+
+    use socketcan::CANFrame;
 
     use crate::{
         state::{ODriveCommand, ReadComm, WriteComm},
-        canframe::{CANRequest}, tests::wait_for_msgs, response::{ResponseType}, utils::ResultAll,
+        canframe::{read_can, ticket, CANRequest, Direction, FrameFilter}, cansocket::{CanTransport, MockCANSocket}, faults::{FaultConfig, FaultyTransport}, simulator::ODriveSimulator, tests::wait_for_msgs, response::{ResponseType, ErrorResponse, ODriveError}, utils::ResultAll,
     };
 
-    use super::CANProxy;
+    use super::{CANProxy, RetryPolicy};
+    use crate::Error;
 
     #[test]
     fn test_register_thread() {
-        let mut can_proxy = CANProxy::new("fakecan");
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
 
-        can_proxy.register_ro("thread 1", |_| {});
-        can_proxy.register_ro("thread 2", |_| {});
-        can_proxy.register_rw("thread 3", |_| {});
+        can_proxy.register_ro("thread 1", |_| {}).unwrap();
+        can_proxy.register_ro("thread 2", |_| {}).unwrap();
+        can_proxy.register_rw("thread 3", |_| {}).unwrap();
 
         assert_eq!(can_proxy.threads.len(), 3);
 
@@ -479,19 +963,28 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn test_register_duplicate() {
-        let mut can_proxy = CANProxy::new("fakecan");
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+
+        can_proxy.register_ro("thread 1", |_| {}).unwrap();
+        let duplicate = can_proxy.register_ro("thread 1", |_| {});
+        assert!(matches!(duplicate, Err(Error::DuplicateThread("thread 1"))));
+    }
 
-        can_proxy.register_ro("thread 1", |_| {});
-        can_proxy.register_ro("thread 1", |_| {});
+    #[test]
+    fn test_unregister_unknown_thread() {
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        can_proxy.register_ro("thread 1", |_| panic!("thread failed")).unwrap();
+
+        assert!(matches!(can_proxy.unregister("thread 2"), Err(Error::UnknownThread(name)) if name == "thread 2"));
+        assert!(matches!(can_proxy.unregister("thread 1"), Err(Error::ThreadPanicked(name)) if name == "thread 1"));
     }
 
     #[test]
     fn test_unregister_rw_thread() {
-        let mut can_proxy = CANProxy::new("fakecan");
-        can_proxy.register_rw("thread 1", |_| {});
-        can_proxy.register_ro("thread 2", |_| {});
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        can_proxy.register_rw("thread 1", |_| {}).unwrap();
+        can_proxy.register_ro("thread 2", |_| {}).unwrap();
 
         assert_ne!(can_proxy.rw_thread, None);
         assert_eq!(can_proxy.threads.len(), 2);
@@ -507,17 +1000,16 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn test_register_duplicate_rw_thread() {
-        let mut can_proxy = CANProxy::new("fakecan");
-        can_proxy.register_rw("thread 1", |_| {});
-        can_proxy.register_rw("thread 2", |_| {});
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        can_proxy.register_rw("thread 1", |_| {}).unwrap();
+        assert!(matches!(can_proxy.register_rw("thread 2", |_| {}), Err(Error::ReadWriteTaken)));
     }
 
     #[test]
     /// Test that a Read command responds back with a response (should be the same one for testing purposes)
     fn test_read_command_response() {
-        let mut can_proxy = CANProxy::new("fakecan");
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
         let request = CANRequest {
             axis: 2,
             cmd: ODriveCommand::Read(ReadComm::EncoderError),
@@ -526,13 +1018,13 @@ mod tests {
 
         // create a channel so that the test thread can communicate back its results
         let (send, rcv) = channel();
-        let request_copy = request.clone();
+        let request_copy = request;
 
         // Create thread to send a set of messages and get their responses
         can_proxy.register_rw("thread 1", move |can_read_write| {
             let response = can_read_write.request(request_copy);
             send.send(response).unwrap()
-        });
+        }).unwrap();
 
         let stop_proxy = can_proxy.begin();
 
@@ -552,7 +1044,7 @@ mod tests {
     #[test]
     /// Write command responds with MsgReceived to notify it was sent over the CAN bus
     fn test_write_command_response() {
-        let mut can_proxy = CANProxy::new("fakecan");
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
         let request = CANRequest {
             axis: 2,
             cmd: ODriveCommand::Write(WriteComm::SetAxisNodeID),
@@ -561,13 +1053,13 @@ mod tests {
 
         // create a channel so that the test thread can communicate back its results
         let (send, rcv) = channel();
-        let request_copy = request.clone();
+        let request_copy = request;
 
         // Create thread to send a set of messages and get their responses
         can_proxy.register_rw("thread 1", move |can_read_write| {
             let response = can_read_write.request(request_copy);
             send.send(response).unwrap()
-        });
+        }).unwrap();
 
         let stop_proxy = can_proxy.begin();
 
@@ -586,7 +1078,7 @@ mod tests {
     /// This is because we cannot assume the odrive will respond sequentially
     /// to requests.
     fn test_request_many() {
-        let mut can_proxy = CANProxy::new("fakecan");
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
 
         // Setup request data
         let mut requests = Vec::new();
@@ -606,7 +1098,7 @@ mod tests {
         can_proxy.register_rw("thread 1", move |can_read_write| {
             let responses = can_read_write.request_many(requests_copy);
             send.send(responses).unwrap()
-        });
+        }).unwrap();
 
         // we sleep for a short amount of time so that that messages can build up and be
         // randomly returned to test that the response is sorted properly
@@ -619,7 +1111,570 @@ mod tests {
         // The response should be returned in the same order as the requests made
         for (expected_req, res) in requests.into_iter().zip(response) {
             let (_, actual_response) = res.body();
-            assert_eq!(actual_response.is_response(&expected_req), true);
+            assert!(actual_response.is_response(&expected_req));
         }
     }
+
+    #[test]
+    /// A read request whose deadline passes before a response is read is answered with
+    /// NoResponse instead of blocking the thread forever
+    fn test_request_timeout() {
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        can_proxy.set_retry_policy(RetryPolicy { send_retries: 0, response_retries: 0 });
+
+        let request = CANRequest {
+            axis: 2,
+            cmd: ODriveCommand::Read(ReadComm::GetVBusVoltage),
+            data: [0; 8],
+        };
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_read_write| {
+            let response = can_read_write.request_with_timeout(request, Duration::ZERO);
+            send.send(response).unwrap()
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let response = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(response, Err(ErrorResponse { request, err: ODriveError::NoResponse }));
+    }
+
+    #[test]
+    /// A request that times out is sent again as long as it has retries left
+    fn test_request_timeout_retry() {
+        // There is no axis 2 on the bus to answer the request
+        let mut can_proxy = CANProxy::with_transport(ODriveSimulator::new(&[0]));
+        can_proxy.set_timeout(Duration::from_millis(20));
+        can_proxy.set_retry_policy(RetryPolicy { send_retries: 0, response_retries: 2 });
+        let bus_events = can_proxy.monitor();
+
+        let request = CANRequest {
+            axis: 2,
+            cmd: ODriveCommand::Read(ReadComm::GetVBusVoltage),
+            data: [0; 8],
+        };
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_read_write| {
+            let start = Instant::now();
+            let response = can_read_write.request(request);
+            send.send((response, start.elapsed())).unwrap()
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let (response, elapsed) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(response, Err(ErrorResponse { request, err: ODriveError::NoResponse }));
+        // The request was sent once and then once more for every retry, each after the
+        // previous attempt timed out
+        let sent: Vec<_> = bus_events
+            .try_iter()
+            .filter(|event| event.direction == Direction::Outgoing && event.frame == request)
+            .collect();
+        assert_eq!(sent.len(), 3);
+        assert!(sent.windows(2).all(|pair| pair[1].timestamp.duration_since(pair[0].timestamp).unwrap() >= Duration::from_millis(20)));
+        assert!(elapsed >= Duration::from_millis(60));
+    }
+
+    /// A transport that counts how many frames it was asked to write and fails every time
+    struct FailingTransport {
+        writes: Arc<AtomicU32>,
+    }
+
+    impl CanTransport for FailingTransport {
+        fn open(_ifname: &str) -> io::Result<Self> {
+            Ok(Self { writes: Arc::new(AtomicU32::new(0)) })
+        }
+
+        fn write_frame(&self, _frame: &CANFrame) -> io::Result<()> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            Err(io::Error::other("bus is down"))
+        }
+
+        fn read_frame(&self) -> io::Result<CANFrame> {
+            Err(io::Error::new(io::ErrorKind::WouldBlock, "no messages available"))
+        }
+
+        fn set_read_timeout(&self, _timeout: Duration) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_write_timeout(&self, _timeout: Duration) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    /// A transport given at runtime is used instead of the default one, and frames that
+    /// fail to send are retried before the thread is told about the failure
+    fn test_with_transport_send_retries() {
+        let writes = Arc::new(AtomicU32::new(0));
+        let mut can_proxy = CANProxy::with_transport(FailingTransport { writes: writes.clone() });
+        can_proxy.set_retry_policy(RetryPolicy { send_retries: 3, response_retries: 0 });
+
+        let request = CANRequest {
+            axis: 1,
+            cmd: ODriveCommand::Write(WriteComm::SetInputVelocity),
+            data: [0; 8],
+        };
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_read_write| {
+            send.send(can_read_write.request(request)).unwrap()
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let response = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(response, Err(ErrorResponse { request, err: ODriveError::FailedToSend }));
+        assert_eq!(writes.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    /// A frame that keeps failing to send is retried after a growing backoff instead of
+    /// as fast as the proxy loop can go
+    fn test_send_retry_backoff() {
+        let writes = Arc::new(AtomicU32::new(0));
+        let mut can_proxy = CANProxy::with_transport(FailingTransport { writes: writes.clone() });
+        can_proxy.set_retry_policy(RetryPolicy { send_retries: 5, response_retries: 0 });
+
+        let request = ticket(1, ODriveCommand::Write(WriteComm::SetInputVelocity), [0; 8]);
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_read_write| {
+            let start = Instant::now();
+            let response = can_read_write.request(request);
+            send.send((response, start.elapsed())).unwrap()
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let (response, elapsed) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(response, Err(ErrorResponse { request, err: ODriveError::FailedToSend }));
+        assert_eq!(writes.load(Ordering::SeqCst), 6);
+        // 1ms + 2ms + 4ms + 8ms + 16ms between the attempts
+        assert!(elapsed >= Duration::from_millis(31), "{:?}", elapsed);
+    }
+
+    #[test]
+    /// Frames handed over with `send()` do not wait for a response, and only their
+    /// failures are reported, in the order they were sent
+    fn test_send_reports_errors() {
+        let writes = Arc::new(AtomicU32::new(0));
+        let mut can_proxy = CANProxy::with_transport(FailingTransport { writes: writes.clone() });
+        can_proxy.set_retry_policy(RetryPolicy { send_retries: 0, response_retries: 0 });
+
+        let requests: Vec<CANRequest> = (0..3)
+            .map(|axis| CANRequest { axis, cmd: ODriveCommand::Write(WriteComm::SetInputVelocity), data: [0; 8] })
+            .collect();
+        let requests_copy = requests.clone();
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_read_write| {
+            can_read_write.send_many(requests_copy);
+            let errors: Vec<_> = (0..3)
+                .map(|_| can_read_write.send_errors().recv_timeout(Duration::from_secs(1)).unwrap())
+                .collect();
+            send.send(errors).unwrap()
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let errors = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        let expected: Vec<_> = requests.into_iter().map(|request| ErrorResponse { request, err: ODriveError::FailedToSend }).collect();
+        assert_eq!(errors, expected);
+        assert_eq!(writes.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    /// Threads receive the heartbeats that the ODrives broadcast without requesting them
+    fn test_subscribe_heartbeats() {
+        let mut can_proxy = CANProxy::with_transport(ODriveSimulator::new(&[0, 1, 2]));
+
+        let (send, rcv) = channel();
+        can_proxy.register_ro("listener", move |can_read| {
+            let heartbeats = can_read.subscribe(FrameFilter::cmd(ReadComm::GetHeartbeat));
+            let axis_1 = can_read.subscribe(FrameFilter::axis(1));
+
+            let mut heartbeat_axes = HashSet::new();
+            let start = Instant::now();
+            while heartbeat_axes.len() < 3 && start.elapsed() < Duration::from_secs(2) {
+                if let Ok(frame) = heartbeats.recv_timeout(Duration::from_millis(10)) {
+                    assert_eq!(frame.cmd, ODriveCommand::Read(ReadComm::GetHeartbeat));
+                    heartbeat_axes.insert(frame.axis);
+                }
+            }
+            let axis_1_frames: Vec<_> = axis_1.try_iter().collect();
+            send.send((heartbeat_axes, axis_1_frames)).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let (heartbeat_axes, axis_1_frames) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(heartbeat_axes, HashSet::from([0, 1, 2]));
+        assert!(!axis_1_frames.is_empty());
+        assert!(axis_1_frames.iter().all(|frame| frame.axis == 1));
+    }
+
+    #[test]
+    /// Dropping the receiver of a subscription does not stop the proxy from delivering
+    /// frames to the remaining subscribers or answering requests
+    fn test_unsubscribe_on_drop() {
+        let mut can_proxy = CANProxy::with_transport(ODriveSimulator::new(&[0]));
+
+        let (dropped_send, dropped_rcv) = channel();
+        can_proxy.register_ro("dropper", move |can_read| {
+            let frames = can_read.subscribe(FrameFilter::all());
+            frames.recv_timeout(Duration::from_secs(1)).unwrap();
+            drop(frames);
+            dropped_send.send(()).unwrap();
+        }).unwrap();
+
+        let (send, rcv) = channel();
+        can_proxy.register_ro("listener", move |can_read| {
+            let heartbeats = can_read.subscribe(FrameFilter::cmd(ReadComm::GetHeartbeat));
+            dropped_rcv.recv().unwrap();
+            // Only count the heartbeats sent after the other subscription was dropped
+            heartbeats.try_iter().for_each(drop);
+
+            // Every heartbeat after the drop passes through the dropped subscription first
+            let mut after_drop = 0;
+            let start = Instant::now();
+            while after_drop < 3 && start.elapsed() < Duration::from_secs(2) {
+                if heartbeats.recv_timeout(Duration::from_millis(10)).is_ok() {
+                    after_drop += 1;
+                }
+            }
+            let response = can_read.request(0, ReadComm::GetEncoderEstimates);
+            send.send((after_drop, response)).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let (after_drop, response) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(after_drop, 3);
+        assert!(response.is_ok());
+    }
+
+    #[test]
+    /// A read-only thread observes the writes of the read-write thread and the
+    /// responses to them through a bus monitor
+    fn test_monitor_write_traffic() {
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        let proxy_events = can_proxy.monitor();
+
+        let (ready_send, ready_rcv) = channel();
+        let (events_send, events_rcv) = channel();
+        can_proxy.register_ro("monitor", move |can_read| {
+            let bus_events = can_read.monitor();
+            ready_send.send(()).unwrap();
+
+            let mut events = vec![];
+            while events.len() < 3 {
+                if let Ok(event) = bus_events.recv_timeout(Duration::from_secs(1)) {
+                    events.push(event);
+                } else {
+                    break;
+                }
+            }
+            events_send.send(events).unwrap();
+        }).unwrap();
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("writer", move |can_rw| {
+            // Wait for the monitor to be registered before sending anything
+            ready_rcv.recv().unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+
+            can_rw.request(ticket(1, ODriveCommand::Write(WriteComm::SetInputVelocity), [1; 8])).unwrap();
+            can_rw.request(read_can(1, ReadComm::GetEncoderEstimates)).unwrap();
+            send.send(()).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        wait_for_msgs(rcv);
+        let events = wait_for_msgs(events_rcv);
+        stop_proxy().unwrap();
+
+        let observed: Vec<_> = events.iter().map(|event| (event.direction, event.thread_name, event.frame.cmd)).collect();
+        assert_eq!(observed, vec![
+            (Direction::Outgoing, Some("writer"), ODriveCommand::Write(WriteComm::SetInputVelocity)),
+            (Direction::Outgoing, Some("writer"), ODriveCommand::Read(ReadComm::GetEncoderEstimates)),
+            (Direction::Incoming, Some("writer"), ODriveCommand::Read(ReadComm::GetEncoderEstimates)),
+        ]);
+        assert_eq!(events[0].frame.data, [1; 8]);
+        assert_eq!(events[2].frame.data, [99; 8]);
+        assert!(events[1].timestamp <= events[2].timestamp);
+
+        // Monitors registered directly on the proxy see the same traffic
+        assert_eq!(proxy_events.try_iter().count(), 3);
+    }
+
+    /// A transport for a bus where nothing is ever received. Every read blocks for
+    /// `read_block` regardless of the read timeout, and the number of reads is counted
+    struct SilentTransport {
+        reads: Arc<AtomicU32>,
+        read_block: Duration,
+    }
+
+    impl CanTransport for SilentTransport {
+        fn open(_ifname: &str) -> io::Result<Self> {
+            Ok(Self { reads: Arc::new(AtomicU32::new(0)), read_block: Duration::ZERO })
+        }
+
+        fn write_frame(&self, _frame: &CANFrame) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn read_frame(&self) -> io::Result<CANFrame> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(self.read_block);
+            Err(io::Error::new(io::ErrorKind::TimedOut, "no messages available"))
+        }
+
+        fn set_read_timeout(&self, _timeout: Duration) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_write_timeout(&self, _timeout: Duration) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    /// Requests are sent right away even while the proxy is waiting on a slow read
+    fn test_no_head_of_line_blocking() {
+        let transport = SilentTransport { reads: Arc::new(AtomicU32::new(0)), read_block: Duration::from_millis(300) };
+        let mut can_proxy = CANProxy::with_transport(transport);
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            // Make sure the proxy is already waiting for a frame
+            std::thread::sleep(Duration::from_millis(50));
+
+            let start = Instant::now();
+            can_rw.request(ticket(1, ODriveCommand::Write(WriteComm::SetInputVelocity), [0; 8])).unwrap();
+            send.send(start.elapsed()).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let latency = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert!(latency < Duration::from_millis(100), "write took {:?}", latency);
+    }
+
+    #[test]
+    /// An idle proxy sleeps instead of spinning on a transport that never blocks
+    fn test_idle_proxy_does_not_spin() {
+        let reads = Arc::new(AtomicU32::new(0));
+        let can_proxy = CANProxy::with_transport(SilentTransport { reads: reads.clone(), read_block: Duration::ZERO });
+
+        let stop_proxy = can_proxy.begin();
+        std::thread::sleep(Duration::from_millis(200));
+        stop_proxy().unwrap();
+
+        // The reader backs off between reads that return immediately
+        assert!(reads.load(Ordering::SeqCst) < 1000, "{} reads", reads.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    /// Submitted requests are responded to through their handle while the thread keeps
+    /// making blocking requests
+    fn test_submit_and_wait() {
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let mut pending = can_rw.submit(read_can(1, ReadComm::GetIQ));
+            let blocking = can_rw.request(read_can(2, ReadComm::GetIQ));
+            let waited = pending.wait_timeout(Duration::from_secs(1));
+            send.send((blocking, waited, pending.poll(), pending.wait())).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let (blocking, waited, polled, response) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(blocking.unwrap().request(), read_can(2, ReadComm::GetIQ));
+        let (request, body) = response.clone().unwrap().body();
+        assert_eq!(request, read_can(1, ReadComm::GetIQ));
+        assert_eq!(body.data, [99; 8]);
+        assert_eq!(waited, Some(response.clone()));
+        assert_eq!(polled, Some(response));
+    }
+
+    #[test]
+    /// A cancelled request is never sent again when it times out, while the request that
+    /// is still being waited on is retried until it fails
+    fn test_cancel_request() {
+        let mut can_proxy = CANProxy::with_transport(ODriveSimulator::new(&[0]));
+        can_proxy.set_timeout(Duration::from_millis(100));
+        can_proxy.set_retry_policy(RetryPolicy { send_retries: 0, response_retries: 2 });
+        let bus_events = can_proxy.monitor();
+
+        let (send, rcv) = channel();
+        can_proxy.register_ro("thread 1", move |can_read| {
+            // There is no axis 5 on the bus to answer these
+            let mut cancelled = can_read.submit(5, ReadComm::GetIQ);
+            let waiting = can_read.submit(5, ReadComm::GetVBusVoltage);
+
+            let polled = cancelled.poll();
+            let waited = cancelled.wait_timeout(Duration::from_millis(5));
+            cancelled.cancel();
+            send.send((polled, waited, waiting.wait())).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let (polled, waited, response) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(polled, None);
+        assert_eq!(waited, None);
+        let request = read_can(5, ReadComm::GetVBusVoltage);
+        assert_eq!(response, Err(ErrorResponse { request, err: ODriveError::NoResponse }));
+
+        let sent: Vec<_> = bus_events
+            .try_iter()
+            .filter(|event| event.direction == Direction::Outgoing)
+            .map(|event| event.frame)
+            .collect();
+        assert_eq!(sent.iter().filter(|frame| **frame == read_can(5, ReadComm::GetIQ)).count(), 1);
+        assert_eq!(sent.iter().filter(|frame| **frame == request).count(), 3);
+    }
+
+    #[test]
+    /// Duplicate requests in one call are all answered in order. Identical reads share a
+    /// single bus transaction while every write is sent
+    fn test_request_many_duplicates() {
+        let mut can_proxy = CANProxy::new("fakecan").unwrap();
+        let bus_events = can_proxy.monitor();
+
+        let read = read_can(1, ReadComm::GetIQ);
+        let write = ticket(1, ODriveCommand::Write(WriteComm::SetInputVelocity), [0; 8]);
+        let requests = vec![read, write, read, write, read];
+
+        let (send, rcv) = channel();
+        let requests_copy = requests.clone();
+        can_proxy.register_rw("thread 1", move |can_read_write| {
+            send.send(can_read_write.request_many(requests_copy)).unwrap()
+        }).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+
+        let stop_all = can_proxy.begin();
+        let responses = wait_for_msgs(rcv).unwrap_all();
+        stop_all().unwrap();
+
+        for (request, response) in requests.into_iter().zip(responses) {
+            assert_eq!(response.request(), request);
+        }
+        let sent: Vec<_> = bus_events
+            .try_iter()
+            .filter(|event| event.direction == Direction::Outgoing)
+            .map(|event| event.frame)
+            .collect();
+        assert_eq!(sent, vec![read, write, write]);
+    }
+
+    #[test]
+    /// Two threads asking for the same value at the same time both get the single response
+    fn test_concurrent_identical_requests() {
+        // The response takes long enough for the second request to arrive while the first is in flight
+        let transport = FaultyTransport::new(MockCANSocket::new(), FaultConfig {
+            delay: Duration::from_millis(100),
+            ..Default::default()
+        });
+        let mut can_proxy = CANProxy::with_transport(transport);
+        let bus_events = can_proxy.monitor();
+
+        let (send, rcv) = channel();
+        for (thread_name, delay) in [("thread 1", 0), ("thread 2", 20)] {
+            let send = send.clone();
+            can_proxy.register_ro(thread_name, move |can_read| {
+                std::thread::sleep(Duration::from_millis(delay));
+                send.send((thread_name, can_read.request(1, ReadComm::GetIQ))).unwrap()
+            }).unwrap();
+        }
+
+        let stop_proxy = can_proxy.begin();
+        let mut responses: Vec<_> = (0..2).map(|_| rcv.recv_timeout(Duration::from_secs(2)).unwrap()).collect();
+        stop_proxy().unwrap();
+
+        responses.sort_by_key(|(thread_name, _)| *thread_name);
+        assert_eq!(responses[0].0, "thread 1");
+        assert_eq!(responses[1].0, "thread 2");
+        assert_eq!(responses[0].1, responses[1].1);
+        assert_eq!(responses[0].1.clone().unwrap().body().1.data, [99; 8]);
+
+        // The second request waited on the first instead of being sent again
+        let sent = bus_events.try_iter().filter(|event| event.direction == Direction::Outgoing).count();
+        assert_eq!(sent, 1);
+    }
+
+    #[test]
+    /// A request that joins an identical one in flight still waits for its own timeout, and
+    /// requests with a different timeout are not joined at all
+    fn test_coalesced_request_timeouts() {
+        let mut can_proxy = CANProxy::with_transport(ODriveSimulator::new(&[0]));
+        can_proxy.set_retry_policy(RetryPolicy { send_retries: 0, response_retries: 0 });
+        let bus_events = can_proxy.monitor();
+
+        let (send, rcv) = channel();
+        for (thread_name, delay, timeout) in [("first", 0, 200), ("joined", 100, 200), ("short", 0, 20)] {
+            let send = send.clone();
+            can_proxy.register_ro(thread_name, move |can_read| {
+                std::thread::sleep(Duration::from_millis(delay));
+                // There is no axis 5 on the bus to answer these
+                let start = Instant::now();
+                let response = can_read.request_with_timeout(5, ReadComm::GetIQ, Duration::from_millis(timeout));
+                send.send((thread_name, response, start.elapsed())).unwrap()
+            }).unwrap();
+        }
+
+        let stop_proxy = can_proxy.begin();
+        let mut responses: Vec<_> = (0..3).map(|_| rcv.recv_timeout(Duration::from_secs(2)).unwrap()).collect();
+        stop_proxy().unwrap();
+
+        responses.sort_by_key(|(thread_name, _, _)| *thread_name);
+        let [(_, first, first_elapsed), (_, joined, joined_elapsed), (_, short, short_elapsed)] = &responses[..] else {
+            panic!("{:?}", responses)
+        };
+        let no_response = Err(ErrorResponse { request: read_can(5, ReadComm::GetIQ), err: ODriveError::NoResponse });
+        assert_eq!((first, joined, short), (&no_response, &no_response, &no_response));
+        assert!(*first_elapsed >= Duration::from_millis(200));
+        assert!(*joined_elapsed >= Duration::from_millis(200), "{:?}", joined_elapsed);
+        assert!(*short_elapsed < Duration::from_millis(200), "{:?}", short_elapsed);
+
+        let sent = bus_events.try_iter().filter(|event| event.direction == Direction::Outgoing).count();
+        assert_eq!(sent, 2);
+    }
+
+    #[test]
+    /// Cancelling a request that others are waiting on still gets them a response
+    fn test_cancel_coalesced_request() {
+        let mut can_proxy = CANProxy::with_transport(ODriveSimulator::new(&[0]));
+        can_proxy.set_timeout(Duration::from_millis(50));
+
+        let (send, rcv) = channel();
+        can_proxy.register_ro("thread 1", move |can_read| {
+            // There is no axis 5 on the bus to answer these
+            let first = can_read.submit(5, ReadComm::GetIQ);
+            let second = can_read.submit(5, ReadComm::GetIQ);
+            first.cancel();
+            send.send(second.wait()).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let response = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(response, Err(ErrorResponse { request: read_can(5, ReadComm::GetIQ), err: ODriveError::NoResponse }));
+    }
 }
@@ -1,57 +1,140 @@
-#[allow(unused_imports)]
-use {std::io,
-    socketcan::{CANSocketOpenError, CANFrame},
-    crate::{canframe::CANRequest, state::ODriveCommand},
-};
-use crate::cfg_match;
+use std::io;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
 
-cfg_match! {
-    feature = "mock-socket" => {
+use socketcan::CANFrame;
+
+use crate::{canframe::CANRequest, cfg_match, state::ODriveCommand};
+
+/// A connection to a CAN bus that the [`CANProxy`](crate::canproxy::CANProxy) sends
+/// and receives frames through.
+///
+/// This is implemented for [`socketcan::CANSocket`] to talk to real hardware and
+/// for [`MockCANSocket`] for testing without any ODrives connected. Any other
+/// transport (or test double) can be used by implementing this trait and passing
+/// it to [`CANProxy::with_transport()`](crate::canproxy::CANProxy::with_transport).
+pub trait CanTransport: Send + Sync {
+    /// Opens the transport on the CAN interface with the given name
+    fn open(ifname: &str) -> io::Result<Self>
+    where
+        Self: Sized;
+
+    /// Writes a single frame to the bus
+    fn write_frame(&self, frame: &CANFrame) -> io::Result<()>;
+
+    /// Reads a single frame from the bus. If no frame is available within the
+    /// read timeout, this returns an error of kind [`io::ErrorKind::WouldBlock`]
+    /// or [`io::ErrorKind::TimedOut`]
+    fn read_frame(&self) -> io::Result<CANFrame>;
+
+    /// Sets how long [`CanTransport::read_frame()`] may block waiting for a frame
+    fn set_read_timeout(&self, timeout: Duration) -> io::Result<()>;
+
+    /// Sets how long [`CanTransport::write_frame()`] may block waiting for the bus
+    fn set_write_timeout(&self, timeout: Duration) -> io::Result<()>;
+}
+
+impl CanTransport for socketcan::CANSocket {
+    fn open(ifname: &str) -> io::Result<Self> {
+        socketcan::CANSocket::open(ifname).map_err(io::Error::other)
+    }
+
+    fn write_frame(&self, frame: &CANFrame) -> io::Result<()> {
+        socketcan::CANSocket::write_frame(self, frame)
+    }
+
+    fn read_frame(&self) -> io::Result<CANFrame> {
+        socketcan::CANSocket::read_frame(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Duration) -> io::Result<()> {
+        socketcan::CANSocket::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Duration) -> io::Result<()> {
+        socketcan::CANSocket::set_write_timeout(self, timeout)
+    }
+}
+
+/// Mock implementation that answers every `Read` request it is sent with a frame
+/// containing `[99; 8]` as its data. `Write` requests are accepted but never answered,
+/// the same way an ODrive behaves.
+///
+/// Reading waits for a response for as long as the read timeout allows, like a real
+/// socket. Until a read timeout is set, reading returns immediately if nothing is waiting.
+#[derive(Default)]
+pub struct MockCANSocket {
+    waiting: Mutex<Vec<CANRequest>>,
+    frame_written: Condvar,
+    read_timeout: Mutex<Option<Duration>>,
+}
 
-        /// Mock implementation
-        pub(crate) struct CANSocket {
-            waiting: Vec<CANFrame>,
+impl MockCANSocket {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CanTransport for MockCANSocket {
+    fn open(_ifname: &str) -> io::Result<Self> {
+        Ok(Self::new())
+    }
+
+    fn write_frame(&self, frame: &CANFrame) -> io::Result<()> {
+        // The odrive only responds to Read commands, not Write. This imitates that
+        let request = CANRequest::from_can(frame).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        match request.cmd {
+            ODriveCommand::Read(_) => {
+                self.waiting.lock().unwrap().push(request);
+                self.frame_written.notify_all();
+            },
+            ODriveCommand::Write(_) => {},
         }
 
-        impl CANSocket {
-            pub fn open(_ifname: &str) -> Result<Self, CANSocketOpenError> {
-                Ok(CANSocket{ waiting: Vec::new() })
-            }
-
-            pub fn write_frame(&mut self, frame: &CANFrame) -> io::Result<()> {
-                // The odrive only responds to Read commands, not Write. This imitates that
-                match CANRequest::from_can(&frame).cmd {
-                    ODriveCommand::Read(_) => self.waiting.push(frame.clone()),
-                    ODriveCommand::Write(_) => {},
-                }
-
-                Ok(())
-            }
-
-            pub fn read_frame(&mut self) -> io::Result<CANFrame> {
-                
-                // We return the last item available in order to send responses out of order
-                // since usually it would be FIFO
-                match self.waiting.pop() {
-                    Some(item) => { 
-                        let mut cloned_frame = CANRequest::from_can(&item);
-                        
-                        // We use [99; 8] just to have a response that is not the same as the request
-                        cloned_frame.data = [99; 8];
-
-                        // The CAN response does not respond with RTR enabled
-                        return Ok(cloned_frame.to_can(false))
-                    
-                    },
-                    // For the sake of testing purposes, we return an Io Error that
-                    // indicates this method would be blocked if it waited. In actuality reading and writing occurs
-                    // in parallel so our code would work fine otherwise
-                    None => return Err(io::Error::new(io::ErrorKind::WouldBlock, "no messages available")),
-                }
-            }
-        }   
+        Ok(())
+    }
+
+    fn read_frame(&self) -> io::Result<CANFrame> {
+        let read_timeout = *self.read_timeout.lock().unwrap();
+        let mut waiting = self.waiting.lock().unwrap();
+        if let Some(timeout) = read_timeout {
+            waiting = self.frame_written.wait_timeout_while(waiting, timeout, |waiting| waiting.is_empty()).unwrap().0;
+        }
+
+        // We return the last item available in order to send responses out of order
+        // since usually it would be FIFO
+        match waiting.pop() {
+            Some(mut cloned_frame) => {
+                // We use [99; 8] just to have a response that is not the same as the request
+                cloned_frame.data = [99; 8];
+
+                // The CAN response does not respond with RTR enabled
+                Ok(cloned_frame.to_can(false))
+            },
+            // For the sake of testing purposes, we return an Io Error that
+            // indicates this method would be blocked if it waited. In actuality reading and writing occurs
+            // in parallel so our code would work fine otherwise
+            None => Err(io::Error::new(io::ErrorKind::WouldBlock, "no messages available")),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Duration) -> io::Result<()> {
+        *self.read_timeout.lock().unwrap() = Some(timeout);
+        Ok(())
+    }
+
+    fn set_write_timeout(&self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// The transport `CANProxy::new().unwrap()` opens. Enabling the `mock-socket` feature swaps the
+// real socket for the mock one so that code can be run without any ODrives connected
+cfg_match! {
+    feature = "mock-socket" => {
+        pub(crate) type DefaultTransport = MockCANSocket;
     },
     other => {
-        pub(crate) use socketcan::CANSocket;
-    },
-}
\ No newline at end of file
+        pub(crate) type DefaultTransport = socketcan::CANSocket;
+    }
+}
@@ -1,47 +1,47 @@
 use crate::canframe::CANResponse;
 use crate::error::{AxisError, EncoderError, MotorError, SensorlessError};
-use crate::response::ODriveError;
+use crate::Error;
 use crate::state::{AxisState, ODriveCommand, ReadComm};
 use crate::utils::ResponseManip;
 
 #[derive(Debug, PartialEq)]
 pub struct Heartbeat {
-    axis_error: AxisError,
-    current_state: AxisState,
+    pub axis_error: AxisError,
+    pub current_state: AxisState,
 } // Not including controller status due to lack of docs
 
 #[derive(Debug, PartialEq)]
 pub struct EncoderEstimates {
-    position: f32,
-    velocity: f32,
+    pub position: f32,
+    pub velocity: f32,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct EncoderCount {
-    shadow_count: i32,
-    cpr_count: i32,
+    pub shadow_count: i32,
+    pub cpr_count: i32,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct IQ {
-    setpoint: f32,
-    measured: f32,
+    pub setpoint: f32,
+    pub measured: f32,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Temperature {
-    inverter: f32,
-    motor: f32,
+    pub inverter: f32,
+    pub motor: f32,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Bus {
-    voltage: f32,
-    current: f32,
+    pub voltage: f32,
+    pub current: f32,
 }
 
 impl TryFrom<CANResponse> for Heartbeat {
-    type Error = ODriveError;
+    type Error = Error;
 
     fn try_from(response: CANResponse) -> Result<Self, Self::Error> {
         // Odrive CAN Signal: (0 1 2 3 4 5 6 7)
@@ -51,7 +51,7 @@ impl TryFrom<CANResponse> for Heartbeat {
 
         // Check that the command can be converted into the proper type
         if response.cmd != ODriveCommand::Read(ReadComm::GetHeartbeat) {
-            panic!("Cannot cast cmd {:?} into type Heartbeat", response.cmd)
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "Heartbeat" })
         }
 
         let axis_err_bin: [u8; 4] = response.data[0..4].try_into().unwrap();
@@ -61,186 +61,183 @@ impl TryFrom<CANResponse> for Heartbeat {
         let axis_error = u32::from_le_bytes(axis_err_bin);
         let axis_error = match axis_error.try_into() {
             Ok(val) => val,
-            Err(_) => return Err(ODriveError::ConvertedBadData),
+            Err(_) => return Err(Error::BadData),
         };
 
         // Try to convert the bytes. If it's bad data, return an error
         let current_state = u8::from_le_bytes(axis_state_bin);
         let current_state = match current_state.try_into() {
             Ok(val) => val,
-            Err(_) => return Err(ODriveError::ConvertedBadData),
+            Err(_) => return Err(Error::BadData),
         };
 
-        return Ok(Heartbeat {
+        Ok(Heartbeat {
             axis_error,
             current_state,
-        });
+        })
     }
 }
 
 impl TryFrom<CANResponse> for EncoderEstimates {
-    type Error = ODriveError;
+    type Error = Error;
 
     fn try_from(response: CANResponse) -> Result<Self, Self::Error> {
         // Check that the command can be converted into the proper type
         if response.cmd != ODriveCommand::Read(ReadComm::GetEncoderEstimates) {
-            panic!("Cannot cast cmd {:?} into type Estimates", response.cmd)
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "Estimates" })
         }
 
         let (position_bytes, velocity_bytes) = ResponseManip::split_32(response.data);
 
-        return Ok(EncoderEstimates {
+        Ok(EncoderEstimates {
             position: f32::from_le_bytes(position_bytes),
             velocity: f32::from_le_bytes(velocity_bytes),
-        });
+        })
     }
 }
 
 impl TryFrom<CANResponse> for EncoderCount {
-    type Error = ODriveError;
+    type Error = Error;
 
     fn try_from(response: CANResponse) -> Result<Self, Self::Error> {
         // Check that the command can be converted into the proper type
         if response.cmd != ODriveCommand::Read(ReadComm::GetEncoderCount) {
-            panic!("Cannot cast cmd {:?} into type Counts", response.cmd)
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "Counts" })
         }
 
         let (shadow_bytes, cpr_bytes) = ResponseManip::split_32(response.data);
 
-        return Ok(EncoderCount {
+        Ok(EncoderCount {
             shadow_count: i32::from_le_bytes(shadow_bytes),
             cpr_count: i32::from_le_bytes(cpr_bytes),
-        });
+        })
     }
 }
 
 impl TryFrom<CANResponse> for IQ {
-    type Error = ODriveError;
+    type Error = Error;
 
     fn try_from(response: CANResponse) -> Result<Self, Self::Error> {
         // Check that the command can be converted into the proper type
         if response.cmd != ODriveCommand::Read(ReadComm::GetIQ) {
-            panic!("Cannot cast cmd {:?} into type IQ", response.cmd)
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "IQ" })
         }
 
         let (setpoint_bytes, measured_bytes) = ResponseManip::split_32(response.data);
-        return Ok(Self {
+        Ok(Self {
             setpoint: f32::from_le_bytes(setpoint_bytes),
             measured: f32::from_le_bytes(measured_bytes),
-        });
+        })
     }
 }
 
 impl TryFrom<CANResponse> for Temperature {
-    type Error = ODriveError;
+    type Error = Error;
 
     fn try_from(response: CANResponse) -> Result<Self, Self::Error> {
         // Check that the command can be converted into the proper type
         if response.cmd != ODriveCommand::Read(ReadComm::GetTemperature) {
-            panic!("Cannot cast cmd {:?} into type Temperature", response.cmd)
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "Temperature" })
         }
 
         let (inverter_temp_bytes, motor_temp_bytes) = ResponseManip::split_32(response.data);
         let inverter = f32::from_le_bytes(inverter_temp_bytes);
         let motor = f32::from_le_bytes(motor_temp_bytes);
 
-        return Ok(Temperature { inverter, motor });
+        Ok(Temperature { inverter, motor })
     }
 }
 
 impl TryFrom<CANResponse> for Bus {
-    type Error = ODriveError;
+    type Error = Error;
 
     fn try_from(response: CANResponse) -> Result<Self, Self::Error> {
         // Check that the command can be converted into the proper type
         if response.cmd != ODriveCommand::Read(ReadComm::GetVBusVoltage) {
-            panic!("Cannot cast cmd {:?} into type Bus", response.cmd)
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "Bus" })
         }
 
         let (voltage_bytes, current_bytes) = ResponseManip::split_32(response.data);
         let voltage = f32::from_le_bytes(voltage_bytes);
         let current = f32::from_le_bytes(current_bytes);
 
-        return Ok(Bus { voltage, current });
+        Ok(Bus { voltage, current })
     }
 }
 
 impl TryFrom<CANResponse> for MotorError {
-    type Error = ODriveError;
+    type Error = Error;
 
     fn try_from(response: CANResponse) -> Result<Self, Self::Error> {
         // Check that the command can be converted into the proper type
         if response.cmd != ODriveCommand::Read(ReadComm::MotorError) {
-            panic!("Cannot cast cmd {:?} into type MotorError", response.cmd)
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "MotorError" })
         }
 
         // Try to convert the bytes. If it's bad data, return an error
         let motor_error = u64::from_le_bytes(response.data);
-        return match motor_error.try_into() {
+        match motor_error.try_into() {
             Ok(val) => Ok(val),
-            Err(_) => Err(ODriveError::ConvertedBadData),
-        };
+            Err(_) => Err(Error::BadData),
+        }
     }
 }
 
 impl TryFrom<CANResponse> for EncoderError {
-    type Error = ODriveError;
+    type Error = Error;
 
     fn try_from(response: CANResponse) -> Result<Self, Self::Error> {
         // Check that the command can be converted into the proper type
         if response.cmd != ODriveCommand::Read(ReadComm::EncoderError) {
-            panic!("Cannot cast cmd {:?} into type EncoderError", response.cmd)
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "EncoderError" })
         }
 
         let (encoder_error_bytes, _) = ResponseManip::split_32(response.data);
 
         // Try to convert the bytes. If it's bad data, return an error
         let encoder_error = u32::from_le_bytes(encoder_error_bytes);
-        return match encoder_error.try_into() {
+        match encoder_error.try_into() {
             Ok(val) => Ok(val),
-            Err(_) => Err(ODriveError::ConvertedBadData),
-        };
+            Err(_) => Err(Error::BadData),
+        }
     }
 }
 
 impl TryFrom<CANResponse> for SensorlessError {
-    type Error = ODriveError;
+    type Error = Error;
 
     fn try_from(response: CANResponse) -> Result<Self, Self::Error> {
         // Check that the command can be converted into the proper type
         if response.cmd != ODriveCommand::Read(ReadComm::SensorlessError) {
-            panic!(
-                "Cannot cast cmd {:?} into type SensorlessError",
-                response.cmd
-            );
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "SensorlessError" });
         }
 
         let (sensorless_error_bytes, _) = ResponseManip::split_32(response.data);
         // Try to convert the bytes. If it's bad data, return an error
         let sensorless_error = u32::from_le_bytes(sensorless_error_bytes);
-        return match sensorless_error.try_into() {
+        match sensorless_error.try_into() {
             Ok(val) => Ok(val),
-            Err(_) => Err(ODriveError::ConvertedBadData),
-        };
+            Err(_) => Err(Error::BadData),
+        }
     }
 }
 
 impl TryFrom<CANResponse> for () {
-    type Error = ODriveError;
+    type Error = Error;
 
-    fn try_from(response: CANResponse) -> Result<Self, ODriveError> {
+    fn try_from(response: CANResponse) -> Result<Self, Error> {
         if let ODriveCommand::Read(_cmd) = response.cmd {
-            panic!("Can only cast a Write command into () since it contains no response, not Read({:?})", response.cmd);
+            // Only a Write command can be cast into () since it contains no response
+            return Err(Error::WrongCommand { cmd: response.cmd, into: "()" });
         }
-        return Ok(());
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::panic;
-
     use crate::{
+        Error,
         canframe::CANResponse,
         casts::{Bus, Temperature},
         error::{AxisError, EncoderError, MotorError, SensorlessError},
@@ -250,10 +247,10 @@ mod tests {
 
     use super::{EncoderCount, EncoderEstimates, Heartbeat, IQ};
 
-    fn bad_convert_test<BadType: TryFrom<CANResponse>>(data: CANResponse) {
-        // Test panic if attempts to cast into wrong return type
-        let result_wrong_type = panic::catch_unwind(|| TryInto::<BadType>::try_into(data));
-        assert!(result_wrong_type.is_err());
+    fn bad_convert_test<BadType: TryFrom<CANResponse, Error = Error>>(data: CANResponse) {
+        // Test error if attempts to cast into wrong return type
+        let result_wrong_type = TryInto::<BadType>::try_into(data);
+        assert!(matches!(result_wrong_type, Err(Error::WrongCommand { .. })));
     }
 
     #[test]
@@ -276,11 +273,11 @@ mod tests {
         };
 
         assert_eq!(
-            TryInto::<Heartbeat>::try_into(fake_response.clone()).unwrap(),
+            TryInto::<Heartbeat>::try_into(fake_response).unwrap(),
             expected
         );
 
-        // Test panic if attempts to cast into wrong return type
+        // Test error if attempts to cast into wrong return type
         bad_convert_test::<Temperature>(fake_response);
     }
 
@@ -307,7 +304,7 @@ mod tests {
             expected
         );
 
-        // Test panic if attempts to cast into wrong return type
+        // Test error if attempts to cast into wrong return type
         bad_convert_test::<Heartbeat>(fake_response);
     }
 
@@ -333,7 +330,7 @@ mod tests {
             expected
         );
 
-        // Test panic if attempts to cast into wrong return type
+        // Test error if attempts to cast into wrong return type
         bad_convert_test::<Temperature>(fake_response);
     }
 
@@ -357,7 +354,7 @@ mod tests {
 
         assert_eq!(TryInto::<IQ>::try_into(fake_response).unwrap(), expected);
 
-        // Test panic if attempts to cast into wrong return type
+        // Test error if attempts to cast into wrong return type
         bad_convert_test::<Temperature>(fake_response);
     }
 
@@ -378,7 +375,7 @@ mod tests {
         let fake_response = CANResponse {
             axis: 1,
             cmd: ODriveCommand::Read(ReadComm::GetTemperature),
-            data: data,
+            data,
         };
 
         assert_eq!(
@@ -386,7 +383,7 @@ mod tests {
             expected
         );
 
-        // Test panic if attempts to cast into wrong return type
+        // Test error if attempts to cast into wrong return type
         bad_convert_test::<Heartbeat>(fake_response);
     }
 
@@ -406,12 +403,12 @@ mod tests {
         let fake_response = CANResponse {
             axis: 1,
             cmd: ODriveCommand::Read(ReadComm::GetVBusVoltage),
-            data: data,
+            data,
         };
 
         assert_eq!(TryInto::<Bus>::try_into(fake_response).unwrap(), expected);
 
-        // Test panic if attempts to cast into wrong return type
+        // Test error if attempts to cast into wrong return type
         bad_convert_test::<Temperature>(fake_response);
     }
 
@@ -430,7 +427,7 @@ mod tests {
             expected
         );
 
-        // Test panic if attempts to cast into wrong return type
+        // Test error if attempts to cast into wrong return type
         bad_convert_test::<Temperature>(fake_response);
     }
 
@@ -449,7 +446,7 @@ mod tests {
             expected
         );
 
-        // Test panic if attempts to cast into wrong return type
+        // Test error if attempts to cast into wrong return type
         bad_convert_test::<Temperature>(fake_response);
     }
 
@@ -469,7 +466,7 @@ mod tests {
             expected
         );
 
-        // Test panic if attempts to cast into wrong return type
+        // Test error if attempts to cast into wrong return type
         bad_convert_test::<Temperature>(fake_response);
     }
 
@@ -484,16 +481,14 @@ mod tests {
 
         TryInto::<()>::try_into(fake_response).unwrap();
 
-        // Test panic if you convert a read command to ()
+        // Test error if you convert a read command to ()
         let fake_response = CANResponse {
             axis: 1,
             cmd: ODriveCommand::Read(ReadComm::MotorError),
             data: u64::to_le_bytes(MotorError::DRVFault as u64),
         };
 
-        let cant_convert = panic::catch_unwind(|| {
-            TryInto::<()>::try_into(fake_response).unwrap();
-        });
-        assert!(cant_convert.is_err());
+        let cant_convert = TryInto::<()>::try_into(fake_response);
+        assert!(matches!(cant_convert, Err(Error::WrongCommand { cmd: ODriveCommand::Read(ReadComm::MotorError), into: "()" })));
     }
 }
@@ -0,0 +1,135 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    axis::AxisID,
+    canframe::FrameFilter,
+    casts::Heartbeat,
+    state::ReadComm,
+    threads::CANThreadCommunicator,
+};
+
+/// How long to listen for heartbeats by default. This is a few times the default
+/// heartbeat interval of the ODrive (`axis.config.can.heartbeat_rate_ms`, 100ms)
+pub const DEFAULT_DISCOVERY_WINDOW: Duration = Duration::from_millis(300);
+
+/// An axis that broadcast its heartbeat while the bus was being listened to
+#[derive(Debug, PartialEq)]
+pub struct DiscoveredNode {
+    pub axis_id: AxisID,
+    /// The most recent heartbeat of the axis. This is `None` if none of its heartbeats
+    /// could be decoded, e.g. because several axis errors were set at once
+    pub heartbeat: Option<Heartbeat>,
+    /// How many heartbeats were received from the axis during the window
+    pub heartbeats: usize,
+}
+
+/// The axes found by listening for heartbeats, ordered by axis ID
+#[derive(Debug, Default, PartialEq)]
+pub struct Inventory {
+    nodes: BTreeMap<AxisID, DiscoveredNode>,
+}
+
+impl Inventory {
+    /// Returns the IDs of every discovered axis in ascending order. These can be passed
+    /// directly to [`ODriveGroup::new()`](crate::odrivegroup::ODriveGroup::new)
+    pub fn axis_ids(&self) -> Vec<AxisID> {
+        self.nodes.keys().copied().collect()
+    }
+
+    /// Returns the discovered axis with the given ID, if it was heard from
+    pub fn node(&self, axis_id: AxisID) -> Option<&DiscoveredNode> {
+        self.nodes.get(&axis_id)
+    }
+
+    /// Returns every discovered axis in ascending order of axis ID
+    pub fn nodes(&self) -> impl Iterator<Item = &DiscoveredNode> {
+        self.nodes.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// Listens for heartbeats for the duration of `window` and returns every axis that sent one.
+/// The window should be longer than the heartbeat interval of the ODrives
+/// (`axis.config.can.heartbeat_rate_ms`, 100ms by default).
+pub(crate) fn discover<C: CANThreadCommunicator>(can: &C, window: Duration) -> Inventory {
+    let heartbeats = can.subscribe(FrameFilter::cmd(ReadComm::GetHeartbeat));
+    let mut inventory = Inventory::default();
+
+    let deadline = Instant::now() + window;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        let frame = match heartbeats.recv_timeout(remaining) {
+            Ok(frame) => frame,
+            Err(_) => continue,
+        };
+        let axis_id = frame.axis as AxisID;
+        let node = inventory.nodes.entry(axis_id).or_insert(DiscoveredNode {
+            axis_id,
+            heartbeat: None,
+            heartbeats: 0,
+        });
+        node.heartbeats += 1;
+        if let Ok(heartbeat) = Heartbeat::try_from(frame) {
+            node.heartbeat = Some(heartbeat);
+        }
+    }
+
+    inventory
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, time::Duration};
+
+    use crate::{
+        canproxy::CANProxy,
+        error::AxisError,
+        simulator::ODriveSimulator,
+        state::AxisState,
+        tests::wait_for_msgs,
+    };
+
+    #[test]
+    fn test_discover_nodes() {
+        let simulator = ODriveSimulator::new(&[4, 1, 9]);
+        simulator.with_node(9, |node| {
+            node.state = AxisState::ClosedLoop;
+            node.axis_error = AxisError::WatchdogTimerExpired as u32;
+        });
+        let mut can_proxy = CANProxy::with_transport(simulator);
+
+        let (send, rcv) = channel();
+        can_proxy.register_ro("discovery", move |can_read| {
+            send.send(can_read.discover(Duration::from_millis(350))).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let inventory = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(inventory.axis_ids(), vec![1, 4, 9]);
+        assert!(inventory.nodes().all(|node| node.heartbeats >= 2));
+
+        let idle = inventory.node(1).unwrap().heartbeat.as_ref().unwrap();
+        assert_eq!(idle.current_state, AxisState::Idle);
+        assert_eq!(idle.axis_error, AxisError::NoError);
+
+        let faulted = inventory.node(9).unwrap().heartbeat.as_ref().unwrap();
+        assert_eq!(faulted.current_state, AxisState::ClosedLoop);
+        assert_eq!(faulted.axis_error, AxisError::WatchdogTimerExpired);
+        assert!(inventory.node(2).is_none());
+    }
+}
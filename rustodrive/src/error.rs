@@ -1,9 +1,126 @@
-use crate::back_to_enum;
+use std::{fmt, io};
+
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    axis::AxisID,
+    back_to_enum,
+    limits::Limit,
+    provisioning::MAX_NODE_ID,
+    response::ErrorResponse,
+    state::{AxisState, ODriveCommand},
+    transition::TransitionOutcome,
+};
+
+/// The error type for everything in this crate that can fail without it being a bug
+#[derive(Debug)]
+pub enum Error {
+    /// The CAN interface could not be opened
+    OpenFailed { interface: String, source: io::Error },
+    /// A thread with the same name is already registered with the `CANProxy`
+    DuplicateThread(&'static str),
+    /// A thread with read and write access is already registered with the `CANProxy`
+    ReadWriteTaken,
+    /// No thread with the name is registered with the `CANProxy`
+    UnknownThread(String),
+    /// A thread panicked and could not be joined
+    ThreadPanicked(String),
+    /// The CAN ID of a frame does not correspond to any ODrive command
+    UnknownCommand(u32),
+    /// A frame did not contain the 8 bytes of data every ODrive message has
+    BadFrameLength(usize),
+    /// A response was converted into the type of a different command
+    WrongCommand { cmd: ODriveCommand, into: &'static str },
+    /// The data of a response could not be converted
+    BadData,
+    /// The axis is not part of the group
+    UnknownAxis(AxisID),
+    /// No sub-group with the name was defined
+    UnknownGroup(String),
+    /// The group has no axes to send the request to
+    EmptyGroup,
+    /// No heartbeat was heard from the axis
+    NodeNotFound(AxisID),
+    /// Another axis on the bus already uses the node ID
+    NodeIdTaken(AxisID),
+    /// The soft limits of the axis cannot be applied, see [`SoftLimits::validate()`](crate::limits::SoftLimits::validate)
+    InvalidLimits { axis: AxisID, limit: Limit },
+    /// The node ID does not fit in the 6 bits of the CAN ID that address the axis
+    NodeIdOutOfRange(AxisID),
+    /// The axis did not come back with its new node ID after saving the configuration
+    NodeIdNotSaved(AxisID),
+    /// The axis did not enter a state it needed to be in
+    StateNotReached { axis: AxisID, state: AxisState, outcome: TransitionOutcome },
+    /// The axis did not find its home before the timeout
+    HomingTimedOut(AxisID),
+    /// A spline needs at least two waypoints, in strictly increasing order of time
+    InvalidWaypoints,
+    /// A stream needs a period longer than zero
+    InvalidPeriod,
+    /// A coordinated move needs a finite and positive maximum velocity and acceleration
+    InvalidMove,
+    /// Switching the axes over to a coordinated move failed. The axes in `reconfigured` already
+    /// accepted some of the new trajectory limits or control mode
+    MoveSetupFailed { reconfigured: Vec<AxisID>, error: ErrorResponse },
+    /// A request to the ODrive failed
+    Request(ErrorResponse),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::OpenFailed { interface, source } => write!(f, "could not open CAN interface {}: {}", interface, source),
+            Error::DuplicateThread(name) => write!(f, "two threads cannot have the same name ({})", name),
+            Error::ReadWriteTaken => write!(f, "only one thread can have write access to the CAN device"),
+            Error::UnknownThread(name) => write!(f, "no thread named {} is registered", name),
+            Error::ThreadPanicked(name) => write!(f, "thread {} panicked", name),
+            Error::UnknownCommand(can_id) => write!(f, "CAN ID {:#x} does not correspond to an ODrive command", can_id),
+            Error::BadFrameLength(len) => write!(f, "expected a frame with 8 bytes of data, got {}", len),
+            Error::WrongCommand { cmd, into } => write!(f, "cannot convert the response to {:?} into {}", cmd, into),
+            Error::BadData => write!(f, "the response contained data that could not be converted"),
+            Error::UnknownAxis(axis) => write!(f, "axis {} is not part of the group", axis),
+            Error::UnknownGroup(name) => write!(f, "no sub-group named {} was defined", name),
+            Error::EmptyGroup => write!(f, "the group has no axes"),
+            Error::NodeNotFound(axis) => write!(f, "no heartbeat was heard from axis {}", axis),
+            Error::NodeIdTaken(axis) => write!(f, "node ID {} is already used by another axis", axis),
+            Error::InvalidLimits { axis, limit } => write!(f, "the {:?} limit of axis {} is not a valid range", limit, axis),
+            Error::NodeIdOutOfRange(axis) => write!(f, "node ID {} is out of range, the highest is {:#x}", axis, MAX_NODE_ID),
+            Error::NodeIdNotSaved(axis) => write!(f, "axis {} did not come back with its new node ID after rebooting", axis),
+            Error::StateNotReached { axis, state, outcome } => write!(f, "axis {} did not enter {}: {:?}", axis, state, outcome),
+            Error::HomingTimedOut(axis) => write!(f, "axis {} did not find its home in time", axis),
+            Error::InvalidWaypoints => write!(f, "a spline needs at least two waypoints in increasing order of time"),
+            Error::InvalidPeriod => write!(f, "a stream needs a period longer than zero"),
+            Error::InvalidMove => write!(f, "a move needs a finite and positive maximum velocity and acceleration"),
+            Error::MoveSetupFailed { reconfigured, error } => write!(
+                f,
+                "request {:?} failed while setting up the move: {:?}, axes {:?} were already reconfigured",
+                error.request, error.err, reconfigured
+            ),
+            Error::Request(response) => write!(f, "request {:?} failed: {:?}", response.request, response.err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::OpenFailed { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorResponse> for Error {
+    fn from(response: ErrorResponse) -> Self {
+        Error::Request(response)
+    }
+}
 
 // See documentation: https://docs.odriverobotics.com/v/latest/fibre_types/com_odriverobotics_ODrive.html?highlight=error#ODrive.Error
 back_to_enum! { u32,
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, PartialEq, Clone, EnumIter)]
     pub enum AxisError { 
+        NoError = 0x0,
         Initializing = 0x1,
         SystemLevel = 0x2,
         TimingError = 0x4,
@@ -28,8 +145,10 @@ back_to_enum! { u32,
 
 
 back_to_enum! { u64,
-    #[derive(Debug, PartialEq, Clone)]
+    #[derive(Debug, PartialEq, Clone, EnumIter)]
+    #[repr(u64)]
     pub enum MotorError {
+        NoError = 0x0,
         PhaseResistanceOFR = 0x1,
         PhaseInductanceOFR = 0x2,
         DRVFault = 0x8,
@@ -61,8 +180,9 @@ back_to_enum! { u64,
 }
 
 back_to_enum! { u32,
-    #[derive(Debug, PartialEq, Clone)]
+    #[derive(Debug, PartialEq, Clone, EnumIter)]
     pub enum EncoderError {
+        NoError = 0x0,
         UnstableGain = 0x1,
         CPRPolepairsMismatch = 0x2,
         NoResponse = 0x4,
@@ -79,7 +199,25 @@ back_to_enum! { u32,
 back_to_enum! { u32,
     #[derive(Debug, PartialEq, Clone)]
     pub enum SensorlessError {
+        NoError = 0x0,
         UnstableGain = 0x1,
         UnknownCurrentMeasurement = 0x2,
     }
-}
\ No newline at end of file
+}
+/// The ODrive reports errors as bit flags, so several can be set at once.
+/// `from_bits` splits them up into the individual errors, ignoring unknown bits.
+macro_rules! impl_from_bits {
+    ($name:ident, $bits:ty) => {
+        impl $name {
+            pub fn from_bits(bits: $bits) -> Vec<$name> {
+                $name::iter()
+                    .filter(|error| *error != $name::NoError && bits & (error.clone() as $bits) != 0)
+                    .collect()
+            }
+        }
+    };
+}
+
+impl_from_bits!(AxisError, u32);
+impl_from_bits!(MotorError, u64);
+impl_from_bits!(EncoderError, u32);
@@ -0,0 +1,418 @@
+use std::collections::HashSet;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use socketcan::CANFrame;
+
+use crate::{canframe::ODriveCANFrame, cansocket::CanTransport};
+
+/// Describes which faults a [`FaultyTransport`] injects and how often.
+///
+/// All rates are probabilities between `0.0` (never) and `1.0` (always) that are
+/// rolled for every frame. By default no faults are injected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaultConfig {
+    /// Chance that a frame received from the bus is lost
+    pub drop_rate: f64,
+    /// Chance that a frame received from the bus has its payload corrupted
+    pub corrupt_rate: f64,
+    /// Chance that a frame is delivered after another frame that arrived later. The frame is
+    /// held back until the next frame has been read, or for at most 5ms if none arrives
+    pub reorder_rate: f64,
+    /// Chance that writing a frame fails with an error
+    pub send_failure_rate: f64,
+    /// How long every received frame is held back before it can be read
+    pub delay: Duration,
+    /// Up to this much extra delay is randomly added to every received frame
+    pub delay_jitter: Duration,
+    /// Nodes that stopped answering. Frames to them never arrive and they send nothing back
+    pub silent_nodes: HashSet<u32>,
+    /// Seed for the random number generator so that a run can be reproduced
+    pub seed: u64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            drop_rate: 0.0,
+            corrupt_rate: 0.0,
+            reorder_rate: 0.0,
+            send_failure_rate: 0.0,
+            delay: Duration::ZERO,
+            delay_jitter: Duration::ZERO,
+            silent_nodes: HashSet::new(),
+            seed: 0x5EED,
+        }
+    }
+}
+
+/// Counts how many times each fault was injected
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FaultStats {
+    pub dropped: usize,
+    pub corrupted: usize,
+    pub reordered: usize,
+    pub failed_sends: usize,
+    pub silenced: usize,
+}
+
+/// The longest a frame is held back waiting for a later frame to overtake it
+const REORDER_WINDOW: Duration = Duration::from_millis(5);
+
+/// Small xorshift generator so that faults are reproducible without an extra dependency
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on a state of 0
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a number in the range [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, rate: f64) -> bool {
+        rate > 0.0 && self.next_f64() < rate
+    }
+}
+
+struct FaultState {
+    config: FaultConfig,
+    rng: Rng,
+    stats: FaultStats,
+    /// Frames that were received from the bus, in the order they were received
+    held: Vec<HeldFrame>,
+}
+
+struct HeldFrame {
+    /// When the frame may be read
+    due: Instant,
+    frame: CANFrame,
+    /// The frame waits for a later frame to be read before it
+    reorder: bool,
+}
+
+impl HeldFrame {
+    /// Whether the frame can be read now. A frame waiting to be overtaken is released
+    /// anyway once nothing overtook it within the reorder window
+    fn deliverable(&self, now: Instant) -> bool {
+        self.due <= now && (!self.reorder || self.due + REORDER_WINDOW <= now)
+    }
+}
+
+/// A handle to change the faults a [`FaultyTransport`] injects while it is being used
+/// by a [`CANProxy`](crate::canproxy::CANProxy)
+#[derive(Clone)]
+pub struct FaultHandle {
+    state: Arc<Mutex<FaultState>>,
+}
+
+impl FaultHandle {
+    /// Replaces the fault configuration. The random number generator is reseeded
+    pub fn set_config(&self, config: FaultConfig) {
+        let mut state = self.state.lock().unwrap();
+        state.rng = Rng::new(config.seed);
+        state.config = config;
+    }
+
+    pub fn config(&self) -> FaultConfig {
+        self.state.lock().unwrap().config.clone()
+    }
+
+    /// Makes the node stop answering, as if it lost power or its cable was unplugged
+    pub fn silence_node(&self, node_id: u32) {
+        self.state.lock().unwrap().config.silent_nodes.insert(node_id);
+    }
+
+    /// Makes a node that was silenced answer again
+    pub fn restore_node(&self, node_id: u32) {
+        self.state.lock().unwrap().config.silent_nodes.remove(&node_id);
+    }
+
+    pub fn stats(&self) -> FaultStats {
+        self.state.lock().unwrap().stats.clone()
+    }
+}
+
+/// A [`CanTransport`] that wraps another transport (usually a
+/// [`MockCANSocket`](crate::cansocket::MockCANSocket) or an
+/// [`ODriveSimulator`](crate::simulator::ODriveSimulator)) and injects bus faults into it:
+/// dropped, delayed, reordered and corrupted frames, failed sends and nodes that stop answering.
+///
+/// # Example
+/// ```
+/// use rustodrive::canproxy::CANProxy;
+/// use rustodrive::cansocket::MockCANSocket;
+/// use rustodrive::faults::{FaultConfig, FaultyTransport};
+///
+/// let transport = FaultyTransport::new(MockCANSocket::new(), FaultConfig {
+///     drop_rate: 0.1,
+///     ..Default::default()
+/// });
+/// let faults = transport.handle();
+/// let mut can_proxy = CANProxy::with_transport(transport);
+///
+/// // Unplug node 3 later on
+/// faults.silence_node(3);
+/// ```
+pub struct FaultyTransport<T: CanTransport> {
+    inner: T,
+    state: Arc<Mutex<FaultState>>,
+}
+
+impl<T: CanTransport> FaultyTransport<T> {
+    pub fn new(inner: T, config: FaultConfig) -> Self {
+        Self {
+            inner,
+            state: Arc::new(Mutex::new(FaultState {
+                rng: Rng::new(config.seed),
+                config,
+                stats: FaultStats::default(),
+                held: Vec::new(),
+            })),
+        }
+    }
+
+    /// Returns a handle that can change the injected faults after this transport
+    /// has been given to the `CANProxy`
+    pub fn handle(&self) -> FaultHandle {
+        FaultHandle { state: self.state.clone() }
+    }
+
+    /// The node a frame was sent to or received from
+    fn node_id(frame: &CANFrame) -> u32 {
+        frame.id() >> ODriveCANFrame::AXIS_BITS
+    }
+}
+
+impl<T: CanTransport> CanTransport for FaultyTransport<T> {
+    fn open(ifname: &str) -> io::Result<Self> {
+        Ok(Self::new(T::open(ifname)?, FaultConfig::default()))
+    }
+
+    fn write_frame(&self, frame: &CANFrame) -> io::Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let rate = state.config.send_failure_rate;
+            if state.rng.chance(rate) {
+                state.stats.failed_sends += 1;
+                return Err(io::Error::other("injected send failure"));
+            }
+
+            // The frame is acknowledged by the other nodes on the bus, but never reaches its target
+            if state.config.silent_nodes.contains(&Self::node_id(frame)) {
+                state.stats.silenced += 1;
+                return Ok(());
+            }
+        }
+
+        self.inner.write_frame(frame)
+    }
+
+    fn read_frame(&self) -> io::Result<CANFrame> {
+        // Held frames that are already due are delivered without waiting on the inner transport
+        let now = Instant::now();
+        let received = match self.state.lock().unwrap().held.iter().any(|held| held.deliverable(now)) {
+            true => Err(io::Error::new(io::ErrorKind::WouldBlock, "held frames are due")),
+            false => self.inner.read_frame(),
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let FaultState { config, rng, stats, held } = &mut *state;
+
+        match received {
+            Ok(frame) if config.silent_nodes.contains(&Self::node_id(&frame)) => stats.silenced += 1,
+            Ok(_) if rng.chance(config.drop_rate) => stats.dropped += 1,
+            Ok(frame) => {
+                let mut data = frame.data().to_vec();
+                if !data.is_empty() && rng.chance(config.corrupt_rate) {
+                    stats.corrupted += 1;
+                    let byte = (rng.next_u64() % data.len() as u64) as usize;
+                    let bit = rng.next_u64() % 8;
+                    data[byte] ^= 1 << bit;
+                }
+
+                let jitter = config.delay_jitter.mul_f64(rng.next_f64());
+                held.push(HeldFrame {
+                    due: Instant::now() + config.delay + jitter,
+                    frame: CANFrame::new(frame.id(), &data, frame.is_rtr(), frame.is_error()).unwrap(),
+                    reorder: rng.chance(config.reorder_rate),
+                });
+            }
+            // Errors other than there being nothing to read are passed on as is
+            Err(err) if !matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                return Err(err);
+            }
+            Err(_) => {}
+        }
+
+        // Deliver the earliest frame that can be read. Frames that are waiting before it were overtaken
+        let now = Instant::now();
+        let index = match (0..held.len()).filter(|i| held[*i].deliverable(now)).min_by_key(|i| held[*i].due) {
+            Some(index) => index,
+            None => return Err(io::Error::new(io::ErrorKind::WouldBlock, "no messages available")),
+        };
+        let delivered = held.remove(index);
+        for overtaken in held.iter_mut().filter(|held| held.reorder && held.due <= delivered.due) {
+            overtaken.reorder = false;
+            stats.reordered += 1;
+        }
+        Ok(delivered.frame)
+    }
+
+    fn set_read_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.inner.set_write_timeout(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::mpsc::channel, time::Duration};
+
+    use crate::{
+        canframe::CANRequest,
+        canproxy::{CANProxy, RetryPolicy},
+        cansocket::MockCANSocket,
+        casts::Heartbeat,
+        odrivegroup::ODriveGroup,
+        response::{ErrorResponse, ODriveError, ODriveResponse},
+        simulator::ODriveSimulator,
+        state::{ODriveCommand, ReadComm, WriteComm},
+        tests::wait_for_msgs,
+        utils::ResultAll,
+    };
+
+    use super::{FaultConfig, FaultyTransport};
+
+    fn requests(cmd: ODriveCommand) -> Vec<CANRequest> {
+        (0..10).map(|axis| CANRequest { axis, cmd, data: [0; 8] }).collect()
+    }
+
+    /// Sends all the requests through a proxy using the faulty transport and returns the responses
+    fn run_requests(transport: FaultyTransport<MockCANSocket>, policy: RetryPolicy, requests: Vec<CANRequest>) -> Vec<ODriveResponse> {
+        let mut can_proxy = CANProxy::with_transport(transport);
+        can_proxy.set_timeout(Duration::from_millis(50));
+        can_proxy.set_retry_policy(policy);
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            send.send(can_rw.request_many(requests)).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let responses = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+        responses
+    }
+
+    #[test]
+    fn test_silent_node() {
+        let simulator = ODriveSimulator::new(&[0, 1, 2]);
+        let transport = FaultyTransport::new(simulator, FaultConfig::default());
+        transport.handle().silence_node(1);
+
+        let mut can_proxy = CANProxy::with_transport(transport);
+        can_proxy.set_timeout(Duration::from_millis(50));
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1, 2]);
+            send.send(odrives.all_axes::<Heartbeat, _>(|ax| ax.get_heartbeat())).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let responses = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert!(responses[0].is_ok());
+        assert_eq!(responses[1].as_ref().unwrap_err().err, ODriveError::NoResponse);
+        assert!(responses[2].is_ok());
+    }
+
+    #[test]
+    fn test_send_failures_are_retried() {
+        let config = FaultConfig { send_failure_rate: 0.5, ..Default::default() };
+        let transport = FaultyTransport::new(MockCANSocket::new(), config);
+        let faults = transport.handle();
+
+        let policy = RetryPolicy { send_retries: 20, response_retries: 0 };
+        let responses = run_requests(transport, policy, requests(ODriveCommand::Write(WriteComm::SetInputVelocity)));
+
+        assert_eq!(responses.unwrap_all().len(), 10);
+        assert!(faults.stats().failed_sends > 0);
+    }
+
+    #[test]
+    fn test_dropped_frames() {
+        let config = FaultConfig { drop_rate: 1.0, ..Default::default() };
+        let transport = FaultyTransport::new(MockCANSocket::new(), config);
+
+        let policy = RetryPolicy { send_retries: 0, response_retries: 1 };
+        let reqs = requests(ODriveCommand::Read(ReadComm::GetIQ));
+        let responses = run_requests(transport, policy, reqs.clone());
+
+        for (request, response) in reqs.into_iter().zip(responses) {
+            assert_eq!(response, Err(ErrorResponse { request, err: ODriveError::NoResponse }));
+        }
+    }
+
+    #[test]
+    fn test_delayed_responses() {
+        let reqs = requests(ODriveCommand::Read(ReadComm::GetIQ));
+        let policy = RetryPolicy { send_retries: 0, response_retries: 0 };
+
+        // Responses arriving before the deadline are still delivered
+        let config = FaultConfig { delay: Duration::from_millis(10), ..Default::default() };
+        let responses = run_requests(FaultyTransport::new(MockCANSocket::new(), config), policy, reqs.clone());
+        assert_eq!(responses.unwrap_all().len(), 10);
+
+        // Responses arriving after the deadline are not
+        let config = FaultConfig { delay: Duration::from_millis(200), ..Default::default() };
+        let responses = run_requests(FaultyTransport::new(MockCANSocket::new(), config), policy, reqs);
+        assert!(responses.iter().all(|res| res.as_ref().unwrap_err().err == ODriveError::NoResponse));
+    }
+
+    #[test]
+    fn test_reordered_and_corrupted_responses() {
+        let config = FaultConfig { reorder_rate: 0.5, corrupt_rate: 1.0, ..Default::default() };
+        let transport = FaultyTransport::new(MockCANSocket::new(), config);
+        let faults = transport.handle();
+
+        let reqs = requests(ODriveCommand::Read(ReadComm::GetIQ));
+        let responses = run_requests(transport, RetryPolicy::default(), reqs.clone()).unwrap_all();
+
+        // request_many still returns the responses in the order the requests were made
+        for (request, response) in reqs.into_iter().zip(responses) {
+            let (_, body) = response.body();
+            assert!(body.is_response(&request));
+            assert_ne!(body.data, [99; 8]);
+        }
+        assert_eq!(faults.stats().corrupted, 10);
+        assert!(faults.stats().reordered > 0);
+    }
+
+    #[test]
+    fn test_handle_changes_config() {
+        let transport = FaultyTransport::new(MockCANSocket::new(), FaultConfig::default());
+        let faults = transport.handle();
+
+        faults.set_config(FaultConfig { silent_nodes: HashSet::from([4]), ..Default::default() });
+        faults.restore_node(4);
+        faults.silence_node(5);
+        assert_eq!(faults.config().silent_nodes, HashSet::from([5]));
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    axis::{Axis, AxisID},
+    canframe::{CANRequest, CANResponse},
+    casts::{EncoderEstimates, IQ},
+    response::ErrorResponse,
+    state::{AxisState, ControlMode, InputMode},
+    threads::ReadWriteCANThread,
+    transition::{self, TransitionOutcome},
+    Error,
+};
+
+/// How often the current and position are read while homing
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// How close in turns the axis has to get to its home before it is zeroed
+const HOME_TOLERANCE: f32 = 0.01;
+/// How slow in turns/s the axis has to be at its home before it is zeroed, so it does not coast
+const SETTLED_VELOCITY: f32 = 0.02;
+/// How many reads in a row the current has to be high while the axis barely moves for it to
+/// count as stalled against a hard stop
+const STALL_SAMPLES: u32 = 3;
+
+/// What the axis uses as its reference point
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HomingMethod {
+    /// Drive slowly until the motor current spikes against a mechanical end stop
+    HardStop,
+    /// Run `EncoderIndexSearch` and use the index pulse of the encoder. The direction and
+    /// speed of the search are taken from the ODrive configuration, not from [`HomingConfig`].
+    /// The rotor coasts past the index before it stops, so the home is taken from where the
+    /// ODrive puts the index, [`HomingConfig::index_offset`], rather than where the rotor stopped
+    Index,
+}
+
+/// Which way the axis moves to find a hard stop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HomingDirection {
+    Positive,
+    Negative,
+}
+
+impl HomingDirection {
+    fn sign(self) -> f32 {
+        match self {
+            HomingDirection::Positive => 1.0,
+            HomingDirection::Negative => -1.0,
+        }
+    }
+}
+
+/// How a single axis is homed
+#[derive(Clone, Debug, PartialEq)]
+pub struct HomingConfig {
+    pub method: HomingMethod,
+    pub direction: HomingDirection,
+    /// Velocity to drive towards a hard stop at in turns/s
+    pub speed: f32,
+    /// Measured motor current in Amps above which the axis counts as having hit a hard stop,
+    /// either once it was moving or while it stalls right where it started
+    pub current_threshold: f32,
+    /// Position of the home relative to the reference point in turns. For hard stops this
+    /// is usually a small distance away from the stop so the axis does not rest against it
+    pub offset: f32,
+    /// Position the ODrive gives the index pulse once it is found, `axis.encoder.config.index_offset`
+    pub index_offset: f32,
+    /// Counts per revolution of the encoder, needed to zero it with `SetLinearCount`
+    pub encoder_cpr: i32,
+    /// How long finding the reference point and moving to the home may take
+    pub timeout: Duration,
+}
+
+impl Default for HomingConfig {
+    fn default() -> Self {
+        Self {
+            method: HomingMethod::HardStop,
+            direction: HomingDirection::Negative,
+            speed: 0.5,
+            current_threshold: 5.0,
+            offset: 0.0,
+            index_offset: 0.0,
+            encoder_cpr: 8192,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Finds the home of a single axis and zeroes its encoder there. Returns the position of the
+/// home as it was measured before zeroing. The axis is left in `Idle`, also when homing fails.
+/// `check` is applied to the velocity the axis drives towards a hard stop with.
+pub(crate) fn home_axis<C>(can: &ReadWriteCANThread, axis_id: AxisID, config: &HomingConfig, check: C) -> Result<f32, Error>
+where
+    C: Fn(CANRequest) -> Result<CANRequest, ErrorResponse>,
+{
+    let axis = Axis::new(axis_id);
+    let result = match config.method {
+        HomingMethod::HardStop => find_hard_stop(can, &axis, config, check),
+        HomingMethod::Index => enter_state(can, axis_id, AxisState::EncoderIndexSearch, config.timeout)
+            .map(|()| config.index_offset + config.offset),
+    };
+    let home = match result {
+        Ok(home) => home,
+        Err(err) => {
+            // Never leave the axis pushing against the stop or still searching. The error
+            // that stopped homing is more useful than one from idling the axis
+            can.request(axis.set_state(AxisState::Idle)).ok();
+            return Err(err);
+        }
+    };
+
+    let position: EncoderEstimates = read(can, axis.encoder.get_estimates())?;
+    let count = ((position.position - home) * config.encoder_cpr as f32).round() as i32;
+    can.request(axis.encoder.set_linear_count(count))?;
+    Ok(home)
+}
+
+/// Drives into the hard stop, backs off to the home and stops in `Idle`
+fn find_hard_stop<C>(can: &ReadWriteCANThread, axis: &Axis, config: &HomingConfig, check: C) -> Result<f32, Error>
+where
+    C: Fn(CANRequest) -> Result<CANRequest, ErrorResponse>,
+{
+    let deadline = Instant::now() + config.timeout;
+    let search_velocity = check(axis.motor.set_input_vel(config.direction.sign() * config.speed))?;
+    can.request(axis.motor.set_control_mode(ControlMode::VelocityControl, InputMode::Passthrough))?;
+    enter_state(can, axis.id(), AxisState::ClosedLoop, config.timeout)?;
+    can.request(search_velocity)?;
+
+    // The current also spikes while accelerating, so it only counts once the axis is moving.
+    // An axis that starts against the stop never moves, but stalls with a high current
+    let mut moving = false;
+    let mut stalled_samples = 0;
+    let stop = loop {
+        if Instant::now() >= deadline {
+            return Err(Error::HomingTimedOut(axis.id()));
+        }
+        let current: IQ = read(can, axis.motor.get_iq_setpoint())?;
+        let estimates: EncoderEstimates = read(can, axis.encoder.get_estimates())?;
+
+        let high_current = current.measured.abs() >= config.current_threshold;
+        moving |= estimates.velocity.abs() >= config.speed / 2.0;
+        stalled_samples = match high_current && estimates.velocity.abs() <= SETTLED_VELOCITY {
+            true => stalled_samples + 1,
+            false => 0,
+        };
+        if (moving && high_current) || stalled_samples >= STALL_SAMPLES {
+            break estimates.position;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let home = stop + config.offset;
+    can.request(axis.motor.set_input_pos(home))?;
+    can.request(axis.motor.set_control_mode(ControlMode::PositionControl, InputMode::Passthrough))?;
+    loop {
+        if Instant::now() >= deadline {
+            return Err(Error::HomingTimedOut(axis.id()));
+        }
+        let estimates: EncoderEstimates = read(can, axis.encoder.get_estimates())?;
+        if (estimates.position - home).abs() <= HOME_TOLERANCE && estimates.velocity.abs() <= SETTLED_VELOCITY {
+            break;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    enter_state(can, axis.id(), AxisState::Idle, config.timeout)?;
+    Ok(home)
+}
+
+fn enter_state(can: &ReadWriteCANThread, axis: AxisID, state: AxisState, timeout: Duration) -> Result<(), Error> {
+    let mut outcomes = transition::transition(can, &[axis], state.clone(), timeout);
+    match outcomes.remove(&axis).unwrap() {
+        TransitionOutcome::Reached => Ok(()),
+        outcome => Err(Error::StateNotReached { axis, state, outcome }),
+    }
+}
+
+fn read<T: TryFrom<CANResponse, Error = Error>>(can: &ReadWriteCANThread, request: CANRequest) -> Result<T, Error> {
+    let (_, response) = can.request(request)?.body();
+    T::try_from(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, time::Duration};
+
+    use crate::{
+        canproxy::CANProxy,
+        limits::{Limit, SoftLimits},
+        odrivegroup::ODriveGroup,
+        response::ODriveError,
+        simulator::{ODriveSimulator, SimulationParams},
+        state::AxisState,
+        tests::wait_for_msgs,
+        Error,
+    };
+
+    use super::{HomingConfig, HomingDirection, HomingMethod};
+
+    #[test]
+    fn test_hard_stop_homing() {
+        let params = SimulationParams {
+            precalibrated: true,
+            hard_stops: Some((-0.4, 0.3)),
+            ..Default::default()
+        };
+        let simulator = ODriveSimulator::with_params(&[0, 1], params);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+            let results = odrives.home(&[
+                (0, HomingConfig { offset: 0.1, ..Default::default() }),
+                (1, HomingConfig { direction: HomingDirection::Positive, offset: -0.05, ..Default::default() }),
+            ]);
+            send.send(results).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let results = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        let home_0 = *results[&0].as_ref().unwrap();
+        let home_1 = *results[&1].as_ref().unwrap();
+        assert!((home_0 - -0.3).abs() < 0.02, "{}", home_0);
+        assert!((home_1 - 0.25).abs() < 0.02, "{}", home_1);
+
+        // The encoders are zeroed at the homes. The simulated rotor has very little friction,
+        // so it coasts a little further once the axis is idle
+        for id in [0, 1] {
+            let node = simulator.node(id).unwrap();
+            assert_eq!(node.state, AxisState::Idle);
+            assert!(node.position.abs() < 0.03, "{}", node.position);
+        }
+    }
+
+    #[test]
+    fn test_hard_stop_homing_from_the_stop() {
+        let params = SimulationParams {
+            precalibrated: true,
+            hard_stops: Some((-0.4, 0.3)),
+            ..Default::default()
+        };
+        let simulator = ODriveSimulator::with_params(&[0], params);
+        // The axis already rests against the stop it is homed towards, so it never moves
+        simulator.with_node(0, |node| node.position = -0.4);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0]);
+            let config = HomingConfig { offset: 0.1, timeout: Duration::from_secs(3), ..Default::default() };
+            send.send(odrives.home_axis(&0, &config)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let home = wait_for_msgs(rcv).unwrap();
+        stop_proxy().unwrap();
+
+        assert!((home - -0.3).abs() < 0.02, "{}", home);
+        assert_eq!(simulator.node(0).unwrap().state, AxisState::Idle);
+    }
+
+    #[test]
+    fn test_homing_respects_soft_limits() {
+        let params = SimulationParams { precalibrated: true, ..Default::default() };
+        let simulator = ODriveSimulator::with_params(&[0], params);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let mut odrives = ODriveGroup::new(can_rw, &[0]);
+            odrives.set_limits(&0, SoftLimits { max_velocity: Some(0.2), ..Default::default() }).unwrap();
+            send.send(odrives.home_axis(&0, &HomingConfig::default())).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let result = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        match result {
+            Err(Error::Request(response)) => assert_eq!(response.err, ODriveError::LimitExceeded(Limit::Velocity)),
+            other => panic!("{:?}", other),
+        }
+        let node = simulator.node(0).unwrap();
+        assert_eq!(node.state, AxisState::Idle);
+        assert_eq!(node.input_vel, 0.0);
+    }
+
+    #[test]
+    fn test_index_homing_and_timeout() {
+        let params = SimulationParams {
+            precalibrated: true,
+            calibration_time: Duration::from_millis(100),
+            index_overshoot: 0.05,
+            heartbeat_interval: Some(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let simulator = ODriveSimulator::with_params(&[0, 1, 2], params);
+        simulator.with_node(0, |node| node.index_offset = 0.1);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        let simulator_copy = simulator.clone();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1, 2]);
+            let index = HomingConfig { method: HomingMethod::Index, index_offset: 0.1, offset: 0.25, ..Default::default() };
+            // There is no hard stop for axis 1 to find
+            let hard_stop = HomingConfig { timeout: Duration::from_millis(300), ..Default::default() };
+            // The index search of axis 2 takes longer than it is given
+            let short_index = HomingConfig { timeout: Duration::from_millis(40), ..index.clone() };
+
+            let short_index = odrives.home_axis(&2, &short_index);
+            let state_after_timeout = simulator_copy.node(2).unwrap().state;
+            let results = (odrives.home_axis(&0, &index), odrives.home_axis(&1, &hard_stop), short_index, state_after_timeout);
+            send.send(results).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let (index, hard_stop, short_index, state_after_timeout) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        // The home is measured from the index, not from where the rotor stopped past it
+        assert!((index.unwrap() - 0.35).abs() < 1e-3);
+        assert!((simulator.node(0).unwrap().position - -0.2).abs() < 1e-3);
+        assert!(matches!(hard_stop, Err(Error::HomingTimedOut(1))));
+        assert_eq!(simulator.node(1).unwrap().state, AxisState::Idle);
+        assert!(matches!(short_index, Err(Error::StateNotReached { axis: 2, .. })), "{:?}", short_index);
+        assert_eq!(state_after_timeout, AxisState::Idle);
+    }
+}
@@ -1,5 +1,5 @@
 pub mod canproxy;
-pub(crate) mod cansocket;
+pub mod cansocket;
 pub mod state;
 pub(crate) mod macros;
 pub mod canframe;
@@ -10,11 +10,28 @@ pub mod response;
 pub mod utils;
 pub mod casts;
 pub mod error;
+pub use error::Error;
+pub mod simulator;
+pub mod faults;
+pub mod candump;
+pub mod discovery;
+pub mod provisioning;
+pub mod transition;
+pub mod calibration;
+pub mod homing;
+pub mod watchdog;
+pub mod supervisor;
+pub mod limits;
+pub mod streaming;
+pub mod trajectory;
+pub mod motion;
+#[cfg(feature = "async")]
+pub mod asyncthread;
 
 #[cfg(test)]
 pub(crate) mod tests {
     use crate::{
-        canframe::{ThreadCANFrame},
+        canframe::{ThreadMessage},
         threads::ReadWriteCANThread, response::ODriveResponse,
     };
     use std::sync::{
@@ -26,14 +43,14 @@ pub(crate) mod tests {
     #[allow(dead_code)]
     pub(crate) struct ThreadStub {
         pub thread_id: &'static str,
-        pub proxy_receiver: Receiver<ThreadCANFrame>,
+        pub proxy_receiver: Receiver<ThreadMessage>,
         pub proxy_sender: Sender<ODriveResponse>,
         pub rw_communicator: ReadWriteCANThread,
     }
 
     impl ThreadStub {
         pub fn new(thread_name: &'static str, threads_alive: Arc<AtomicBool>) -> Self {
-            let (thread_requester, proxy_receiver) = channel::<ThreadCANFrame>();
+            let (thread_requester, proxy_receiver) = channel::<ThreadMessage>();
             let (proxy_sender, thread_receiver) = channel::<ODriveResponse>();
 
             Self {
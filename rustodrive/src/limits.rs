@@ -0,0 +1,199 @@
+use crate::{
+    canframe::CANRequest,
+    state::{ODriveCommand::Write, WriteComm},
+    utils::ResponseManip,
+};
+
+/// The limit that a command exceeded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Position,
+    Velocity,
+    Torque,
+}
+
+/// What happens to a command that exceeds a soft limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LimitMode {
+    /// The command is not sent, and an error is returned for the axis instead
+    #[default]
+    Reject,
+    /// The command is sent with the offending value clamped to the limit
+    Clamp,
+}
+
+/// Per-axis limits that `SetInputPosition`, `SetInputVelocity` and `SetInputTorque`
+/// commands are checked against before they are sent. A limit that is `None` is not checked.
+///
+/// Values that are not a number always exceed the limit, even with [`LimitMode::Clamp`]
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct SoftLimits {
+    /// The lowest and highest allowed position in turns
+    pub position: Option<(f32, f32)>,
+    /// The highest allowed velocity in either direction in turns/s, including feedforward
+    pub max_velocity: Option<f32>,
+    /// The highest allowed torque in either direction in Nm, including feedforward
+    pub max_torque: Option<f32>,
+    pub mode: LimitMode,
+}
+
+impl SoftLimits {
+    /// Checks that the limits can be applied: every bound must be finite, the lowest position
+    /// must not be above the highest, and the highest velocity and torque must not be negative.
+    /// This returns the first limit that is invalid.
+    pub fn validate(&self) -> Result<(), Limit> {
+        let valid = |range: Option<(f32, f32)>| match range {
+            Some((min, max)) => min.is_finite() && max.is_finite() && min <= max,
+            None => true,
+        };
+
+        if !valid(self.position) {
+            Err(Limit::Position)
+        } else if !valid(symmetric(self.max_velocity)) {
+            Err(Limit::Velocity)
+        } else if !valid(symmetric(self.max_torque)) {
+            Err(Limit::Torque)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the request as it should be sent, or the limit it exceeds if it has to be rejected.
+    /// Requests for any other command are returned unchanged. Limits that do not pass
+    /// `validate()` reject every value instead of clamping it.
+    pub fn apply(&self, mut request: CANRequest) -> Result<CANRequest, Limit> {
+        let (low, high) = ResponseManip::split_32(request.data);
+        let (low, high) = match request.cmd {
+            Write(WriteComm::SetInputPosition) => {
+                let (vel_ff, torque_ff) = ResponseManip::split_16(high);
+                let position = self.check(f32::from_le_bytes(low), self.position, Limit::Position)?;
+                let vel_ff = self.check_feedforward(vel_ff, self.max_velocity, Limit::Velocity)?;
+                let torque_ff = self.check_feedforward(torque_ff, self.max_torque, Limit::Torque)?;
+                (position.to_le_bytes(), ResponseManip::combine_16(vel_ff, torque_ff))
+            }
+            Write(WriteComm::SetInputVelocity) => {
+                let velocity = self.check(f32::from_le_bytes(low), symmetric(self.max_velocity), Limit::Velocity)?;
+                let torque_ff = self.check(f32::from_le_bytes(high), symmetric(self.max_torque), Limit::Torque)?;
+                (velocity.to_le_bytes(), torque_ff.to_le_bytes())
+            }
+            Write(WriteComm::SetInputTorque) => {
+                let torque = self.check(f32::from_le_bytes(low), symmetric(self.max_torque), Limit::Torque)?;
+                (torque.to_le_bytes(), high)
+            }
+            _ => return Ok(request),
+        };
+
+        request.data = ResponseManip::combine_32(low, high);
+        Ok(request)
+    }
+
+    fn check(&self, value: f32, range: Option<(f32, f32)>, limit: Limit) -> Result<f32, Limit> {
+        let (min, max) = match range {
+            Some(range) => range,
+            None => return Ok(value),
+        };
+
+        match self.mode {
+            _ if value.is_nan() => Err(limit),
+            _ if (min..=max).contains(&value) => Ok(value),
+            LimitMode::Clamp if min <= max => Ok(value.clamp(min, max)),
+            _ => Err(limit),
+        }
+    }
+
+    /// Feedforward terms of `SetInputPosition` are sent as thousandths in an `i16`
+    fn check_feedforward(&self, bytes: [u8; 2], max: Option<f32>, limit: Limit) -> Result<[u8; 2], Limit> {
+        let value = i16::from_le_bytes(bytes) as f32 * 0.001;
+        let checked = self.check(value, symmetric(max), limit)?;
+        match checked == value {
+            true => Ok(bytes),
+            false => Ok(((checked * 1000.0) as i16).to_le_bytes()),
+        }
+    }
+}
+
+fn symmetric(max: Option<f32>) -> Option<(f32, f32)> {
+    max.map(|max| (-max, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        axis::Axis,
+        state::{ODriveCommand::Write, WriteComm},
+        utils::ResponseManip,
+    };
+
+    use super::{Limit, LimitMode, SoftLimits};
+
+    fn limits(mode: LimitMode) -> SoftLimits {
+        SoftLimits {
+            position: Some((-0.5, 1.0)),
+            max_velocity: Some(2.0),
+            max_torque: Some(0.3),
+            mode,
+        }
+    }
+
+    #[test]
+    fn test_reject() {
+        let axis = Axis::new(3);
+        let limits = limits(LimitMode::Reject);
+
+        let allowed = axis.motor.set_input_pos(0.75);
+        assert_eq!(limits.apply(allowed), Ok(allowed));
+        assert_eq!(limits.apply(axis.motor.set_input_pos(1.5)), Err(Limit::Position));
+        assert_eq!(limits.apply(axis.motor.set_input_pos(f32::NAN)), Err(Limit::Position));
+        assert_eq!(limits.apply(axis.motor.set_input_vel(-2.5)), Err(Limit::Velocity));
+        assert_eq!(limits.apply(axis.motor.set_input_torque(0.5)), Err(Limit::Torque));
+
+        // Commands without a value to limit are passed through
+        let state = axis.set_state(crate::state::AxisState::ClosedLoop);
+        assert_eq!(SoftLimits::default().apply(state), Ok(state));
+        assert_eq!(limits.apply(state), Ok(state));
+    }
+
+    #[test]
+    fn test_clamp() {
+        let axis = Axis::new(3);
+        let limits = limits(LimitMode::Clamp);
+
+        let clamped = limits.apply(axis.motor.set_input_pos(-3.0)).unwrap();
+        assert_eq!(clamped, axis.motor.set_input_pos(-0.5));
+
+        let clamped = limits.apply(axis.motor.set_input_vel(5.0)).unwrap();
+        assert_eq!(clamped, axis.motor.set_input_vel(2.0));
+
+        // The velocity feedforward of a position command is clamped on its own
+        let mut with_ff = axis.motor.set_input_pos(0.5);
+        with_ff.data = ResponseManip::combine_32(0.5f32.to_le_bytes(), ResponseManip::combine_16(3000i16.to_le_bytes(), 100i16.to_le_bytes()));
+        let clamped = limits.apply(with_ff).unwrap();
+        assert_eq!(clamped.cmd, Write(WriteComm::SetInputPosition));
+        let (_, feedforward) = ResponseManip::split_32(clamped.data);
+        let (vel_ff, torque_ff) = ResponseManip::split_16(feedforward);
+        assert_eq!(i16::from_le_bytes(vel_ff), 2000);
+        assert_eq!(i16::from_le_bytes(torque_ff), 100);
+
+        assert_eq!(limits.apply(axis.motor.set_input_torque(f32::NAN)), Err(Limit::Torque));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(limits(LimitMode::Clamp).validate(), Ok(()));
+        assert_eq!(SoftLimits::default().validate(), Ok(()));
+
+        let inverted = SoftLimits { position: Some((1.0, -1.0)), mode: LimitMode::Clamp, ..Default::default() };
+        let negative = SoftLimits { max_velocity: Some(-2.0), mode: LimitMode::Clamp, ..Default::default() };
+        let not_a_number = SoftLimits { max_torque: Some(f32::NAN), ..Default::default() };
+        let unbounded = SoftLimits { position: Some((f32::NEG_INFINITY, 0.0)), ..Default::default() };
+        assert_eq!(inverted.validate(), Err(Limit::Position));
+        assert_eq!(negative.validate(), Err(Limit::Velocity));
+        assert_eq!(not_a_number.validate(), Err(Limit::Torque));
+        assert_eq!(unbounded.validate(), Err(Limit::Position));
+
+        // Applying invalid limits directly rejects the command instead of panicking
+        let axis = Axis::new(3);
+        assert_eq!(inverted.apply(axis.motor.set_input_pos(0.0)), Err(Limit::Position));
+        assert_eq!(negative.apply(axis.motor.set_input_vel(1.0)), Err(Limit::Velocity));
+    }
+}
@@ -35,10 +35,10 @@ macro_rules! cfg_match {
     ( $cfg:meta => $expansion:tt $(, $($rest:tt)+)? ) => (
         #[cfg($cfg)]
         cfg_match! { other => $expansion }
-        $($(
+        $(
             #[cfg(not($cfg))]
-            cfg_match! { other => $rest }
-        )?)?
+            cfg_match! { $($rest)+ }
+        )?
     );
 } 
 
@@ -46,8 +46,6 @@ macro_rules! cfg_match {
 
 #[cfg(test)]
 mod tests {
-    use crate::{back_to_enum};
-
     back_to_enum! { u32, 
         pub enum TestEnum {
             A = 0x1, 
@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+
+use crate::{axis::AxisID, trajectory::Trapezoidal, Error};
+
+/// A move of several axes to their targets that starts and finishes on every axis at the same time
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoordinatedMove {
+    /// The position in turns each axis moves to
+    pub targets: Vec<(AxisID, f32)>,
+    /// Velocity in turns/s of the axis that moves the furthest. The other axes move
+    /// proportionally slower
+    pub max_velocity: f32,
+    /// Acceleration and deceleration in turns/s² of the axis that moves the furthest.
+    /// The other axes accelerate proportionally slower
+    pub max_acceleration: f32,
+}
+
+/// How a single axis moves as part of a [`MovePlan`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisMove {
+    pub start: f32,
+    pub target: f32,
+    /// The peak velocity of the axis in turns/s
+    pub velocity: f32,
+    /// The acceleration and deceleration of the axis in turns/s²
+    pub acceleration: f32,
+}
+
+/// The velocity profile of every axis of a [`CoordinatedMove`]. All axes spend the same time
+/// accelerating, cruising and decelerating, so they arrive together
+#[derive(Clone, Debug, PartialEq)]
+pub struct MovePlan {
+    /// How long the move takes in seconds
+    pub duration: f32,
+    /// How long every axis accelerates, and decelerates, in seconds
+    pub accel_time: f32,
+    pub axes: BTreeMap<AxisID, AxisMove>,
+}
+
+impl MovePlan {
+    /// Plans the move from the current positions of the axes. The axis that moves the furthest
+    /// follows the fastest profile the limits allow, and every other axis follows the same
+    /// profile scaled down to its distance.
+    ///
+    /// This returns [`Error::InvalidMove`] unless both limits are finite and positive.
+    pub fn new(starts: &BTreeMap<AxisID, f32>, targets: &[(AxisID, f32)], max_velocity: f32, max_acceleration: f32) -> Result<Self, Error> {
+        let valid = |limit: f32| limit.is_finite() && limit > 0.0;
+        if !valid(max_velocity) || !valid(max_acceleration) {
+            return Err(Error::InvalidMove);
+        }
+
+        let distance = |(axis_id, target): &(AxisID, f32)| (target - starts[axis_id]).abs();
+        let longest = targets.iter().map(distance).fold(0.0, f32::max);
+
+        // The same profile as a trapezoidal trajectory, or a triangle if the distance is too short
+        let (accel_time, duration) = match longest * max_acceleration <= max_velocity * max_velocity {
+            true => {
+                let accel_time = (longest / max_acceleration).sqrt();
+                (accel_time, 2.0 * accel_time)
+            }
+            false => (max_velocity / max_acceleration, longest / max_velocity + max_velocity / max_acceleration),
+        };
+        let peak_velocity = max_acceleration * accel_time;
+
+        let axes = targets
+            .iter()
+            .map(|axis_target| {
+                let (axis_id, target) = *axis_target;
+                let scale = match longest > 0.0 {
+                    true => distance(axis_target) / longest,
+                    false => 0.0,
+                };
+                let axis_move = AxisMove {
+                    start: starts[&axis_id],
+                    target,
+                    velocity: scale * peak_velocity,
+                    acceleration: scale * max_acceleration,
+                };
+                (axis_id, axis_move)
+            })
+            .collect();
+
+        Ok(Self { duration, accel_time, axes })
+    }
+
+    /// Returns the trajectory of every axis, to stream the move from the host instead
+    /// of the trajectory planners on the ODrives
+    pub fn trajectories(&self) -> BTreeMap<AxisID, Trapezoidal> {
+        self.axes
+            .iter()
+            .map(|(axis_id, axis_move)| {
+                let accel_fraction = match self.duration > 0.0 {
+                    true => self.accel_time / self.duration,
+                    false => 0.5,
+                };
+                (*axis_id, Trapezoidal::with_duration(axis_move.start, axis_move.target, self.duration, accel_fraction))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, io, sync::mpsc::channel, thread, time::{Duration, Instant}};
+
+    use socketcan::CANFrame;
+
+    use crate::{
+        canframe::CANRequest,
+        canproxy::CANProxy,
+        cansocket::CanTransport,
+        limits::{Limit, LimitMode, SoftLimits},
+        odrivegroup::ODriveGroup,
+        response::ODriveError,
+        simulator::{ODriveSimulator, SimulationParams},
+        state::{AxisState, InputMode, ODriveCommand, WriteComm},
+        streaming::StreamConfig,
+        tests::wait_for_msgs,
+        trajectory::Trajectory,
+        Error,
+    };
+
+    use super::{CoordinatedMove, MovePlan};
+
+    #[test]
+    fn test_plan_scales_axes() {
+        let starts = BTreeMap::from([(0, 0.0), (1, 1.0), (2, -0.5)]);
+        // Axis 0 goes the furthest and cruises at the velocity limit
+        let plan = MovePlan::new(&starts, &[(0, 4.0), (1, 0.0), (2, -0.5)], 2.0, 4.0).unwrap();
+
+        assert!((plan.duration - 2.5).abs() < 1e-4);
+        assert!((plan.accel_time - 0.5).abs() < 1e-4);
+        assert_eq!((plan.axes[&0].velocity, plan.axes[&0].acceleration), (2.0, 4.0));
+        assert_eq!((plan.axes[&1].velocity, plan.axes[&1].acceleration), (0.5, 1.0));
+        assert_eq!((plan.axes[&2].velocity, plan.axes[&2].acceleration), (0.0, 0.0));
+
+        // Sampled halfway, every axis covered half its distance
+        for (axis_id, trajectory) in plan.trajectories() {
+            assert!((trajectory.duration() - plan.duration).abs() < 1e-4);
+            let axis_move = &plan.axes[&axis_id];
+            let halfway = trajectory.sample(plan.duration / 2.0).position;
+            assert!((halfway - (axis_move.start + axis_move.target) / 2.0).abs() < 1e-4);
+        }
+    }
+
+    fn closed_loop_simulator() -> ODriveSimulator {
+        let params = SimulationParams { precalibrated: true, ..Default::default() };
+        let simulator = ODriveSimulator::with_params(&[0, 1], params);
+        for id in [0, 1] {
+            simulator.with_node(id, |node| node.state = AxisState::ClosedLoop);
+        }
+        simulator.with_node(1, |node| {
+            node.position = 0.5;
+            node.input_pos = 0.5;
+        });
+        simulator
+    }
+
+    #[test]
+    fn test_onboard_move_arrives_together() {
+        let simulator = closed_loop_simulator();
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+            let coordinated = CoordinatedMove { targets: vec![(0, 1.0), (1, 0.25)], max_velocity: 2.0, max_acceleration: 8.0 };
+            send.send(odrives.coordinated_move(&coordinated)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let plan = wait_for_msgs(rcv).unwrap();
+
+        // Throughout the move both axes have covered the same share of their distance
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let (node_0, node_1) = (simulator.node(0).unwrap(), simulator.node(1).unwrap());
+            let progress_0 = node_0.position / 1.0;
+            let progress_1 = (0.5 - node_1.position) / 0.25;
+            assert!((progress_0 - progress_1).abs() < 0.15, "{} {}", progress_0, progress_1);
+
+            if (node_0.position - 1.0).abs() < 0.01 && (node_1.position - 0.25).abs() < 0.01 {
+                break;
+            }
+            assert!(Instant::now() < deadline, "the axes did not arrive");
+            thread::sleep(Duration::from_millis(5));
+        }
+        stop_proxy().unwrap();
+
+        assert_eq!(plan.axes[&1].velocity, plan.axes[&0].velocity / 4.0);
+        assert_eq!(simulator.node(1).unwrap().traj_vel_limit, plan.axes[&1].velocity);
+    }
+
+    /// Plans the same move as `test_onboard_move_arrives_together`, with a velocity limit on axis 1
+    fn move_with_limit(mode: LimitMode) -> (ODriveSimulator, Result<MovePlan, Error>) {
+        let simulator = closed_loop_simulator();
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let mut odrives = ODriveGroup::new(can_rw, &[0, 1]);
+            odrives.set_limits(&1, SoftLimits { max_velocity: Some(0.25), mode, ..Default::default() }).unwrap();
+            let coordinated = CoordinatedMove { targets: vec![(0, 1.0), (1, 0.25)], max_velocity: 2.0, max_acceleration: 8.0 };
+            send.send(odrives.coordinated_move(&coordinated)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let plan = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+        (simulator, plan)
+    }
+
+    #[test]
+    fn test_move_velocity_rejected() {
+        // Axis 1 would need 0.5 turns/s
+        let (simulator, plan) = move_with_limit(LimitMode::Reject);
+
+        match plan {
+            Err(Error::Request(response)) => {
+                assert_eq!(response.request.axis, 1);
+                assert_eq!(response.err, ODriveError::LimitExceeded(Limit::Velocity));
+            }
+            other => panic!("{:?}", other),
+        }
+        // Nothing was sent to either axis
+        for id in [0, 1] {
+            let node = simulator.node(id).unwrap();
+            assert_eq!(node.input_mode, InputMode::Passthrough);
+            assert_eq!(node.traj_vel_limit, 2.0);
+        }
+    }
+
+    #[test]
+    fn test_move_velocity_clamped() {
+        let (simulator, plan) = move_with_limit(LimitMode::Clamp);
+        let plan = plan.unwrap();
+
+        // The whole move is stretched to twice as long, so the axes still arrive together
+        let unlimited = MovePlan::new(&BTreeMap::from([(0, 0.0), (1, 0.5)]), &[(0, 1.0), (1, 0.25)], 2.0, 8.0).unwrap();
+        assert!((plan.duration - unlimited.duration * 2.0).abs() < 1e-4, "{:?}", plan);
+        assert!((plan.axes[&1].velocity - 0.25).abs() < 1e-4);
+        assert!((plan.axes[&0].velocity - 1.0).abs() < 1e-4);
+        assert_eq!(simulator.node(1).unwrap().traj_vel_limit, plan.axes[&1].velocity);
+    }
+
+    #[test]
+    fn test_streamed_move() {
+        let simulator = closed_loop_simulator();
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+            let coordinated = CoordinatedMove { targets: vec![(0, -0.3), (1, 0.8)], max_velocity: 2.0, max_acceleration: 8.0 };
+            send.send(odrives.stream_coordinated_move(&coordinated, &StreamConfig::with_frequency(200.0).unwrap())).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let stats = wait_for_msgs(rcv).unwrap();
+        stop_proxy().unwrap();
+
+        assert_eq!(stats.send_errors, 0);
+        assert!((simulator.node(0).unwrap().input_pos - -0.3).abs() < 1e-4);
+        assert!((simulator.node(1).unwrap().input_pos - 0.8).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_invalid_move_limits() {
+        let starts = BTreeMap::from([(0, 0.0)]);
+        for (max_velocity, max_acceleration) in [(0.0, 4.0), (-2.0, 4.0), (f32::NAN, 4.0), (2.0, 0.0), (2.0, f32::INFINITY)] {
+            let plan = MovePlan::new(&starts, &[(0, 1.0)], max_velocity, max_acceleration);
+            assert!(matches!(plan, Err(Error::InvalidMove)), "{} {}", max_velocity, max_acceleration);
+        }
+    }
+
+    /// Passes every frame through to the simulator, except that setting the controller
+    /// mode of `axis` always fails to send
+    struct ModeRejectingTransport {
+        simulator: ODriveSimulator,
+        axis: u32,
+    }
+
+    impl CanTransport for ModeRejectingTransport {
+        fn open(ifname: &str) -> io::Result<Self> {
+            Ok(Self { simulator: ODriveSimulator::open(ifname)?, axis: 0 })
+        }
+
+        fn write_frame(&self, frame: &CANFrame) -> io::Result<()> {
+            let request = CANRequest::from_can(frame).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            match request.axis == self.axis && request.cmd == ODriveCommand::Write(WriteComm::SetControllerMode) {
+                true => Err(io::Error::other("bus off")),
+                false => self.simulator.write_frame(frame),
+            }
+        }
+
+        fn read_frame(&self) -> io::Result<CANFrame> {
+            self.simulator.read_frame()
+        }
+
+        fn set_read_timeout(&self, timeout: Duration) -> io::Result<()> {
+            self.simulator.set_read_timeout(timeout)
+        }
+
+        fn set_write_timeout(&self, timeout: Duration) -> io::Result<()> {
+            self.simulator.set_write_timeout(timeout)
+        }
+    }
+
+    #[test]
+    fn test_move_setup_failed() {
+        let simulator = closed_loop_simulator();
+        let mut can_proxy = CANProxy::with_transport(ModeRejectingTransport { simulator: simulator.clone(), axis: 1 });
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+            let coordinated = CoordinatedMove { targets: vec![(0, 1.0), (1, 0.25)], max_velocity: 2.0, max_acceleration: 8.0 };
+            send.send(odrives.coordinated_move(&coordinated)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let plan = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        // Axis 1 took the new trajectory limits before its mode failed to send
+        match plan {
+            Err(Error::MoveSetupFailed { reconfigured, error }) => {
+                assert_eq!(reconfigured, vec![0, 1]);
+                assert_eq!(error.request.axis, 1);
+                assert_eq!(error.err, ODriveError::FailedToSend);
+            }
+            other => panic!("{:?}", other),
+        }
+        // No targets were sent
+        assert_eq!(simulator.node(0).unwrap().input_mode, InputMode::TrapTraj);
+        assert_eq!(simulator.node(0).unwrap().input_pos, 0.0);
+        assert_eq!(simulator.node(1).unwrap().input_pos, 0.5);
+    }
+}
@@ -1,11 +1,24 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
 
 use crate::{
     axis::{Axis, AxisID},
+    calibration::{self, CalibrationEvent, CalibrationOptions, CalibrationReport},
     canframe::{ticket, CANRequest, CANResponse, ODriveCANFrame},
+    casts::EncoderEstimates,
+    homing::{self, HomingConfig},
+    limits::{Limit, LimitMode, SoftLimits},
+    motion::{CoordinatedMove, MovePlan},
+    streaming::{self, StreamConfig, StreamStats, StreamTick},
+    trajectory::TrajectoryPlayer,
     response::{ErrorResponse, ODriveError, ODriveResponse, ResponseType, Success},
-    state::{ODriveCommand::Write, WriteComm::*},
+    state::{AxisState, ControlMode, InputMode, ODriveCommand::Write, WriteComm::*},
     threads::ReadWriteCANThread,
+    transition::{self, TransitionOutcome},
+    Error,
 };
 
 /// `ODriveGroup` is an interface for communicating with the odrive,
@@ -27,6 +40,17 @@ use crate::{
 /// [`ODriveGroup::axis()`] and [`ODriveGroup::all_axes()`] as seen in the example.
 /// You may pass CANFrames directly or use the preconfigured ones in the [`Axis`] struct
 ///
+/// ### Changing the axes
+/// The group owns the IDs of its axes, so they can come from a config file or the command
+/// line. Axes can be added and removed at runtime with [`ODriveGroup::add_axis()`] and
+/// [`ODriveGroup::remove_axis()`]. Named sub-groups, such as the axes of one leg, are
+/// defined with [`ODriveGroup::define_subgroup()`] and commanded with [`ODriveGroup::subgroup()`].
+///
+/// ### Soft limits
+/// Each axis can be given [`SoftLimits`] with [`ODriveGroup::set_limits()`]. Every position,
+/// velocity and torque command sent through the group is checked against them first, so a
+/// command that would drive a joint into its hard stop is rejected or clamped before it is sent.
+///
 /// # Example
 /// ```
 /// // rust code
@@ -39,8 +63,8 @@ use crate::{
 /// };
 ///
 /// fn main() {
-///     let mut can_proxy = CANProxy::new("can0");
-///     can_proxy.register_rw("thread 1", odrive_main);
+///     let mut can_proxy = CANProxy::new("can0").unwrap();
+///     can_proxy.register_rw("thread 1", odrive_main).unwrap();
 ///
 ///     let stop_all = can_proxy.begin();
 ///     std::thread::sleep(Duration::from_secs(1));
@@ -52,26 +76,197 @@ use crate::{
 /// // Entrypoint for odrive control
 /// fn odrive_main(can_rw: ReadWriteCANThread) {
 ///     let odrives = ODriveGroup::new(can_rw, &[1, 2, 3, 4]);
-
 ///     println!("Starting calibration sequence");
-///     odrives.all_axes(|ax| ax.set_state(FullCalibrationSequence));
+///     odrives.all_axes::<(), _>(|ax| ax.set_state(FullCalibrationSequence));
 ///     println!("Motors fully calibrated!")
 /// }
 /// ```
-
-pub struct ODriveGroup<'a> {
+pub struct ODriveGroup {
     can: ReadWriteCANThread,
-    axes: BTreeMap<&'a AxisID, Axis<'a>>,
+    members: GroupMembers,
 }
 
-impl<'a> ODriveGroup<'a> {
-    pub fn new(can: ReadWriteCANThread, axis_ids: &'static [AxisID]) -> Self {
+/// The axes of a group together with their named sub-groups and soft limits. This is
+/// shared with the async group so that both manage and check their axes the same way
+pub(crate) struct GroupMembers {
+    axes: BTreeMap<AxisID, Axis>,
+    subgroups: BTreeMap<String, BTreeSet<AxisID>>,
+    limits: BTreeMap<AxisID, SoftLimits>,
+}
+
+impl GroupMembers {
+    pub(crate) fn new(axis_ids: &[AxisID]) -> Self {
+        GroupMembers {
+            axes: axis_ids.iter().map(|id| (*id, Axis::new(*id))).collect(),
+            subgroups: BTreeMap::new(),
+            limits: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn axis_ids(&self) -> Vec<AxisID> {
+        self.axes.keys().copied().collect()
+    }
+
+    pub(crate) fn add_axis(&mut self, axis_id: AxisID) {
+        self.axes.entry(axis_id).or_insert_with(|| Axis::new(axis_id));
+    }
+
+    pub(crate) fn remove_axis(&mut self, axis_id: AxisID) -> Result<(), Error> {
+        self.axes.remove(&axis_id).ok_or(Error::UnknownAxis(axis_id))?;
+        self.limits.remove(&axis_id);
+        for members in self.subgroups.values_mut() {
+            members.remove(&axis_id);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_limits(&mut self, axis_id: &AxisID, limits: SoftLimits) -> Result<(), Error> {
+        self.get_axis(axis_id)?;
+        limits.validate().map_err(|limit| Error::InvalidLimits { axis: *axis_id, limit })?;
+        self.limits.insert(*axis_id, limits);
+        Ok(())
+    }
+
+    pub(crate) fn limits(&self, axis_id: &AxisID) -> Result<SoftLimits, Error> {
+        self.get_axis(axis_id)?;
+        Ok(self.limits.get(axis_id).cloned().unwrap_or_default())
+    }
+
+    pub(crate) fn define_subgroup(&mut self, name: &str, axis_ids: &[AxisID]) -> Result<(), Error> {
+        if let Some(unknown) = axis_ids.iter().find(|id| !self.axes.contains_key(id)) {
+            return Err(Error::UnknownAxis(*unknown));
+        }
+        self.subgroups.insert(name.to_string(), axis_ids.iter().copied().collect());
+        Ok(())
+    }
+
+    pub(crate) fn remove_subgroup(&mut self, name: &str) -> Result<(), Error> {
+        match self.subgroups.remove(name) {
+            Some(_) => Ok(()),
+            None => Err(Error::UnknownGroup(name.to_string())),
+        }
+    }
+
+    pub(crate) fn subgroup_ids(&self, name: &str) -> Result<Vec<AxisID>, Error> {
+        match self.subgroups.get(name) {
+            Some(members) => Ok(members.iter().copied().collect()),
+            None => Err(Error::UnknownGroup(name.to_string())),
+        }
+    }
+
+    pub(crate) fn get_axis(&self, id: &AxisID) -> Result<&Axis, Error> {
+        self.axes.get(id).ok_or(Error::UnknownAxis(*id))
+    }
+
+    /// Builds a request for every axis and checks it against the soft limits of the axis
+    pub(crate) fn requests<F: FnMut(&Axis) -> CANRequest>(&self, f: F) -> Vec<Result<CANRequest, ErrorResponse>> {
+        self.axes.values().map(f).map(|request| self.check_limits(request)).collect()
+    }
+
+    /// Same as `.requests()`, but only for the axes of a named sub-group
+    pub(crate) fn subgroup_requests<F: FnMut(&Axis) -> CANRequest>(
+        &self,
+        name: &str,
+        f: F,
+    ) -> Result<Vec<Result<CANRequest, ErrorResponse>>, Error> {
+        let members = self.subgroups.get(name).ok_or_else(|| Error::UnknownGroup(name.to_string()))?;
+        let axes = members.iter().filter_map(|id| self.axes.get(id));
+        Ok(axes.map(f).map(|request| self.check_limits(request)).collect())
+    }
+
+    /// Applies the soft limits of the axis the request is addressed to
+    pub(crate) fn check_limits(&self, request: CANRequest) -> Result<CANRequest, ErrorResponse> {
+        match self.limits.get(&(request.axis as AxisID)) {
+            None => Ok(request),
+            Some(limits) => limits
+                .apply(request)
+                .map_err(|limit| ErrorResponse { request, err: ODriveError::LimitExceeded(limit) }),
+        }
+    }
+}
+
+impl ODriveGroup {
+    pub fn new(can: ReadWriteCANThread, axis_ids: &[AxisID]) -> Self {
         ODriveGroup {
-            axes: axis_ids.iter().map(|id| (id, Axis::new(id))).collect(),
+            members: GroupMembers::new(axis_ids),
             can,
         }
     }
 
+    /// Returns the IDs of all axes in the group in ascending order
+    pub fn axis_ids(&self) -> Vec<AxisID> {
+        self.members.axis_ids()
+    }
+
+    /// Adds an axis to the group. Adding an axis that is already part of the group does nothing
+    pub fn add_axis(&mut self, axis_id: AxisID) {
+        self.members.add_axis(axis_id)
+    }
+
+    /// Removes an axis from the group and from every sub-group it is a part of
+    pub fn remove_axis(&mut self, axis_id: AxisID) -> Result<(), Error> {
+        self.members.remove_axis(axis_id)
+    }
+
+    /// Sets the soft limits of an axis, replacing any it had before. Requests that exceed them
+    /// are answered with [`ODriveError::LimitExceeded`] without being sent, or are clamped,
+    /// depending on [`SoftLimits::mode`]. Limits that fail [`SoftLimits::validate()`] are
+    /// not set, and [`Error::InvalidLimits`] is returned.
+    ///
+    /// ### Example
+    /// ```
+    /// use rustodrive::odrivegroup::ODriveGroup;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::limits::SoftLimits;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     let mut odrives = ODriveGroup::new(can_rw, &[0, 1]);
+    ///     let knee = SoftLimits { position: Some((-0.25, 0.4)), max_velocity: Some(2.0), ..Default::default() };
+    ///     odrives.set_limits(&1, knee).unwrap();
+    ///     // This is never sent to the ODrive
+    ///     assert!(odrives.axis::<(), _>(&1, |ax| ax.motor.set_input_pos(0.5)).is_err());
+    /// }).unwrap();
+    /// ```
+    pub fn set_limits(&mut self, axis_id: &AxisID, limits: SoftLimits) -> Result<(), Error> {
+        self.members.set_limits(axis_id, limits)
+    }
+
+    /// Returns the soft limits of an axis, which check nothing unless they were set
+    pub fn limits(&self, axis_id: &AxisID) -> Result<SoftLimits, Error> {
+        self.members.limits(axis_id)
+    }
+
+    /// Defines a named sub-group of axes, such as the axes of one leg, replacing any
+    /// sub-group with the same name. Every axis must already be part of the group.
+    ///
+    /// ### Example
+    /// ```
+    /// use rustodrive::odrivegroup::ODriveGroup;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::state::AxisState::ClosedLoop;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     let mut odrives = ODriveGroup::new(can_rw, &[0, 1, 2, 3, 4, 5]);
+    ///     odrives.define_subgroup("front_left_leg", &[0, 1, 2]).unwrap();
+    ///     odrives.subgroup::<(), _>("front_left_leg", |ax| ax.set_state(ClosedLoop)).unwrap();
+    /// }).unwrap();
+    /// ```
+    pub fn define_subgroup(&mut self, name: &str, axis_ids: &[AxisID]) -> Result<(), Error> {
+        self.members.define_subgroup(name, axis_ids)
+    }
+
+    /// Removes a named sub-group. The axes stay part of the group
+    pub fn remove_subgroup(&mut self, name: &str) -> Result<(), Error> {
+        self.members.remove_subgroup(name)
+    }
+
+    /// Returns the IDs of the axes in a named sub-group in ascending order
+    pub fn subgroup_ids(&self, name: &str) -> Result<Vec<AxisID>, Error> {
+        self.members.subgroup_ids(name)
+    }
+
     /// This method sends the request specified by the closure to all the axes simultaneously
     /// and blocks until they all come back. Conversely, `.axis()` sends a request to only 1
     /// axis and blocks until it receives a response.
@@ -91,35 +286,76 @@ impl<'a> ODriveGroup<'a> {
     /// use rustodrive::canproxy::CANProxy;
     /// use rustodrive::state::AxisState::FullCalibrationSequence;
     ///
-    /// let mut can_proxy = CANProxy::new("can0");
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
     /// can_proxy.register_rw("thread 1", |can_rw| {
     ///     let odrives = ODriveGroup::new(can_rw, &[1, 2, 3, 4]);
-    ///     odrives.all_axes(|ax| ax.set_state(FullCalibrationSequence));
-    /// });
+    ///     odrives.all_axes::<(), _>(|ax| ax.set_state(FullCalibrationSequence));
+    /// }).unwrap();
     ///
     /// let stop = can_proxy.begin();
     /// std::thread::sleep(Duration::from_secs(1));
     /// stop();
     /// ```
-    pub fn all_axes<T: TryFrom<CANResponse, Error = ODriveError>, F>(
+    pub fn all_axes<T: TryFrom<CANResponse, Error = Error>, F>(
         &self,
-        mut f: F,
+        f: F,
     ) -> Vec<Result<Success<T>, ErrorResponse>>
     where
         F: FnMut(&Axis) -> CANRequest,
     {
-        let requests = self.axes.values().map(|ax| f(ax)).collect();
-        let responses = self.can.request_many(requests);
+        self.request_checked(self.members.requests(f))
+    }
+
+    /// This method sends the request specified by the closure to every axis of a named
+    /// sub-group simultaneously and blocks until they all come back. Responses are in
+    /// ascending order of axis ID, just like `.all_axes()`.
+    ///
+    /// This returns [`Error::UnknownGroup`] if no sub-group with the name was defined
+    /// with [`ODriveGroup::define_subgroup()`].
+    pub fn subgroup<T: TryFrom<CANResponse, Error = Error>, F>(
+        &self,
+        name: &str,
+        f: F,
+    ) -> Result<Vec<Result<Success<T>, ErrorResponse>>, Error>
+    where
+        F: FnMut(&Axis) -> CANRequest,
+    {
+        Ok(self.request_checked(self.members.subgroup_requests(name, f)?))
+    }
+
+    /// Sends the requests that passed the soft limits together and blocks until they come back
+    fn request_checked<T: TryFrom<CANResponse, Error = Error>>(
+        &self,
+        checked: Vec<Result<CANRequest, ErrorResponse>>,
+    ) -> Vec<Result<Success<T>, ErrorResponse>> {
+        let allowed = checked.iter().filter_map(|res| res.as_ref().ok()).copied().collect();
+        let responses = self.can.request_many(allowed);
+        Self::merge_responses(checked, responses)
+    }
 
+    /// Converts the responses to the requests that were sent. Rejected requests keep their
+    /// place among them
+    pub(crate) fn merge_responses<T: TryFrom<CANResponse, Error = Error>>(
+        checked: Vec<Result<CANRequest, ErrorResponse>>,
+        responses: Vec<ODriveResponse>,
+    ) -> Vec<Result<Success<T>, ErrorResponse>> {
+        let mut responses = responses.into_iter();
         let mut final_responses = vec![];
-        for res in responses {
-            let res = Self::convert_response(res);
+        for res in checked {
+            let res = match res {
+                Ok(_) => Self::convert_response(responses.next().unwrap()),
+                Err(rejected) => Err(rejected),
+            };
             final_responses.push(res);
         }
 
         final_responses
     }
 
+    fn check_limits(&self, request: CANRequest) -> Result<CANRequest, ErrorResponse> {
+        self.members.check_limits(request)
+    }
+
     /// This method sends the request specified by the closure to the axis specified.
     /// Conversely, `.all_axes()` sends the request to all axes simulatenously and
     /// blocks until it receives a response.
@@ -139,34 +375,84 @@ impl<'a> ODriveGroup<'a> {
     /// use rustodrive::canproxy::CANProxy;
     /// use rustodrive::state::AxisState::FullCalibrationSequence;
     ///
-    /// let mut can_proxy = CANProxy::new("can0");
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
     /// can_proxy.register_rw("thread 1", |can_rw| {
     ///     let odrives = ODriveGroup::new(can_rw, &[1, 2, 3, 4]);
-    ///     odrives.axis(&1, |ax| ax.set_state(FullCalibrationSequence));
-    /// });
+    ///     odrives.axis::<(), _>(&1, |ax| ax.set_state(FullCalibrationSequence));
+    /// }).unwrap();
     ///
     /// let stop = can_proxy.begin();
     /// std::thread::sleep(Duration::from_secs(1));
     /// stop();
     /// ```
-    pub fn axis<T: TryFrom<ODriveCANFrame, Error = ODriveError>, F: FnOnce(&Axis) -> CANRequest>(
+    pub fn axis<T: TryFrom<ODriveCANFrame, Error = Error>, F: FnOnce(&Axis) -> CANRequest>(
         &self,
         axis_id: &AxisID,
         f: F,
-    ) -> Result<Success<T>, ErrorResponse>
+    ) -> Result<Success<T>, Error>
     {
-        Self::convert_response(self.can.request(f(self.get_axis(axis_id))))
+        let request = self.check_limits(f(self.get_axis(axis_id)?))?;
+        Ok(Self::convert_response(self.can.request(request))?)
+    }
+
+    /// Sends the request specified by the closure to the axis without waiting for it to be
+    /// written to the CAN bus. See [`ReadWriteCANThread::send()`].
+    ///
+    /// The request is checked against the soft limits of the axis before it is handed to the
+    /// `CANProxy`, so a rejected request is returned as [`Error::Request`] right away. Errors
+    /// from sending it are reported on [`ODriveGroup::send_errors()`] instead.
+    pub fn send<F: FnOnce(&Axis) -> CANRequest>(&self, axis_id: &AxisID, f: F) -> Result<(), Error> {
+        let request = self.check_limits(f(self.get_axis(axis_id)?))?;
+        self.can.send(request);
+        Ok(())
+    }
+
+    /// Sends the request specified by the closure to every axis without waiting for them to
+    /// be written to the CAN bus, like [`ODriveGroup::send()`]. The requests that exceeded
+    /// the soft limits of their axis are returned, and the rest are sent regardless.
+    ///
+    /// ### Example
+    /// ```
+    /// use rustodrive::odrivegroup::ODriveGroup;
+    /// use rustodrive::canproxy::CANProxy;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     let odrives = ODriveGroup::new(can_rw, &[0, 1, 2]);
+    ///     for step in 0..500 {
+    ///         let rejected = odrives.send_many(|ax| ax.motor.set_input_vel(step as f32 * 0.001));
+    ///         assert!(rejected.is_empty());
+    ///     }
+    ///     if let Ok(error) = odrives.send_errors().try_recv() {
+    ///         println!("axis {} missed a setpoint", error.request.axis);
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn send_many<F: FnMut(&Axis) -> CANRequest>(&self, f: F) -> Vec<ErrorResponse> {
+        let mut rejected = vec![];
+        let mut allowed = vec![];
+        for request in self.members.requests(f) {
+            match request {
+                Ok(request) => allowed.push(request),
+                Err(error) => rejected.push(error),
+            }
+        }
+        self.can.send_many(allowed);
+        rejected
     }
 
-    fn convert_response<T: TryFrom<CANResponse, Error = ODriveError>>(
+    /// Returns the receiver for the errors of requests made with [`ODriveGroup::send()`] and
+    /// [`ODriveGroup::send_many()`]
+    pub fn send_errors(&self) -> &Receiver<ErrorResponse> {
+        self.can.send_errors()
+    }
+
+    pub(crate) fn convert_response<T: TryFrom<CANResponse, Error = Error>>(
         response: ODriveResponse,
     ) -> Result<Success<T>, ErrorResponse> {
         // For each received response, either add the error to the responses or
         // convert the response to the appropriate type
-        let resp_type = match response {
-            Ok(resp) => resp,
-            Err(err) => return Err(err),
-        };
+        let resp_type = response?;
 
         // We convert a response to the generic type if it has a body. Otherwise, we convert
         // the request made to the response so that it can be converted to the () type
@@ -178,9 +464,9 @@ impl<'a> ODriveGroup<'a> {
             ResponseType::Bodyless { req } => req,
         };
 
-        // We use the request to check if the command sent is a Read request. If it is, it panics. Otherwise it returns ()
+        // We use the request to check if the command sent is a Read request. If it is, it is an error. Otherwise it returns ()
         // If there is bad data that is a recoverable error, return ODriveError
-        return match can_to_convert.try_into() {
+        match can_to_convert.try_into() {
             Ok(data) => Ok(Success {
                 axis: can_to_convert.axis as usize,
                 sent_request: resp_type.request(),
@@ -188,24 +474,363 @@ impl<'a> ODriveGroup<'a> {
             }),
             Err(e) => Err(ErrorResponse {
                 request: resp_type.request(),
-                err: e,
+                err: match e {
+                    Error::WrongCommand { .. } => ODriveError::ConvertedWrongCommand,
+                    _ => ODriveError::ConvertedBadData,
+                },
             }),
-        };
+        }
+    }
+
+    fn get_axis(&self, id: &AxisID) -> Result<&Axis, Error> {
+        self.members.get_axis(id)
+    }
+
+    /// Requests a state on a single axis and blocks until the axis is in it, watching its
+    /// heartbeats. Sequences that end on their own, such as `FullCalibrationSequence`, are
+    /// waited on until the axis returns to `Idle`. The wait ends early if the axis reports
+    /// an error.
+    ///
+    /// Errors that are already set on the axis are reported as a failure, so clear them
+    /// first if needed. This returns [`Error::UnknownAxis`] if the axis is not part of the group.
+    ///
+    /// ### Example
+    /// ```
+    /// use std::time::Duration;
+    /// use rustodrive::odrivegroup::ODriveGroup;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::state::AxisState::ClosedLoop;
+    /// use rustodrive::transition::TransitionOutcome;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+    ///     match odrives.transition_axis(&0, ClosedLoop, Duration::from_secs(1)) {
+    ///         Ok(TransitionOutcome::Reached) => println!("axis 0 is in closed loop"),
+    ///         other => println!("axis 0 did not enter closed loop: {:?}", other),
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn transition_axis(&self, axis_id: &AxisID, state: AxisState, timeout: Duration) -> Result<TransitionOutcome, Error> {
+        self.get_axis(axis_id)?;
+        let mut outcomes = transition::transition(&self.can, &[*axis_id], state, timeout);
+        Ok(outcomes.remove(axis_id).unwrap())
+    }
+
+    /// Requests a state on every axis at once and waits for each of them to reach it,
+    /// like [`ODriveGroup::transition_axis()`]. The outcome of every axis is returned by axis ID
+    pub fn transition_all(&self, state: AxisState, timeout: Duration) -> BTreeMap<AxisID, TransitionOutcome> {
+        transition::transition(&self.can, &self.axis_ids(), state, timeout)
+    }
+
+    /// Requests a state on every axis of a named sub-group and waits for each of them to
+    /// reach it, like [`ODriveGroup::transition_axis()`]
+    pub fn transition_subgroup(
+        &self,
+        name: &str,
+        state: AxisState,
+        timeout: Duration,
+    ) -> Result<BTreeMap<AxisID, TransitionOutcome>, Error> {
+        let axis_ids = self.subgroup_ids(name)?;
+        Ok(transition::transition(&self.can, &axis_ids, state, timeout))
+    }
+
+    /// Calibrates every axis in the group: motor calibration, then the encoder index search
+    /// if enabled, then the encoder offset calibration. Each step is waited on like
+    /// [`ODriveGroup::transition_axis()`], and an axis that fails a step skips the rest.
+    ///
+    /// `on_progress` is called whenever an axis starts or finishes a step. The returned report
+    /// contains the motor and encoder errors of every axis that failed.
+    ///
+    /// ### Example
+    /// ```
+    /// use rustodrive::odrivegroup::ODriveGroup;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::calibration::{CalibrationEvent, CalibrationOptions};
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     let odrives = ODriveGroup::new(can_rw, &[0, 1, 2, 3]);
+    ///     // Calibrate two axes at a time to limit the current drawn from the power supply
+    ///     let options = CalibrationOptions { max_concurrent: Some(2), ..Default::default() };
+    ///     let report = odrives.calibrate(&options, |event| {
+    ///         if let CalibrationEvent::StepFinished { axis_id, step, outcome } = event {
+    ///             println!("axis {}: {} {:?}", axis_id, step, outcome);
+    ///         }
+    ///     });
+    ///     print!("{}", report);
+    /// }).unwrap();
+    /// ```
+    pub fn calibrate<F: FnMut(&CalibrationEvent)>(&self, options: &CalibrationOptions, on_progress: F) -> CalibrationReport {
+        calibration::calibrate(&self.can, &self.axis_ids(), options, on_progress)
+    }
+
+    /// Calls `f` at the fixed rate of `config.period` and sends the setpoints it returns,
+    /// until it returns `None`. Unlike `.all_axes()`, this does not wait for the setpoints to be
+    /// acknowledged before the next tick, so the closure only has to finish within a period.
+    /// Setpoints that exceed the soft limits of their axis are not sent.
+    ///
+    /// The closure is given the scheduled time of the tick, which should be used to compute the
+    /// setpoints. The loop period, jitter, overruns and the latency of writing the setpoints to
+    /// the bus are returned once the stream ends. This returns [`Error::InvalidPeriod`] without
+    /// calling `f` if `config.period` is zero.
+    ///
+    /// ### Example
+    /// ```
+    /// use rustodrive::axis::Axis;
+    /// use rustodrive::odrivegroup::ODriveGroup;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::streaming::StreamConfig;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+    ///     // Sway both axes at 1Hz for 5 seconds, streamed at 500Hz
+    ///     let stats = odrives.stream(&StreamConfig::with_frequency(500.0).unwrap(), |tick| {
+    ///         let t = tick.time.as_secs_f32();
+    ///         let position = 0.1 * (t * std::f32::consts::TAU).sin();
+    ///         (t < 5.0).then(|| vec![Axis::new(0).motor.set_input_pos(position), Axis::new(1).motor.set_input_pos(-position)])
+    ///     }).unwrap();
+    ///     print!("{}", stats);
+    /// }).unwrap();
+    /// ```
+    pub fn stream<F: FnMut(&StreamTick) -> Option<Vec<CANRequest>>>(&self, config: &StreamConfig, f: F) -> Result<StreamStats, Error> {
+        streaming::stream(&self.can, config, |request| self.check_limits(request), f)
     }
 
-    fn get_axis(&self, id: &AxisID) -> &Axis {
-        match self.axes.get(id) {
-            Some(axis) => axis,
-            None => panic!("Cannot retrieve axis {} that doesn't exist!", id),
+    /// Streams the trajectories of the player to their axes at the rate of `config`, starting
+    /// right away and ending with the last sample of the longest trajectory. The axes should
+    /// already be in closed loop position control with the `Passthrough` input mode.
+    ///
+    /// This returns [`Error::UnknownAxis`] without sending anything if the player has a
+    /// trajectory for an axis that is not part of the group.
+    ///
+    /// ### Example
+    /// ```
+    /// use rustodrive::odrivegroup::ODriveGroup;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::streaming::StreamConfig;
+    /// use rustodrive::trajectory::{Spline, TrajectoryPlayer, Trapezoidal};
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+    ///     let mut player = TrajectoryPlayer::new();
+    ///     player.add_track(0, Trapezoidal::new(0.0, 2.0, 1.0, 4.0), 0.0);
+    ///     player.add_track(1, Spline::through(&[(0.0, 0.0), (1.0, 0.5), (2.5, -0.25)]).unwrap(), 0.0);
+    ///     let stats = odrives.play(&player, &StreamConfig::with_frequency(500.0).unwrap()).unwrap();
+    ///     print!("{}", stats);
+    /// }).unwrap();
+    /// ```
+    pub fn play(&self, player: &TrajectoryPlayer, config: &StreamConfig) -> Result<StreamStats, Error> {
+        for axis_id in player.axis_ids() {
+            self.get_axis(&axis_id)?;
         }
+
+        let duration = player.duration();
+        let mut finished = false;
+        self.stream(config, |tick| {
+            if finished {
+                return None;
+            }
+            let t = tick.time.as_secs_f32();
+            finished = t >= duration;
+            Some(player.setpoints(t.min(duration)))
+        })
     }
 
-    fn first_axis_id(&self) -> usize {
-        **self.axes.iter().next().unwrap().0
+    /// Moves several axes to their targets so that they all start and arrive at the same time,
+    /// using the trapezoidal trajectory planners of the ODrives. The trajectory velocity and
+    /// acceleration limits of every axis are scaled to its distance, the axes are switched to
+    /// position control with the `TrapTraj` input mode, and then all targets are sent at once.
+    /// Axes that are already at their target are not sent anything.
+    ///
+    /// This returns as soon as the targets are sent, with the plan of the move. The axes should
+    /// already be in closed loop. Nothing is sent if an axis is not part of the group, its
+    /// position cannot be read, its target exceeds its soft limits, or `max_velocity` or
+    /// `max_acceleration` is not finite and positive ([`Error::InvalidMove`]).
+    ///
+    /// If switching any axis over to the move fails, no targets are sent and
+    /// [`Error::MoveSetupFailed`] is returned with the axes that were already (partly) switched over.
+    ///
+    /// The velocity an axis needs for the move is checked against its soft limits as well. With
+    /// [`LimitMode::Reject`] the move returns [`ODriveError::LimitExceeded`] before anything is
+    /// sent. With [`LimitMode::Clamp`] the whole move is slowed down until every axis is within
+    /// its limit, so the axes still arrive together.
+    ///
+    /// ### Example
+    /// ```
+    /// use std::time::Duration;
+    /// use rustodrive::odrivegroup::ODriveGroup;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::motion::CoordinatedMove;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     let odrives = ODriveGroup::new(can_rw, &[0, 1, 2]);
+    ///     let reach = CoordinatedMove {
+    ///         targets: vec![(0, 0.25), (1, -0.5), (2, 0.1)],
+    ///         max_velocity: 1.0,
+    ///         max_acceleration: 4.0,
+    ///     };
+    ///     if let Ok(plan) = odrives.coordinated_move(&reach) {
+    ///         std::thread::sleep(Duration::from_secs_f32(plan.duration));
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn coordinated_move(&self, coordinated: &CoordinatedMove) -> Result<MovePlan, Error> {
+        let plan = self.plan_move(coordinated)?;
+
+        let moving: Vec<_> = plan.axes.iter().filter(|(_, axis_move)| axis_move.velocity > 0.0).collect();
+        let mut setup = vec![];
+        for (axis_id, axis_move) in &moving {
+            let axis = self.get_axis(axis_id)?;
+            setup.push(axis.trajectory.set_traj_vel_limit(axis_move.velocity));
+            setup.push(axis.trajectory.set_traj_accel_limit(axis_move.acceleration, axis_move.acceleration));
+            setup.push(axis.motor.set_control_mode(ControlMode::PositionControl, InputMode::TrapTraj));
+        }
+        // The previous trajectory limits and control modes cannot be read back to restore them,
+        // so the axes that accepted any of the new configuration are reported instead
+        let mut reconfigured = BTreeSet::new();
+        let mut failure = None;
+        for response in self.can.request_many(setup) {
+            match response {
+                Ok(response) => {
+                    reconfigured.insert(response.request().axis as AxisID);
+                }
+                Err(error) => {
+                    failure.get_or_insert(error);
+                }
+            }
+        }
+        if let Some(error) = failure {
+            return Err(Error::MoveSetupFailed { reconfigured: reconfigured.into_iter().collect(), error });
+        }
+
+        // The targets go out back to back so the planners start together
+        let targets = moving.iter().map(|(axis_id, axis_move)| Axis::new(**axis_id).motor.set_input_pos(axis_move.target)).collect();
+        for response in self.can.request_many(targets) {
+            response?;
+        }
+        Ok(plan)
+    }
+
+    /// Same as [`ODriveGroup::coordinated_move()`], but the move is streamed from the host at
+    /// the rate of `config` instead of being planned on the ODrives, like [`ODriveGroup::play()`].
+    /// The axes should already be in closed loop position control with the `Passthrough` input mode.
+    pub fn stream_coordinated_move(&self, coordinated: &CoordinatedMove, config: &StreamConfig) -> Result<StreamStats, Error> {
+        let plan = self.plan_move(coordinated)?;
+        let mut player = TrajectoryPlayer::new();
+        for (axis_id, trajectory) in plan.trajectories() {
+            player.add_track(axis_id, trajectory, 0.0);
+        }
+        self.play(&player, config)
+    }
+
+    /// Reads the positions of the axes and plans the move from them, after making sure
+    /// every target and the velocity of every axis is within the soft limits of the axis
+    fn plan_move(&self, coordinated: &CoordinatedMove) -> Result<MovePlan, Error> {
+        let mut targets = vec![];
+        for (axis_id, target) in &coordinated.targets {
+            let request = self.check_limits(self.get_axis(axis_id)?.motor.set_input_pos(*target))?;
+            // A target outside the limits may have been clamped
+            targets.push((*axis_id, f32::from_le_bytes(request.data[0..4].try_into().unwrap())));
+        }
+
+        let reads = targets.iter().map(|(axis_id, _)| Axis::new(*axis_id).encoder.get_estimates()).collect();
+        let mut starts = BTreeMap::new();
+        for response in self.can.request_many(reads) {
+            let estimates: Success<EncoderEstimates> = Self::convert_response(response)?;
+            starts.insert(estimates.axis, estimates.data.position);
+        }
+
+        let plan = MovePlan::new(&starts, &targets, coordinated.max_velocity, coordinated.max_acceleration)?;
+
+        // Slowing the whole move down by a factor scales the velocity of every axis by it,
+        // and the acceleration by its square
+        let mut slowdown: f32 = 1.0;
+        for (axis_id, axis_move) in &plan.axes {
+            let limits = match self.members.limits.get(axis_id) {
+                Some(limits) => limits,
+                None => continue,
+            };
+            let max_velocity = match limits.max_velocity {
+                Some(max_velocity) if axis_move.velocity > max_velocity => max_velocity,
+                _ => continue,
+            };
+            match limits.mode {
+                LimitMode::Clamp if max_velocity > 0.0 => slowdown = slowdown.min(max_velocity / axis_move.velocity),
+                _ => {
+                    let request = Axis::new(*axis_id).trajectory.set_traj_vel_limit(axis_move.velocity);
+                    return Err(ErrorResponse { request, err: ODriveError::LimitExceeded(Limit::Velocity) }.into());
+                }
+            }
+        }
+
+        match slowdown < 1.0 {
+            true => Ok(MovePlan::new(
+                &starts,
+                &targets,
+                coordinated.max_velocity * slowdown,
+                coordinated.max_acceleration * slowdown * slowdown,
+            )?),
+            false => Ok(plan),
+        }
     }
 
-    pub fn reboot(&self) -> () {
-        ticket(self.first_axis_id(), Write(RebootODrive), [0; 8]);
+    /// Homes a single axis: finds its reference point, moves to the home at `config.offset` from
+    /// it, and zeroes the encoder there with `SetLinearCount`. This returns the home position
+    /// as it was measured before zeroing, and leaves the axis in `Idle`.
+    ///
+    /// This returns [`Error::UnknownAxis`] if the axis is not part of the group and
+    /// [`Error::HomingTimedOut`] if the reference point or home were not reached in time.
+    /// The speed towards a hard stop is checked against the soft limits of the axis. If it is
+    /// rejected, [`ODriveError::LimitExceeded`] is returned before the axis moves.
+    pub fn home_axis(&self, axis_id: &AxisID, config: &HomingConfig) -> Result<f32, Error> {
+        self.get_axis(axis_id)?;
+        homing::home_axis(&self.can, *axis_id, config, |request| self.check_limits(request))
+    }
+
+    /// Homes several axes one after the other, each with its own configuration, so that only
+    /// one axis moves at a time. The result of every axis is returned by axis ID.
+    ///
+    /// ### Example
+    /// ```
+    /// use rustodrive::odrivegroup::ODriveGroup;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::homing::{HomingConfig, HomingDirection, HomingMethod};
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+    ///     let knee = HomingConfig {
+    ///         direction: HomingDirection::Positive,
+    ///         speed: 0.25,
+    ///         current_threshold: 4.0,
+    ///         offset: -0.1,
+    ///         ..Default::default()
+    ///     };
+    ///     let hip = HomingConfig { method: HomingMethod::Index, ..Default::default() };
+    ///     for (axis, home) in odrives.home(&[(0, knee), (1, hip)]) {
+    ///         println!("axis {}: {:?}", axis, home);
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn home(&self, configs: &[(AxisID, HomingConfig)]) -> BTreeMap<AxisID, Result<f32, Error>> {
+        configs.iter().map(|(id, config)| (*id, self.home_axis(id, config))).collect()
+    }
+
+    fn first_axis_id(&self) -> Result<AxisID, Error> {
+        self.members.axes.keys().next().copied().ok_or(Error::EmptyGroup)
+    }
+
+    /// Reboots the ODrive that the first axis of the group belongs to, and returns once the
+    /// request was written to the CAN bus. This returns [`Error::EmptyGroup`] if the group
+    /// has no axes.
+    pub fn reboot(&self) -> Result<(), Error> {
+        let request = ticket(self.first_axis_id()?, Write(RebootODrive), [0; 8]);
+        self.can.request(request)?;
+        Ok(())
     }
 }
 
@@ -216,17 +841,20 @@ mod tests {
     use crate::canframe::CANRequest;
     use crate::canproxy::CANProxy;
     use crate::casts::Temperature;
-    use crate::response::Success;
+    use crate::limits::{Limit, LimitMode, SoftLimits};
+    use crate::response::{ODriveError, Success};
+    use crate::simulator::ODriveSimulator;
     use crate::state::ReadComm;
     use crate::state::{AxisState::{*, self}, ODriveCommand, WriteComm};
     use crate::tests::wait_for_msgs;
     use crate::utils::ResultAll;
+    use crate::Error;
 
     use super::ODriveGroup;
 
     #[test]
     fn test_axes() {
-        let mut proxy = CANProxy::new("fakecan");
+        let mut proxy = CANProxy::new("fakecan").unwrap();
 
         let (send, rcv) = channel();
 
@@ -241,7 +869,7 @@ mod tests {
 
             let responses: Success<Temperature> = odrives.axis(&1, |ax| ax.get_temperatures()).unwrap();
             send.send(responses).unwrap();
-        });
+        }).unwrap();
         let stop_all = proxy.begin();
 
         // test the that all the results are returned in the order they were sent
@@ -253,7 +881,7 @@ mod tests {
 
     #[test]
     fn test_all_axes() {
-        let mut proxy = CANProxy::new("fakecan");
+        let mut proxy = CANProxy::new("fakecan").unwrap();
 
         let (send, rcv) = channel();
 
@@ -272,7 +900,7 @@ mod tests {
             let responses: Vec<Success<()>> =
                 odrives.all_axes(|ax| ax.set_state(AxisState::FullCalibrationSequence)).unwrap_all();
             send.send(responses).unwrap();
-        });
+        }).unwrap();
         let stop_all = proxy.begin();
 
         // test the that all the results are returned in the order they were sent
@@ -283,4 +911,186 @@ mod tests {
             assert_eq!(response.sent_request, request);
         }
     }
+
+    #[test]
+    fn test_unknown_axis() {
+        let mut proxy = CANProxy::new("fakecan").unwrap();
+
+        let (send, rcv) = channel();
+        proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+
+            let response = odrives.axis::<Temperature, _>(&5, |ax| ax.get_temperatures());
+            send.send(response).unwrap();
+        }).unwrap();
+        let stop_all = proxy.begin();
+
+        let response = wait_for_msgs(rcv);
+        stop_all().unwrap();
+
+        assert!(matches!(response, Err(Error::UnknownAxis(5))));
+    }
+
+    #[test]
+    fn test_reboot() {
+        let simulator = ODriveSimulator::new(&[0, 1]);
+        let mut proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        proxy.register_rw("thread 1", move |can_rw| {
+            let mut odrives = ODriveGroup::new(can_rw, &[1]);
+            let rebooted = odrives.reboot();
+            odrives.remove_axis(1).unwrap();
+            send.send((rebooted, odrives.reboot())).unwrap();
+        }).unwrap();
+        let stop_all = proxy.begin();
+
+        let (rebooted, empty) = wait_for_msgs(rcv);
+        stop_all().unwrap();
+
+        assert!(rebooted.is_ok());
+        assert!(simulator.node(1).unwrap().is_rebooting());
+        assert!(!simulator.node(0).unwrap().is_rebooting());
+        assert!(matches!(empty, Err(Error::EmptyGroup)));
+    }
+
+    #[test]
+    fn test_add_remove_axes() {
+        let mut proxy = CANProxy::new("fakecan").unwrap();
+
+        let (send, rcv) = channel();
+        proxy.register_rw("thread 1", move |can_rw| {
+            let axis_ids = vec![3, 1];
+            let mut odrives = ODriveGroup::new(can_rw, &axis_ids);
+            odrives.add_axis(7);
+            odrives.add_axis(1);
+            odrives.define_subgroup("leg", &[1, 7]).unwrap();
+            odrives.remove_axis(1).unwrap();
+
+            let responses: Vec<Success<()>> = odrives.all_axes(|ax| ax.set_state(Idle)).unwrap_all();
+            send.send((
+                odrives.axis_ids(),
+                odrives.subgroup_ids("leg").unwrap(),
+                odrives.remove_axis(1),
+                responses.into_iter().map(|res| res.sent_request.axis).collect::<Vec<_>>(),
+            )).unwrap();
+        }).unwrap();
+        let stop_all = proxy.begin();
+
+        let (ids, leg, removed, requested) = wait_for_msgs(rcv);
+        stop_all().unwrap();
+
+        assert_eq!(ids, vec![3, 7]);
+        assert_eq!(leg, vec![7]);
+        assert!(matches!(removed, Err(Error::UnknownAxis(1))));
+        assert_eq!(requested, vec![3, 7]);
+    }
+
+    #[test]
+    fn test_subgroup() {
+        let mut proxy = CANProxy::new("fakecan").unwrap();
+
+        let (send, rcv) = channel();
+        proxy.register_rw("thread 1", move |can_rw| {
+            let mut odrives = ODriveGroup::new(can_rw, &[0, 1, 2, 3, 4, 5]);
+            odrives.define_subgroup("front_left_leg", &[4, 0, 2]).unwrap();
+            let bad_define = odrives.define_subgroup("back_left_leg", &[1, 9]);
+
+            let responses: Vec<Success<()>> = odrives
+                .subgroup("front_left_leg", |ax| ax.set_state(ClosedLoop))
+                .unwrap()
+                .unwrap_all();
+            let unknown = odrives.subgroup::<(), _>("back_left_leg", |ax| ax.set_state(ClosedLoop));
+            odrives.remove_subgroup("front_left_leg").unwrap();
+
+            send.send((
+                responses.into_iter().map(|res| res.sent_request.axis).collect::<Vec<_>>(),
+                bad_define,
+                unknown.map(|_| ()),
+                odrives.subgroup_ids("front_left_leg"),
+            )).unwrap();
+        }).unwrap();
+        let stop_all = proxy.begin();
+
+        let (requested, bad_define, unknown, removed) = wait_for_msgs(rcv);
+        stop_all().unwrap();
+
+        assert_eq!(requested, vec![0, 2, 4]);
+        assert!(matches!(bad_define, Err(Error::UnknownAxis(9))));
+        assert!(matches!(unknown, Err(Error::UnknownGroup(name)) if name == "back_left_leg"));
+        assert!(matches!(removed, Err(Error::UnknownGroup(_))));
+    }
+
+    #[test]
+    fn test_soft_limits() {
+        let simulator = ODriveSimulator::new(&[0, 1, 2]);
+        let mut proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        proxy.register_rw("thread 1", move |can_rw| {
+            let mut odrives = ODriveGroup::new(can_rw, &[0, 1, 2]);
+            let limits = SoftLimits { position: Some((-1.0, 1.0)), max_torque: Some(0.5), ..Default::default() };
+            odrives.set_limits(&0, limits.clone()).unwrap();
+            odrives.set_limits(&1, SoftLimits { mode: LimitMode::Clamp, ..limits }).unwrap();
+            let unknown = odrives.set_limits(&7, SoftLimits::default());
+            let inverted = odrives.set_limits(&2, SoftLimits { position: Some((1.0, -1.0)), ..Default::default() });
+
+            // Axis 2 has no limits, and axis 0 still gets the next command
+            let positions = odrives.all_axes::<(), _>(|ax| ax.motor.set_input_pos(3.0));
+            let torque = odrives.axis::<(), _>(&0, |ax| ax.motor.set_input_torque(-0.4));
+            let rejected = odrives.axis::<(), _>(&0, |ax| ax.motor.set_input_torque(-0.6));
+            send.send((positions, torque.is_ok(), rejected, unknown, inverted)).unwrap();
+        }).unwrap();
+        let stop_all = proxy.begin();
+
+        let (positions, torque_sent, rejected, unknown, inverted) = wait_for_msgs(rcv);
+        stop_all().unwrap();
+
+        assert_eq!(positions[0].as_ref().unwrap_err().err, ODriveError::LimitExceeded(Limit::Position));
+        assert_eq!(positions[1].as_ref().unwrap().sent_request.axis, 1);
+        assert!(positions[2].is_ok());
+        assert!(torque_sent);
+        assert!(matches!(rejected, Err(Error::Request(res)) if res.err == ODriveError::LimitExceeded(Limit::Torque)));
+        assert!(matches!(unknown, Err(Error::UnknownAxis(7))));
+        assert!(matches!(inverted, Err(Error::InvalidLimits { axis: 2, limit: Limit::Position })));
+
+        assert_eq!(simulator.node(0).unwrap().input_pos, 0.0);
+        assert_eq!(simulator.node(0).unwrap().input_torque, -0.4);
+        assert_eq!(simulator.node(1).unwrap().input_pos, 1.0);
+        assert_eq!(simulator.node(2).unwrap().input_pos, 3.0);
+    }
+
+    #[test]
+    fn test_send_many() {
+        let simulator = ODriveSimulator::new(&[0, 1, 2]);
+        let mut proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        proxy.register_rw("thread 1", move |can_rw| {
+            let mut odrives = ODriveGroup::new(can_rw, &[0, 1, 2]);
+            odrives.set_limits(&1, SoftLimits { max_velocity: Some(1.0), ..Default::default() }).unwrap();
+
+            let rejected = odrives.send_many(|ax| ax.motor.set_input_vel(1.5));
+            let single = odrives.send(&1, |ax| ax.motor.set_input_vel(-0.5));
+            let unknown = odrives.send(&4, |ax| ax.motor.set_input_vel(0.0));
+            // A request after the sent ones is only answered once they were written
+            let _: Success<Temperature> = odrives.axis(&0, |ax| ax.get_temperatures()).unwrap();
+            send.send((rejected, single.is_ok(), unknown, odrives.send_errors().try_recv().ok())).unwrap();
+        }).unwrap();
+        let stop_all = proxy.begin();
+
+        let (rejected, single_sent, unknown, send_error) = wait_for_msgs(rcv);
+        stop_all().unwrap();
+
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].request.axis, 1);
+        assert_eq!(rejected[0].err, ODriveError::LimitExceeded(Limit::Velocity));
+        assert!(single_sent);
+        assert!(matches!(unknown, Err(Error::UnknownAxis(4))));
+        assert_eq!(send_error, None);
+
+        assert_eq!(simulator.node(0).unwrap().input_vel, 1.5);
+        assert_eq!(simulator.node(1).unwrap().input_vel, -0.5);
+        assert_eq!(simulator.node(2).unwrap().input_vel, 1.5);
+    }
 }
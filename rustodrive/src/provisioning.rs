@@ -0,0 +1,235 @@
+use std::{
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    axis::{Axis, AxisID},
+    canframe::{CANResponse, FrameFilter, ODriveCANFrame},
+    casts::Heartbeat,
+    discovery::{self, DiscoveredNode},
+    state::ReadComm,
+    threads::{CANThreadCommunicator, ReadWriteCANThread},
+    Error,
+};
+
+/// How long an ODrive may take to reboot after its configuration was saved
+const REBOOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The highest node ID that can be addressed. The node ID takes the bits of the 11 bit
+/// standard CAN ID that are left over after the command ID
+pub(crate) const MAX_NODE_ID: AxisID = (0x7FF >> ODriveCANFrame::AXIS_BITS) as AxisID;
+
+/// Moves the axis at `current` to the node ID `new`.
+///
+/// Before anything is sent, `new` is checked to be at most [`MAX_NODE_ID`] and the bus is listened to for `window` to make sure the axis
+/// exists and that no other axis already sends heartbeats with the new ID. Afterwards the
+/// axis must send a heartbeat with the new ID within `window`. If `persist` is set, the
+/// configuration is saved, which reboots the ODrive. The axis must go silent and then
+/// reappear with the new ID, and not with the old one.
+pub(crate) fn assign_node_id(
+    can: &ReadWriteCANThread,
+    current: AxisID,
+    new: AxisID,
+    persist: bool,
+    window: Duration,
+) -> Result<DiscoveredNode, Error> {
+    if new > MAX_NODE_ID {
+        return Err(Error::NodeIdOutOfRange(new));
+    }
+
+    let inventory = discovery::discover(can, window);
+    if inventory.node(current).is_none() {
+        return Err(Error::NodeNotFound(current));
+    }
+    if new != current && inventory.node(new).is_some() {
+        return Err(Error::NodeIdTaken(new));
+    }
+
+    // Subscribe before sending so that the first heartbeat with the new ID is not missed
+    let heartbeats = subscribe_heartbeats(can, new);
+    can.request(Axis::new(current).motor.set_node_id(new))?;
+    let mut node = wait_for_heartbeat(&heartbeats, new, window)?;
+
+    if persist {
+        // The heartbeat interval is estimated from the heartbeats heard during discovery.
+        // Going without a heartbeat for two intervals means the axis went silent
+        let heartbeats_seen = inventory.node(current).map_or(1, |node| node.heartbeats.max(1));
+        let gap = window * 2 / heartbeats_seen as u32;
+
+        let heartbeats = CANThreadCommunicator::subscribe(can, FrameFilter::cmd(ReadComm::GetHeartbeat));
+        can.request(Axis::new(new).save_configuration())?;
+        node = wait_for_reboot(&heartbeats, current, new, gap)?;
+    }
+
+    Ok(node)
+}
+
+/// Waits for the axis to stop sending heartbeats while it reboots and then for it to send
+/// them again with the new ID. Heartbeats it sent before rebooting prove nothing, and firmware
+/// that does not support saving comes back with the old ID
+fn wait_for_reboot(
+    heartbeats: &Receiver<CANResponse>,
+    current: AxisID,
+    new: AxisID,
+    gap: Duration,
+) -> Result<DiscoveredNode, Error> {
+    let deadline = Instant::now() + REBOOT_TIMEOUT;
+
+    // Drain the heartbeats the axis still sends until it has been silent for a whole gap
+    let mut last_heard = Instant::now();
+    while last_heard.elapsed() < gap {
+        if Instant::now() >= deadline {
+            return Err(Error::NodeIdNotSaved(current));
+        }
+        let wait = (last_heard + gap).saturating_duration_since(Instant::now());
+        if let Ok(frame) = heartbeats.recv_timeout(wait) {
+            if frame.axis == new as u32 {
+                last_heard = Instant::now();
+            }
+        }
+    }
+
+    // The first heartbeat after the reboot tells which ID the axis came back with.
+    // The old ID must then stay silent for another gap
+    let mut node = None;
+    let mut listen_until = deadline;
+    while let Ok(frame) = heartbeats.recv_timeout(listen_until.saturating_duration_since(Instant::now())) {
+        if frame.axis == current as u32 && current != new {
+            return Err(Error::NodeIdNotSaved(current));
+        }
+        if frame.axis == new as u32 {
+            if node.is_none() {
+                listen_until = Instant::now() + gap;
+            }
+            node = Some(node_from_heartbeat(frame, new));
+        }
+    }
+    node.ok_or(Error::NodeIdNotSaved(current))
+}
+
+fn subscribe_heartbeats(can: &ReadWriteCANThread, axis: AxisID) -> Receiver<CANResponse> {
+    CANThreadCommunicator::subscribe(can, FrameFilter {
+        axis: Some(axis as u32),
+        cmd: Some(ReadComm::GetHeartbeat),
+    })
+}
+
+fn wait_for_heartbeat(
+    heartbeats: &Receiver<CANResponse>,
+    axis_id: AxisID,
+    window: Duration,
+) -> Result<DiscoveredNode, Error> {
+    match heartbeats.recv_timeout(window) {
+        Ok(frame) => Ok(node_from_heartbeat(frame, axis_id)),
+        Err(_) => Err(Error::NodeNotFound(axis_id)),
+    }
+}
+
+fn node_from_heartbeat(frame: CANResponse, axis_id: AxisID) -> DiscoveredNode {
+    DiscoveredNode {
+        axis_id,
+        heartbeat: Heartbeat::try_from(frame).ok(),
+        heartbeats: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, time::{Duration, Instant}};
+
+    use crate::{
+        canproxy::CANProxy,
+        discovery::DiscoveredNode,
+        simulator::{ODriveSimulator, SimulationParams},
+        state::AxisState,
+        tests::wait_for_msgs,
+        Error,
+    };
+
+    const WINDOW: Duration = Duration::from_millis(250);
+
+    #[test]
+    fn test_assign_node_id() {
+        let simulator = ODriveSimulator::new(&[0, 1]);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("provisioning", move |can_rw| {
+            send.send(can_rw.assign_node_id_with_window(1, 7, false, WINDOW)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let node = wait_for_msgs(rcv).unwrap();
+        stop_proxy().unwrap();
+
+        assert_eq!(node.axis_id, 7);
+        assert_eq!(node.heartbeat.unwrap().current_state, AxisState::Idle);
+        assert!(simulator.node(1).is_none());
+        // The new ID was never saved, so it is lost when the ODrive reboots
+        assert_eq!(simulator.node(7).unwrap().saved_node_id, 1);
+    }
+
+    fn assign_persisted(params: SimulationParams) -> (ODriveSimulator, Result<DiscoveredNode, Error>, Duration) {
+        let simulator = ODriveSimulator::with_params(&[3], params);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("provisioning", move |can_rw| {
+            let start = Instant::now();
+            let node = can_rw.assign_node_id_with_window(3, 5, true, WINDOW);
+            send.send((node, start.elapsed())).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let (node, elapsed) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+        (simulator, node, elapsed)
+    }
+
+    #[test]
+    fn test_assign_node_id_persist() {
+        let params = SimulationParams { reboot_time: Duration::from_millis(600), ..Default::default() };
+        let (simulator, node, elapsed) = assign_persisted(params);
+
+        assert_eq!(node.unwrap().axis_id, 5);
+        // The heartbeats sent before the reboot were not taken as proof that it came back
+        assert!(elapsed >= Duration::from_millis(600), "{:?}", elapsed);
+        let rebooted = simulator.node(5).unwrap();
+        assert!(!rebooted.is_rebooting());
+        assert_eq!(rebooted.saved_node_id, 5);
+    }
+
+    #[test]
+    fn test_assign_node_id_persist_unsupported() {
+        // Old firmware reboots without saving, so the axis comes back with its old ID
+        let params = SimulationParams { saves_on_reboot: false, ..Default::default() };
+        let (simulator, node, _elapsed) = assign_persisted(params);
+
+        assert!(matches!(node, Err(Error::NodeIdNotSaved(3))), "{:?}", node);
+        assert!(simulator.node(3).is_some());
+        assert!(simulator.node(5).is_none());
+    }
+
+    #[test]
+    fn test_assign_node_id_errors() {
+        let simulator = ODriveSimulator::new(&[0, 1]);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("provisioning", move |can_rw| {
+            let taken = can_rw.assign_node_id_with_window(0, 1, false, WINDOW);
+            let missing = can_rw.assign_node_id_with_window(4, 2, false, WINDOW);
+            let out_of_range = can_rw.assign_node_id_with_window(0, 0x40, false, WINDOW);
+            send.send((taken, missing, out_of_range)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let (taken, missing, out_of_range) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert!(matches!(taken, Err(Error::NodeIdTaken(1))));
+        assert!(matches!(missing, Err(Error::NodeNotFound(4))));
+        assert!(matches!(out_of_range, Err(Error::NodeIdOutOfRange(0x40))));
+        // Nothing was sent to the axes when a check failed
+        assert!(simulator.node(0).is_some());
+        assert!(simulator.node(1).is_some());
+    }
+}
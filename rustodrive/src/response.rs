@@ -1,4 +1,4 @@
-use crate::{canframe::{CANRequest, CANResponse, ODriveCANFrame}, axis::AxisID};
+use crate::{canframe::{CANRequest, CANResponse, ODriveCANFrame}, axis::AxisID, limits::Limit};
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct ErrorResponse {
@@ -10,7 +10,11 @@ pub struct ErrorResponse {
 pub enum ODriveError {
     FailedToSend,
     NoResponse,
-    ConvertedBadData
+    ConvertedBadData,
+    /// The response was converted into the type for a different command
+    ConvertedWrongCommand,
+    /// The request was not sent because it exceeds the soft limits of the axis
+    LimitExceeded(Limit),
 }
 
 pub type ODriveResponse = Result<ResponseType, ErrorResponse>;
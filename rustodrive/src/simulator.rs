@@ -0,0 +1,833 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use socketcan::CANFrame;
+
+use crate::{
+    canframe::{CANRequest, CANResponse},
+    cansocket::CanTransport,
+    error::AxisError,
+    state::{AxisState, ControlMode, InputMode, ODriveCommand, ReadComm, WriteComm},
+    utils::ResponseManip,
+};
+
+/// The maximum number of frames waiting to be read before the oldest ones are dropped,
+/// similar to the receive buffer of a CAN socket
+const RECEIVE_BUFFER_SIZE: usize = 1024;
+
+/// Physical and configuration parameters shared by every simulated axis.
+///
+/// Positions are in turns, velocities in turns/s and torques in Nm,
+/// the same units the ODrive uses on the CAN bus.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationParams {
+    /// Rotational inertia of the motor and its load in kg*m^2
+    pub inertia: f32,
+    /// Viscous friction in Nm/(turn/s)
+    pub damping: f32,
+    /// Torque constant of the motor in Nm/A
+    pub torque_constant: f32,
+    /// Resistance of a motor phase in Ohms
+    pub phase_resistance: f32,
+    /// Bus voltage of the power supply with no load in Volts
+    pub supply_voltage: f32,
+    /// Internal resistance of the power supply in Ohms
+    pub supply_resistance: f32,
+    /// Ambient temperature that the motor and inverter cool down to in Celsius
+    pub ambient_temperature: f32,
+    /// Thermal resistance of the motor and inverter in Celsius/W
+    pub thermal_resistance: f32,
+    /// Thermal time constant of the motor and inverter
+    pub thermal_time_constant: Duration,
+    /// Counts per revolution of the simulated encoder
+    pub encoder_cpr: i32,
+    /// How long each calibration step (motor, encoder offset and index search) takes
+    pub calibration_time: Duration,
+    /// How far in turns the rotor coasts past the index pulse before the index search stops
+    pub index_overshoot: f32,
+    /// If true, axes can enter closed loop control without being calibrated first
+    pub precalibrated: bool,
+    /// How often each axis broadcasts its heartbeat, like `axis.config.can.heartbeat_rate_ms`
+    pub heartbeat_interval: Option<Duration>,
+    /// How often each axis broadcasts its encoder estimates, like `axis.config.can.encoder_rate_ms`
+    pub encoder_interval: Option<Duration>,
+    /// Timestep used to integrate the motor physics
+    pub timestep: Duration,
+    /// Mechanical end stops as the lowest and highest reachable position in turns.
+    /// The rotor stops dead when it runs into one
+    pub hard_stops: Option<(f32, f32)>,
+    /// How long an axis may go without receiving a CAN message before its watchdog trips,
+    /// like `axis.config.watchdog_timeout`. `None` disables the watchdog
+    pub watchdog_timeout: Option<Duration>,
+    /// How long an axis is silent while it reboots. It ignores every frame sent to it
+    /// and broadcasts nothing in the meantime
+    pub reboot_time: Duration,
+    /// If false, rebooting does not save the configuration first, like firmware older than v0.6
+    pub saves_on_reboot: bool,
+}
+
+impl Default for SimulationParams {
+    fn default() -> Self {
+        Self {
+            inertia: 0.0005,
+            damping: 0.001,
+            torque_constant: 8.27 / 270.0,
+            phase_resistance: 0.05,
+            supply_voltage: 24.0,
+            supply_resistance: 0.05,
+            ambient_temperature: 25.0,
+            thermal_resistance: 3.0,
+            thermal_time_constant: Duration::from_secs(60),
+            encoder_cpr: 8192,
+            calibration_time: Duration::from_millis(500),
+            index_overshoot: 0.05,
+            precalibrated: false,
+            heartbeat_interval: Some(Duration::from_millis(100)),
+            encoder_interval: Some(Duration::from_millis(10)),
+            timestep: Duration::from_micros(500),
+            hard_stops: None,
+            watchdog_timeout: None,
+            reboot_time: Duration::from_millis(500),
+            saves_on_reboot: true,
+        }
+    }
+}
+
+/// The state of a single simulated ODrive axis. Every `Write` command sent to its node ID
+/// updates it and every `Read` command is answered with data taken from it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulatedODrive {
+    pub node_id: u32,
+    /// The node ID stored in the saved configuration, which the axis returns to on reboot
+    pub saved_node_id: u32,
+    pub state: AxisState,
+    pub control_mode: ControlMode,
+    pub input_mode: InputMode,
+    pub axis_error: u32,
+    pub motor_error: u64,
+    pub encoder_error: u32,
+    pub sensorless_error: u32,
+    pub motor_calibrated: bool,
+    pub encoder_ready: bool,
+    /// The position the encoder is set to at the index pulse, like `axis.encoder.config.index_offset`
+    pub index_offset: f32,
+
+    pub input_pos: f32,
+    pub input_vel: f32,
+    pub input_torque: f32,
+    pub vel_limit: f32,
+    pub current_limit: f32,
+    pub traj_vel_limit: f32,
+    pub traj_accel_limit: f32,
+    pub traj_decel_limit: f32,
+    pub traj_inertia: f32,
+    pub pos_gain: f32,
+    pub vel_gain: f32,
+    pub vel_integrator_gain: f32,
+
+    pub position: f32,
+    pub velocity: f32,
+    pub iq_setpoint: f32,
+    pub iq_measured: f32,
+    pub bus_voltage: f32,
+    pub bus_current: f32,
+    pub motor_temperature: f32,
+    pub inverter_temperature: f32,
+
+    /// The setpoint the trapezoidal trajectory planner is currently at
+    traj_pos: f32,
+    traj_vel: f32,
+    vel_integrator: f32,
+    /// Time left in the calibration step the axis is currently running
+    calibration_remaining: Duration,
+    /// Time left until the axis has finished rebooting
+    reboot_remaining: Duration,
+    since_heartbeat: Duration,
+    since_encoder: Duration,
+    since_fed: Duration,
+}
+
+impl SimulatedODrive {
+    pub fn new(node_id: u32, params: &SimulationParams) -> Self {
+        Self {
+            node_id,
+            saved_node_id: node_id,
+            state: AxisState::Idle,
+            control_mode: ControlMode::PositionControl,
+            input_mode: InputMode::Passthrough,
+            axis_error: 0,
+            motor_error: 0,
+            encoder_error: 0,
+            sensorless_error: 0,
+            motor_calibrated: params.precalibrated,
+            encoder_ready: params.precalibrated,
+            index_offset: 0.0,
+
+            input_pos: 0.0,
+            input_vel: 0.0,
+            input_torque: 0.0,
+            vel_limit: 2.0,
+            current_limit: 10.0,
+            traj_vel_limit: 2.0,
+            traj_accel_limit: 0.5,
+            traj_decel_limit: 0.5,
+            traj_inertia: 0.0,
+            pos_gain: 20.0,
+            vel_gain: 1.0 / 6.0,
+            vel_integrator_gain: 1.0 / 3.0,
+
+            position: 0.0,
+            velocity: 0.0,
+            iq_setpoint: 0.0,
+            iq_measured: 0.0,
+            bus_voltage: params.supply_voltage,
+            bus_current: 0.0,
+            motor_temperature: params.ambient_temperature,
+            inverter_temperature: params.ambient_temperature,
+
+            traj_pos: 0.0,
+            traj_vel: 0.0,
+            vel_integrator: 0.0,
+            calibration_remaining: Duration::ZERO,
+            reboot_remaining: Duration::ZERO,
+            since_heartbeat: Duration::ZERO,
+            since_encoder: Duration::ZERO,
+            since_fed: Duration::ZERO,
+        }
+    }
+
+    /// Whether the trapezoidal trajectory planner has reached `input_pos`
+    pub fn trajectory_done(&self) -> bool {
+        self.traj_pos == self.input_pos && self.traj_vel == 0.0
+    }
+
+    /// Advances the simulation of this axis by `dt`, integrating the motor physics in
+    /// steps of [`SimulationParams::timestep`]
+    pub fn step(&mut self, dt: Duration, params: &SimulationParams) {
+        if self.is_rebooting() {
+            self.reboot_remaining = self.reboot_remaining.saturating_sub(dt);
+            return;
+        }
+
+        let mut remaining = dt;
+        while !remaining.is_zero() {
+            let substep = remaining.min(params.timestep);
+            self.step_watchdog(substep, params);
+            self.step_calibration(substep, params);
+            self.step_physics(substep.as_secs_f32(), params);
+            remaining -= substep;
+        }
+    }
+
+    /// Whether the axis is still rebooting and can not be talked to
+    pub fn is_rebooting(&self) -> bool {
+        !self.reboot_remaining.is_zero()
+    }
+
+    fn is_calibrating(&self) -> bool {
+        matches!(
+            self.state,
+            AxisState::FullCalibrationSequence
+                | AxisState::MotorCalibration
+                | AxisState::EncoderIndexSearch
+                | AxisState::EncoderOffsetCalib
+                | AxisState::EncoderDirFind
+                | AxisState::EncoderHallPolarityCalib
+                | AxisState::EncoderHallPhaseCalib
+        )
+    }
+
+    /// Every CAN message sent to the axis feeds its watchdog
+    pub fn feed_watchdog(&mut self) {
+        self.since_fed = Duration::ZERO;
+    }
+
+    /// An axis that is not idle stops with an error if its watchdog was not fed in time
+    fn step_watchdog(&mut self, dt: Duration, params: &SimulationParams) {
+        self.since_fed += dt;
+        let expired = params.watchdog_timeout.is_some_and(|timeout| self.since_fed > timeout);
+        if expired && self.state != AxisState::Idle {
+            self.axis_error |= AxisError::WatchdogTimerExpired as u32;
+            self.state = AxisState::Idle;
+        }
+    }
+
+    /// Calibration states finish after their configured time and return to `Idle`
+    fn step_calibration(&mut self, dt: Duration, params: &SimulationParams) {
+        if !self.is_calibrating() {
+            return;
+        }
+
+        self.calibration_remaining = self.calibration_remaining.saturating_sub(dt);
+        if self.calibration_remaining.is_zero() {
+            match self.state {
+                AxisState::MotorCalibration => self.motor_calibrated = true,
+                AxisState::FullCalibrationSequence => {
+                    self.motor_calibrated = true;
+                    self.encoder_ready = true;
+                }
+                AxisState::EncoderOffsetCalib => self.encoder_ready = self.motor_calibrated,
+                // The encoder is referenced to the index pulse, and the rotor comes to rest past it
+                AxisState::EncoderIndexSearch => {
+                    self.position = self.index_offset + params.index_overshoot;
+                    self.input_pos = self.position;
+                    self.traj_pos = self.position;
+                    self.velocity = 0.0;
+                }
+                _ => {}
+            }
+            self.state = AxisState::Idle;
+        }
+    }
+
+    fn step_physics(&mut self, dt: f32, params: &SimulationParams) {
+        let max_torque = self.current_limit * params.torque_constant;
+
+        let torque = if self.state == AxisState::ClosedLoop {
+            self.controller_torque(dt, params).clamp(-max_torque, max_torque)
+        } else {
+            self.vel_integrator = 0.0;
+            0.0
+        };
+
+        // Semi-implicit euler integration of the rotor. The inertia is converted so that
+        // the acceleration is in turns/s^2
+        let acceleration = (torque - params.damping * self.velocity) / (params.inertia * 2.0 * PI);
+        self.velocity += acceleration * dt;
+        self.position += self.velocity * dt;
+        if let Some((low, high)) = params.hard_stops {
+            if self.position <= low || self.position >= high {
+                self.position = self.position.clamp(low, high);
+                self.velocity = 0.0;
+            }
+        }
+
+        // Electrical model
+        self.iq_setpoint = torque / params.torque_constant;
+        self.iq_measured = self.iq_setpoint;
+        let copper_loss = 1.5 * self.iq_measured.powi(2) * params.phase_resistance;
+        let mechanical_power = torque * self.velocity * 2.0 * PI;
+        let electrical_power = mechanical_power + copper_loss;
+
+        // The bus voltage sags with the current drawn from the supply
+        self.bus_current = electrical_power / self.bus_voltage.max(1.0);
+        self.bus_voltage = params.supply_voltage - self.bus_current * params.supply_resistance;
+
+        // First order thermal model that heats up from the losses and cools towards ambient
+        let tau = params.thermal_time_constant.as_secs_f32().max(f32::EPSILON);
+        let motor_target = params.ambient_temperature + copper_loss * params.thermal_resistance;
+        let inverter_target = params.ambient_temperature + 0.2 * copper_loss * params.thermal_resistance;
+        self.motor_temperature += (motor_target - self.motor_temperature) * dt / tau;
+        self.inverter_temperature += (inverter_target - self.inverter_temperature) * dt / tau;
+    }
+
+    /// The cascaded position/velocity controller of the ODrive
+    fn controller_torque(&mut self, dt: f32, params: &SimulationParams) -> f32 {
+        let (pos_setpoint, vel_feedforward) = match self.input_mode {
+            InputMode::TrapTraj => {
+                self.step_trajectory(dt);
+                (self.traj_pos, self.traj_vel)
+            }
+            _ => (self.input_pos, self.input_vel),
+        };
+
+        let vel_setpoint = match self.control_mode {
+            ControlMode::PositionControl => {
+                self.pos_gain * (pos_setpoint - self.position) + vel_feedforward
+            }
+            ControlMode::VelocityControl => self.input_vel,
+            ControlMode::TorqueControl | ControlMode::VoltageControl => {
+                return self.input_torque;
+            }
+        }
+        .clamp(-self.vel_limit, self.vel_limit);
+
+        let vel_error = vel_setpoint - self.velocity;
+        let max_torque = self.current_limit * params.torque_constant;
+        self.vel_integrator = (self.vel_integrator + self.vel_integrator_gain * vel_error * dt)
+            .clamp(-max_torque, max_torque);
+
+        self.vel_gain * vel_error + self.vel_integrator + self.input_torque
+    }
+
+    /// Moves the trajectory setpoint towards `input_pos` without exceeding the
+    /// trajectory velocity, acceleration and deceleration limits
+    fn step_trajectory(&mut self, dt: f32) {
+        let error = self.input_pos - self.traj_pos;
+        if error.abs() < 1e-6 && self.traj_vel.abs() < self.traj_decel_limit * dt {
+            self.traj_pos = self.input_pos;
+            self.traj_vel = 0.0;
+            return;
+        }
+
+        // The fastest speed from which we can still stop at the target
+        let stopping_speed = (2.0 * self.traj_decel_limit * error.abs()).sqrt();
+        let desired_vel = error.signum() * stopping_speed.min(self.traj_vel_limit);
+
+        let max_change = if desired_vel.abs() > self.traj_vel.abs() {
+            self.traj_accel_limit * dt
+        } else {
+            self.traj_decel_limit * dt
+        };
+        self.traj_vel += (desired_vel - self.traj_vel).clamp(-max_change, max_change);
+        self.traj_pos += self.traj_vel * dt;
+    }
+
+    /// Requests a new axis state the same way `SetAxisRequestedState` does
+    pub fn request_state(&mut self, state: AxisState, params: &SimulationParams) {
+        match state {
+            AxisState::ClosedLoop if !(self.motor_calibrated && self.encoder_ready) => {
+                self.axis_error |= AxisError::MissingEstimate as u32;
+                self.state = AxisState::Idle;
+            }
+            AxisState::ClosedLoop => {
+                // Start holding the current position so the axis does not jump
+                self.input_pos = self.position;
+                self.traj_pos = self.position;
+                self.traj_vel = 0.0;
+                self.state = AxisState::ClosedLoop;
+            }
+            AxisState::FullCalibrationSequence => {
+                self.calibration_remaining = params.calibration_time * 2;
+                self.state = state;
+            }
+            AxisState::EncoderOffsetCalib | AxisState::EncoderIndexSearch if !self.motor_calibrated => {
+                self.motor_error |= crate::error::MotorError::UnknownPhaseEstimate as u64;
+                self.state = AxisState::Idle;
+            }
+            AxisState::MotorCalibration
+            | AxisState::EncoderIndexSearch
+            | AxisState::EncoderOffsetCalib
+            | AxisState::EncoderDirFind
+            | AxisState::EncoderHallPolarityCalib
+            | AxisState::EncoderHallPhaseCalib => {
+                self.calibration_remaining = params.calibration_time;
+                self.state = state;
+            }
+            AxisState::StartupSequence | AxisState::Homing | AxisState::LockinSpin => {
+                self.state = AxisState::Idle;
+            }
+            AxisState::Undefined | AxisState::Idle => self.state = AxisState::Idle,
+        }
+    }
+
+    /// Applies a `Write` command sent to this axis
+    pub fn handle_write(&mut self, cmd: WriteComm, data: [u8; 8], params: &SimulationParams) {
+        let (low, high) = ResponseManip::split_32(data);
+        let low_f32 = f32::from_le_bytes(low);
+        let high_f32 = f32::from_le_bytes(high);
+
+        match cmd {
+            WriteComm::EStop => {
+                self.axis_error |= AxisError::EStopRequested as u32;
+                self.state = AxisState::Idle;
+            }
+            WriteComm::SetAxisNodeID => self.node_id = u32::from_le_bytes(low),
+            WriteComm::SetAxisRequestedState => {
+                if let Ok(state) = AxisState::try_from(data[0]) {
+                    self.request_state(state, params);
+                }
+            }
+            WriteComm::SetControllerMode => {
+                if let Ok(control_mode) = ControlMode::try_from(i32::from_le_bytes(low)) {
+                    self.control_mode = control_mode;
+                }
+                if let Ok(input_mode) = InputMode::try_from(i32::from_le_bytes(high)) {
+                    // Like the ODrive, the planner starts from the setpoint the axis already has
+                    if input_mode == InputMode::TrapTraj && self.input_mode != InputMode::TrapTraj {
+                        self.traj_pos = self.input_pos;
+                        self.traj_vel = 0.0;
+                    }
+                    self.input_mode = input_mode;
+                }
+            }
+            WriteComm::SetInputPosition => {
+                let (vel_ff, torque_ff) = ResponseManip::split_16(high);
+                self.input_pos = low_f32;
+                self.input_vel = i16::from_le_bytes(vel_ff) as f32 * 0.001;
+                self.input_torque = i16::from_le_bytes(torque_ff) as f32 * 0.001;
+            }
+            WriteComm::SetInputVelocity => {
+                self.input_vel = low_f32;
+                self.input_torque = high_f32;
+            }
+            WriteComm::SetInputTorque => self.input_torque = low_f32,
+            WriteComm::SetLimits => {
+                self.vel_limit = low_f32;
+                self.current_limit = high_f32;
+            }
+            WriteComm::StartAnticogging => {}
+            WriteComm::SetTrajVelocityLim => self.traj_vel_limit = low_f32,
+            WriteComm::SetTrajAccelLim => {
+                self.traj_accel_limit = low_f32;
+                self.traj_decel_limit = high_f32;
+            }
+            WriteComm::SetTrajInertia => self.traj_inertia = low_f32,
+            WriteComm::RebootODrive => {
+                // Action 1 saves the configuration before rebooting
+                let saved_node_id = match data[0] {
+                    1 if params.saves_on_reboot => self.node_id,
+                    _ => self.saved_node_id,
+                };
+                *self = Self::new(saved_node_id, params);
+                self.reboot_remaining = params.reboot_time;
+            }
+            WriteComm::ClearErrors => {
+                self.axis_error = 0;
+                self.motor_error = 0;
+                self.encoder_error = 0;
+                self.sensorless_error = 0;
+            }
+            WriteComm::SetLinearCount => {
+                self.position = i32::from_le_bytes(low) as f32 / params.encoder_cpr as f32;
+                self.input_pos = self.position;
+                self.traj_pos = self.position;
+            }
+            WriteComm::SetPositionGain => self.pos_gain = low_f32,
+            WriteComm::SetVelocityGain => {
+                self.vel_gain = low_f32;
+                self.vel_integrator_gain = high_f32;
+            }
+        }
+    }
+
+    /// Builds the data that this axis would respond with to a `Read` command
+    pub fn read_data(&self, cmd: ReadComm, params: &SimulationParams) -> [u8; 8] {
+        let floats = |a: f32, b: f32| ResponseManip::combine_32(a.to_le_bytes(), b.to_le_bytes());
+
+        match cmd {
+            ReadComm::GetHeartbeat => {
+                let mut data = ResponseManip::combine_32(self.axis_error.to_le_bytes(), [0; 4]);
+                data[4] = self.state.clone() as u8;
+                data[7] = self.trajectory_done() as u8;
+                data
+            }
+            ReadComm::MotorError => self.motor_error.to_le_bytes(),
+            ReadComm::EncoderError => ResponseManip::combine_32(self.encoder_error.to_le_bytes(), [0; 4]),
+            ReadComm::SensorlessError => ResponseManip::combine_32(self.sensorless_error.to_le_bytes(), [0; 4]),
+            ReadComm::GetEncoderEstimates => floats(self.position, self.velocity),
+            ReadComm::GetEncoderCount => {
+                let shadow_count = (self.position * params.encoder_cpr as f32).floor() as i32;
+                let cpr_count = shadow_count.rem_euclid(params.encoder_cpr);
+                ResponseManip::combine_32(shadow_count.to_le_bytes(), cpr_count.to_le_bytes())
+            }
+            ReadComm::GetIQ => floats(self.iq_setpoint, self.iq_measured),
+            ReadComm::GetTemperature => floats(self.inverter_temperature, self.motor_temperature),
+            ReadComm::GetVBusVoltage => floats(self.bus_voltage, self.bus_current),
+        }
+    }
+
+    /// Returns the frames this axis broadcasts on its own after `dt` has passed
+    fn cyclic_messages(&mut self, dt: Duration, params: &SimulationParams) -> Vec<CANResponse> {
+        let mut messages = Vec::new();
+        if self.is_rebooting() {
+            return messages;
+        }
+
+        let cyclic = [
+            (params.heartbeat_interval, &mut self.since_heartbeat, ReadComm::GetHeartbeat),
+            (params.encoder_interval, &mut self.since_encoder, ReadComm::GetEncoderEstimates),
+        ];
+
+        let mut due = Vec::new();
+        for (interval, elapsed, cmd) in cyclic {
+            let interval = match interval {
+                Some(interval) if !interval.is_zero() => interval,
+                _ => continue,
+            };
+
+            *elapsed += dt;
+            if *elapsed >= interval {
+                // Only the latest message matters if we fell behind by several intervals
+                *elapsed = Duration::from_nanos((elapsed.as_nanos() % interval.as_nanos()) as u64);
+                due.push(cmd);
+            }
+        }
+
+        for cmd in due {
+            messages.push(CANResponse {
+                axis: self.node_id,
+                cmd: ODriveCommand::Read(cmd),
+                data: self.read_data(cmd, params),
+            });
+        }
+        messages
+    }
+}
+
+struct SimulatedBus {
+    params: SimulationParams,
+    nodes: Vec<SimulatedODrive>,
+    received: VecDeque<CANFrame>,
+    last_update: Instant,
+}
+
+impl SimulatedBus {
+    /// Advances every node up to the current time and queues their cyclic messages
+    fn update(&mut self) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_update);
+        self.last_update = now;
+
+        let mut cyclic = Vec::new();
+        for node in self.nodes.iter_mut() {
+            node.step(dt, &self.params);
+            cyclic.extend(node.cyclic_messages(dt, &self.params));
+        }
+        for message in cyclic {
+            self.queue(message);
+        }
+    }
+
+    fn queue(&mut self, response: CANResponse) {
+        if self.received.len() >= RECEIVE_BUFFER_SIZE {
+            self.received.pop_front();
+        }
+        self.received.push_back(response.to_can(false));
+    }
+}
+
+/// A [`CanTransport`] that simulates a bus of ODrives, one [`SimulatedODrive`] per axis.
+///
+/// Each axis keeps track of its own state, control and input mode, integrates its position
+/// and velocity under the setpoints it is sent and models its bus voltage, current and
+/// temperatures, so every `Read` request is answered with consistent data. Axes also
+/// broadcast their heartbeat and encoder estimates at the rates given in [`SimulationParams`].
+///
+/// The simulator is a cheap handle that can be cloned before passing it to
+/// [`CANProxy::with_transport()`](crate::canproxy::CANProxy::with_transport) to inspect the
+/// simulated axes while the proxy is running.
+///
+/// # Example
+/// ```
+/// use rustodrive::canproxy::CANProxy;
+/// use rustodrive::simulator::ODriveSimulator;
+///
+/// let simulator = ODriveSimulator::new(&[0, 1, 2, 3]);
+/// let mut can_proxy = CANProxy::with_transport(simulator.clone());
+/// can_proxy.register_rw("thread 1", |can_rw| {}).unwrap();
+///
+/// assert_eq!(simulator.node(2).unwrap().position, 0.0);
+/// ```
+#[derive(Clone)]
+pub struct ODriveSimulator {
+    bus: Arc<Mutex<SimulatedBus>>,
+}
+
+impl ODriveSimulator {
+    /// Creates a simulated ODrive axis for each of the given node IDs with the default parameters
+    pub fn new(node_ids: &[u32]) -> Self {
+        Self::with_params(node_ids, SimulationParams::default())
+    }
+
+    pub fn with_params(node_ids: &[u32], params: SimulationParams) -> Self {
+        let nodes = node_ids.iter().map(|id| SimulatedODrive::new(*id, &params)).collect();
+        Self {
+            bus: Arc::new(Mutex::new(SimulatedBus {
+                params,
+                nodes,
+                received: VecDeque::new(),
+                last_update: Instant::now(),
+            })),
+        }
+    }
+
+    /// Adds another simulated axis to the bus
+    pub fn add_node(&self, node_id: u32) {
+        let mut bus = self.bus.lock().unwrap();
+        let node = SimulatedODrive::new(node_id, &bus.params);
+        bus.nodes.push(node);
+    }
+
+    /// Returns a snapshot of the simulated axis with the given node ID
+    pub fn node(&self, node_id: u32) -> Option<SimulatedODrive> {
+        let mut bus = self.bus.lock().unwrap();
+        bus.update();
+        bus.nodes.iter().find(|node| node.node_id == node_id).cloned()
+    }
+
+    /// Gives mutable access to the simulated axis with the given node ID,
+    /// for example to put it into an error state
+    pub fn with_node<R>(&self, node_id: u32, f: impl FnOnce(&mut SimulatedODrive) -> R) -> Option<R> {
+        let mut bus = self.bus.lock().unwrap();
+        bus.update();
+        bus.nodes.iter_mut().find(|node| node.node_id == node_id).map(f)
+    }
+}
+
+impl CanTransport for ODriveSimulator {
+    fn open(_ifname: &str) -> io::Result<Self> {
+        Ok(Self::new(&[]))
+    }
+
+    fn write_frame(&self, frame: &CANFrame) -> io::Result<()> {
+        let mut bus = self.bus.lock().unwrap();
+        bus.update();
+
+        let request = CANRequest::from_can(frame).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let SimulatedBus { params, nodes, .. } = &mut *bus;
+
+        // Every node with the ID handles the frame, just like a real bus would
+        // if two ODrives were accidentally given the same ID
+        let mut responses = Vec::new();
+        for node in nodes.iter_mut().filter(|node| node.node_id == request.axis && !node.is_rebooting()) {
+            node.feed_watchdog();
+            match request.cmd {
+                ODriveCommand::Read(cmd) => responses.push(CANResponse {
+                    axis: node.node_id,
+                    cmd: request.cmd,
+                    data: node.read_data(cmd, params),
+                }),
+                ODriveCommand::Write(cmd) => node.handle_write(cmd, request.data, params),
+            }
+        }
+
+        for response in responses {
+            bus.queue(response);
+        }
+        Ok(())
+    }
+
+    fn read_frame(&self) -> io::Result<CANFrame> {
+        let mut bus = self.bus.lock().unwrap();
+        bus.update();
+
+        match bus.received.pop_front() {
+            Some(frame) => Ok(frame),
+            None => Err(io::Error::new(io::ErrorKind::WouldBlock, "no messages available")),
+        }
+    }
+
+    fn set_read_timeout(&self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_write_timeout(&self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, time::Duration};
+
+    use crate::{
+        axis::Axis,
+        canproxy::CANProxy,
+        casts::{EncoderEstimates, Heartbeat},
+        error::AxisError,
+        odrivegroup::ODriveGroup,
+        response::Success,
+        state::{AxisState, ControlMode, InputMode, WriteComm},
+        tests::wait_for_msgs,
+        utils::ResultAll,
+    };
+
+    use super::{ODriveSimulator, SimulatedODrive, SimulationParams};
+
+    fn closed_loop_axis(params: &SimulationParams) -> SimulatedODrive {
+        let mut node = SimulatedODrive::new(0, params);
+        node.request_state(AxisState::FullCalibrationSequence, params);
+        node.step(params.calibration_time * 2, params);
+        assert_eq!(node.state, AxisState::Idle);
+
+        node.request_state(AxisState::ClosedLoop, params);
+        assert_eq!(node.state, AxisState::ClosedLoop);
+        node
+    }
+
+    #[test]
+    fn test_closed_loop_requires_calibration() {
+        let params = SimulationParams::default();
+        let mut node = SimulatedODrive::new(0, &params);
+
+        node.request_state(AxisState::ClosedLoop, &params);
+        assert_eq!(node.state, AxisState::Idle);
+        assert_eq!(node.axis_error, AxisError::MissingEstimate as u32);
+    }
+
+    #[test]
+    fn test_position_control() {
+        let params = SimulationParams::default();
+        let mut node = closed_loop_axis(&params);
+
+        let set_pos = Axis::new(0).motor.set_input_pos(1.5);
+        node.handle_write(WriteComm::SetInputPosition, set_pos.data, &params);
+        node.step(Duration::from_secs(3), &params);
+
+        assert!((node.position - 1.5).abs() < 0.01, "position was {}", node.position);
+        assert!(node.velocity.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_velocity_control_heats_motor() {
+        let params = SimulationParams::default();
+        let mut node = closed_loop_axis(&params);
+
+        let axis = Axis::new(0);
+        let mode = axis.motor.set_control_mode(ControlMode::VelocityControl, InputMode::Passthrough);
+        node.handle_write(WriteComm::SetControllerMode, mode.data, &params);
+        node.handle_write(WriteComm::SetInputVelocity, axis.motor.set_input_vel(1.0).data, &params);
+        node.step(Duration::from_secs(2), &params);
+
+        assert!((node.velocity - 1.0).abs() < 0.01, "velocity was {}", node.velocity);
+        assert!(node.position > 1.0);
+        assert!(node.iq_measured > 0.0);
+        assert!(node.bus_current > 0.0);
+        assert!(node.bus_voltage < params.supply_voltage);
+        assert!(node.motor_temperature > params.ambient_temperature);
+    }
+
+    #[test]
+    fn test_trap_traj_respects_limits() {
+        let params = SimulationParams::default();
+        let mut node = closed_loop_axis(&params);
+        let axis = Axis::new(0);
+
+        let mode = axis.motor.set_control_mode(ControlMode::PositionControl, InputMode::TrapTraj);
+        node.handle_write(WriteComm::SetControllerMode, mode.data, &params);
+        node.handle_write(WriteComm::SetInputPosition, axis.motor.set_input_pos(1.0).data, &params);
+
+        let mut max_traj_vel: f32 = 0.0;
+        for _ in 0..5000 {
+            node.step(Duration::from_millis(1), &params);
+            max_traj_vel = max_traj_vel.max(node.traj_vel.abs());
+        }
+
+        assert!(max_traj_vel <= node.traj_vel_limit + 1e-4);
+        assert!(node.trajectory_done());
+        assert!((node.position - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    /// The simulator answers requests made through the CANProxy with data that can be decoded
+    fn test_simulated_bus() {
+        let simulator = ODriveSimulator::new(&[0, 1]);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+            let heartbeats: Vec<Success<Heartbeat>> = odrives.all_axes(|ax| ax.get_heartbeat()).unwrap_all();
+            let estimates: Vec<Success<EncoderEstimates>> = odrives.all_axes(|ax| ax.encoder.get_estimates()).unwrap_all();
+            send.send((heartbeats, estimates)).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        let (heartbeats, estimates) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        for heartbeat in heartbeats {
+            assert_eq!(heartbeat.data.current_state, AxisState::Idle);
+            assert_eq!(heartbeat.data.axis_error, AxisError::NoError);
+        }
+        for estimate in estimates {
+            assert_eq!(estimate.data.position, 0.0);
+        }
+        assert!(simulator.node(1).is_some());
+        assert!(simulator.node(2).is_none());
+    }
+}
@@ -0,0 +1,334 @@
+use std::{
+    fmt, thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    canframe::CANRequest,
+    response::{ErrorResponse, ODriveError},
+    threads::{PendingRequest, ReadWriteCANThread},
+    Error,
+};
+
+/// How long before a tick the loop stops sleeping and spins instead, because `thread::sleep`
+/// can overshoot by more than the jitter a control loop tolerates
+const SPIN_MARGIN: Duration = Duration::from_micros(200);
+
+/// Configures [`ODriveGroup::stream()`](crate::odrivegroup::ODriveGroup::stream)
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamConfig {
+    /// Time between two calls of the closure. Streams with a period of zero are rejected
+    pub period: Duration,
+    /// Spin instead of sleeping for the last moments before every tick. This gives much
+    /// lower jitter, but keeps a CPU core busy for up to 200µs per tick
+    pub spin: bool,
+}
+
+impl StreamConfig {
+    /// Calls the closure `frequency` times per second. This returns [`Error::InvalidPeriod`]
+    /// unless the frequency is finite, positive and gives a period of at least a nanosecond
+    pub fn with_frequency(frequency: f32) -> Result<Self, Error> {
+        match Duration::try_from_secs_f32(1.0 / frequency) {
+            Ok(period) if frequency.is_finite() && !period.is_zero() => Ok(Self { period, spin: true }),
+            _ => Err(Error::InvalidPeriod),
+        }
+    }
+}
+
+/// Passed to the closure on every tick of the stream
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamTick {
+    /// Number of the tick, starting at 0. Ticks that were skipped because of an overrun are not counted
+    pub cycle: u64,
+    /// When the tick was scheduled, relative to the start of the stream. Setpoints should be
+    /// computed for this time rather than the current time, so jitter does not distort them
+    pub time: Duration,
+    /// How much later than scheduled the tick started
+    pub late_by: Duration,
+}
+
+/// Minimum, maximum and mean of a series of durations
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct DurationStats {
+    pub samples: u64,
+    pub min: Duration,
+    pub max: Duration,
+    total: Duration,
+}
+
+impl DurationStats {
+    pub fn record(&mut self, sample: Duration) {
+        self.min = match self.samples {
+            0 => sample,
+            _ => self.min.min(sample),
+        };
+        self.max = self.max.max(sample);
+        self.total += sample;
+        self.samples += 1;
+    }
+
+    /// Returns zero if nothing was recorded
+    pub fn mean(&self) -> Duration {
+        match self.samples {
+            0 => Duration::ZERO,
+            samples => self.total / samples as u32,
+        }
+    }
+}
+
+impl fmt::Display for DurationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mean {:?}, min {:?}, max {:?}", self.mean(), self.min, self.max)
+    }
+}
+
+/// Timing of a stream. Printing it gives a short summary
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct StreamStats {
+    pub cycles: u64,
+    /// Ticks that started a full period or more late, e.g. because the closure took too long.
+    /// The stream skips the ticks it missed instead of trying to catch up
+    pub overruns: u64,
+    /// How much later than scheduled each tick started
+    pub jitter: DurationStats,
+    /// Time between the starts of consecutive ticks
+    pub period: DurationStats,
+    /// Time from handing a setpoint to the proxy until it was written to the CAN bus
+    pub send_latency: DurationStats,
+    /// Setpoints that exceeded the soft limits of their axis and were not sent
+    pub rejected: u64,
+    /// Setpoints the proxy failed to write to the CAN bus
+    pub send_errors: u64,
+    /// The most recent setpoint that was rejected or could not be sent
+    pub last_error: Option<ErrorResponse>,
+}
+
+impl fmt::Display for StreamStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} cycles, {} overruns, {} rejected, {} send errors",
+            self.cycles, self.overruns, self.rejected, self.send_errors
+        )?;
+        writeln!(f, "  period: {}", self.period)?;
+        writeln!(f, "  jitter: {}", self.jitter)?;
+        writeln!(f, "  send latency: {}", self.send_latency)
+    }
+}
+
+/// Calls `f` every period until it returns `None`, and sends the setpoints it returns without
+/// waiting for them. `check` is applied to every setpoint before it is sent. The time until the
+/// next tick is spent collecting the responses, which only confirm the frames were written.
+pub(crate) fn stream<C, F>(can: &ReadWriteCANThread, config: &StreamConfig, check: C, mut f: F) -> Result<StreamStats, Error>
+where
+    C: Fn(CANRequest) -> Result<CANRequest, ErrorResponse>,
+    F: FnMut(&StreamTick) -> Option<Vec<CANRequest>>,
+{
+    // The next tick would never be scheduled after the current time
+    if config.period.is_zero() {
+        return Err(Error::InvalidPeriod);
+    }
+
+    let mut stats = StreamStats::default();
+    let mut pending: Vec<(Instant, PendingRequest)> = vec![];
+    let start = Instant::now();
+    let mut scheduled = start;
+    let mut last_tick: Option<Instant> = None;
+
+    loop {
+        let now = Instant::now();
+        let late_by = now - scheduled;
+        stats.jitter.record(late_by);
+        if let Some(last_tick) = last_tick {
+            stats.period.record(now - last_tick);
+        }
+        last_tick = Some(now);
+
+        let tick = StreamTick { cycle: stats.cycles, time: scheduled - start, late_by };
+        let setpoints = match f(&tick) {
+            Some(setpoints) => setpoints,
+            None => break,
+        };
+        stats.cycles += 1;
+
+        for setpoint in setpoints {
+            match check(setpoint) {
+                Ok(setpoint) => pending.push((Instant::now(), can.submit(setpoint))),
+                Err(rejected) => {
+                    stats.rejected += 1;
+                    stats.last_error = Some(rejected);
+                }
+            }
+        }
+
+        scheduled += config.period;
+        if Instant::now() >= scheduled {
+            stats.overruns += 1;
+            while Instant::now() >= scheduled {
+                scheduled += config.period;
+            }
+        }
+
+        collect_responses(&mut pending, &mut stats, Some(scheduled));
+        wait_until(scheduled, config.spin);
+    }
+
+    collect_responses(&mut pending, &mut stats, None);
+    Ok(stats)
+}
+
+/// Records the responses that arrive before the deadline, or all of them if there is none
+fn collect_responses(pending: &mut Vec<(Instant, PendingRequest)>, stats: &mut StreamStats, deadline: Option<Instant>) {
+    // The frames are written in the order they were submitted, so the first one is always
+    // the next to be answered
+    while let Some((sent, request)) = pending.first_mut() {
+        let sent = *sent;
+        let response = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now()).saturating_sub(SPIN_MARGIN);
+                match request.wait_timeout(remaining) {
+                    Some(response) => response,
+                    None => return,
+                }
+            }
+            None => request.wait_timeout(Duration::from_secs(1)).unwrap_or_else(|| {
+                // Never block forever on a proxy that has stopped
+                Err(ErrorResponse { request: request.request(), err: ODriveError::FailedToSend })
+            }),
+        };
+        pending.remove(0);
+
+        stats.send_latency.record(sent.elapsed());
+        if let Err(error) = response {
+            stats.send_errors += 1;
+            stats.last_error = Some(error);
+        }
+    }
+}
+
+fn wait_until(deadline: Instant, spin: bool) {
+    let now = Instant::now();
+    if deadline <= now {
+        return;
+    }
+
+    match spin {
+        true => {
+            thread::sleep((deadline - now).saturating_sub(SPIN_MARGIN));
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+        false => thread::sleep(deadline - now),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, thread, time::Duration};
+
+    use crate::{
+        axis::Axis,
+        canproxy::CANProxy,
+        limits::SoftLimits,
+        odrivegroup::ODriveGroup,
+        simulator::ODriveSimulator,
+        tests::wait_for_msgs,
+        Error,
+    };
+
+    use super::StreamConfig;
+
+    #[test]
+    fn test_stream_setpoints() {
+        let simulator = ODriveSimulator::new(&[0, 1, 2]);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+        let config = StreamConfig::with_frequency(200.0).unwrap();
+        let period = config.period;
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let mut odrives = ODriveGroup::new(can_rw, &[0, 1, 2]);
+            odrives.set_limits(&2, SoftLimits { position: Some((-0.105, 0.105)), ..Default::default() }).unwrap();
+
+            let mut times = vec![];
+            let stats = odrives.stream(&config, |tick| {
+                times.push(tick.time);
+                match tick.cycle {
+                    50 => None,
+                    cycle => Some((0..3).map(|id| Axis::new(id).motor.set_input_pos(cycle as f32 * 0.01)).collect()),
+                }
+            }).unwrap();
+            send.send((stats, times)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let (stats, times) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(stats.cycles, 50);
+        // Ticks are scheduled a period apart, except where an overrun on a busy machine skipped some
+        assert_eq!(times[0], Duration::ZERO);
+        assert!(times.windows(2).all(|pair| pair[1] > pair[0] && (pair[1] - pair[0]).as_nanos() % period.as_nanos() == 0), "{:?}", times);
+        assert_eq!(times.len() as u64, stats.cycles + 1);
+        assert_eq!(stats.period.samples, stats.cycles);
+        assert_eq!(stats.send_errors, 0);
+        // Axis 2 goes past its limit after 10 ticks
+        assert_eq!(stats.rejected, 39);
+        assert_eq!(stats.send_latency.samples, 50 * 3 - 39);
+
+        assert!((simulator.node(0).unwrap().input_pos - 0.49).abs() < 1e-6);
+        assert!((simulator.node(2).unwrap().input_pos - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_stream_overrun() {
+        let mut can_proxy = CANProxy::with_transport(ODriveSimulator::new(&[0]));
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0]);
+            let config = StreamConfig { period: Duration::from_millis(10), spin: false };
+            let stats = odrives.stream(&config, |tick| {
+                if tick.cycle == 3 {
+                    thread::sleep(Duration::from_millis(25));
+                }
+                (tick.cycle < 10).then(Vec::new)
+            }).unwrap();
+            send.send(stats).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let stats = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(stats.cycles, 10);
+        assert!(stats.overruns >= 1);
+        assert!(stats.period.max >= Duration::from_millis(25));
+        assert_eq!(stats.send_latency.samples, 0);
+    }
+
+    #[test]
+    fn test_invalid_period() {
+        for frequency in [0.0, -10.0, f32::NAN, f32::INFINITY, 1e-40, 1e12] {
+            assert!(matches!(StreamConfig::with_frequency(frequency), Err(Error::InvalidPeriod)), "{}", frequency);
+        }
+
+        let mut can_proxy = CANProxy::with_transport(ODriveSimulator::new(&[0]));
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0]);
+            let config = StreamConfig { period: Duration::ZERO, spin: false };
+            let mut ticks = 0;
+            let stats = odrives.stream(&config, |_| {
+                ticks += 1;
+                None
+            });
+            send.send((stats, ticks)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let (stats, ticks) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert!(matches!(stats, Err(Error::InvalidPeriod)));
+        assert_eq!(ticks, 0);
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    axis::{Axis, AxisID},
+    canframe::{CANResponse, FrameFilter},
+    error::{AxisError, EncoderError, MotorError},
+    state::{AxisState, ODriveCommand, ReadComm},
+    threads::{CANThreadCommunicator, PendingRequest, ReadOnlyCANThread},
+};
+
+/// The longest the supervisor waits for a heartbeat before checking on everything else
+const IDLE_WAIT: Duration = Duration::from_millis(5);
+
+/// What the supervisor does to every axis when one of them faults
+#[derive(Clone, Debug, PartialEq)]
+pub enum SafeAction {
+    /// Send `EStop`, which disarms the axes and sets `AxisError::EStopRequested` on them
+    EStop,
+    /// Request the given state, e.g. `Idle` to let the axes go limp without an error
+    State(AxisState),
+}
+
+/// Configures the supervisor started with
+/// [`CANProxy::register_supervisor()`](crate::canproxy::CANProxy::register_supervisor)
+#[derive(Clone, Debug, PartialEq)]
+pub struct SupervisorConfig {
+    pub axes: Vec<AxisID>,
+    /// How often the motor and encoder errors of every axis are read. The axis errors are
+    /// taken from the heartbeats instead
+    pub poll_interval: Duration,
+    /// How long an axis may go without sending a heartbeat before it counts as a fault.
+    /// `None` does not check for missing heartbeats
+    pub heartbeat_timeout: Option<Duration>,
+    pub action: SafeAction,
+}
+
+impl SupervisorConfig {
+    /// E-stops all the axes if any of them reports an error or stops sending heartbeats
+    /// for 500ms. Motor and encoder errors are read every 20ms
+    pub fn new(axes: &[AxisID]) -> Self {
+        Self {
+            axes: axes.to_vec(),
+            poll_interval: Duration::from_millis(20),
+            heartbeat_timeout: Some(Duration::from_millis(500)),
+            action: SafeAction::EStop,
+        }
+    }
+}
+
+/// The fault that made the supervisor trip
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    Axis { axis: AxisID, errors: Vec<AxisError> },
+    Motor { axis: AxisID, errors: Vec<MotorError> },
+    Encoder { axis: AxisID, errors: Vec<EncoderError> },
+    HeartbeatLost { axis: AxisID },
+}
+
+/// The application's side of the supervisor
+pub struct SupervisorHandle {
+    tripped: Arc<AtomicBool>,
+    faults: Receiver<Fault>,
+}
+
+impl SupervisorHandle {
+    pub(crate) fn new(tripped: Arc<AtomicBool>, faults: Receiver<Fault>) -> Self {
+        Self { tripped, faults }
+    }
+
+    /// Returns true if the supervisor stopped the axes because of a fault
+    pub fn is_tripped(&self) -> bool {
+        self.tripped.load(Ordering::SeqCst)
+    }
+
+    /// Arms the supervisor again after it tripped. The errors on the axes, including the
+    /// ones set by `EStop`, have to be cleared first or it trips again right away
+    pub fn reset(&self) {
+        self.tripped.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns the receiver for the fault that made the supervisor trip,
+    /// one for every time it trips
+    pub fn faults(&self) -> &Receiver<Fault> {
+        &self.faults
+    }
+}
+
+/// Watches the axes until the proxy stops. Called on the supervisor's thread
+pub(crate) fn run(can: ReadOnlyCANThread, config: SupervisorConfig, tripped: Arc<AtomicBool>, faults: Sender<Fault>) {
+    let heartbeats = CANThreadCommunicator::subscribe(&can, FrameFilter::cmd(ReadComm::GetHeartbeat));
+    let mut last_heard: BTreeMap<AxisID, Instant> = config.axes.iter().map(|id| (*id, Instant::now())).collect();
+    let mut pending: Vec<(AxisID, PendingRequest)> = vec![];
+    let mut next_poll = Instant::now();
+
+    while can.is_alive() {
+        let mut fault = None;
+        if let Ok(heartbeat) = heartbeats.recv_timeout(IDLE_WAIT) {
+            fault = check_heartbeat(&heartbeat, &mut last_heard);
+        }
+
+        if Instant::now() >= next_poll {
+            next_poll = Instant::now() + config.poll_interval;
+            pending.retain_mut(|(axis, request)| match request.poll() {
+                None => true,
+                Some(Ok(response)) => {
+                    let (_, response) = response.body();
+                    if fault.is_none() {
+                        fault = check_error_read(*axis, &response);
+                    }
+                    false
+                }
+                // A missing answer shows up as a lost heartbeat if the axis is really gone
+                Some(Err(_)) => false,
+            });
+
+            // Only one read of each kind is in flight per axis, even if the bus is slow
+            if pending.is_empty() {
+                for axis in &config.axes {
+                    pending.push((*axis, can.submit(*axis as u32, ReadComm::MotorError)));
+                    pending.push((*axis, can.submit(*axis as u32, ReadComm::EncoderError)));
+                }
+            }
+        }
+
+        if let Some(timeout) = config.heartbeat_timeout {
+            if let Some((axis, _)) = last_heard.iter().find(|(_, heard)| heard.elapsed() > timeout) {
+                fault = fault.or(Some(Fault::HeartbeatLost { axis: *axis }));
+            }
+        }
+
+        match fault {
+            Some(fault) if !tripped.load(Ordering::SeqCst) => {
+                tripped.store(true, Ordering::SeqCst);
+                stop_axes(&can, &config);
+                // The application may not be listening, but the axes were stopped either way
+                faults.send(fault).unwrap_or(());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn stop_axes(can: &ReadOnlyCANThread, config: &SupervisorConfig) {
+    let requests = config
+        .axes
+        .iter()
+        .map(|id| {
+            let axis = Axis::new(*id);
+            match &config.action {
+                SafeAction::EStop => axis.estop(),
+                SafeAction::State(state) => axis.set_state(state.clone()),
+            }
+        })
+        .collect();
+    CANThreadCommunicator::request_many(can, requests, None);
+}
+
+fn check_heartbeat(heartbeat: &CANResponse, last_heard: &mut BTreeMap<AxisID, Instant>) -> Option<Fault> {
+    let axis = heartbeat.axis as AxisID;
+    *last_heard.get_mut(&axis)? = Instant::now();
+
+    let error_bits = u32::from_le_bytes(heartbeat.data[0..4].try_into().unwrap());
+    match error_bits {
+        0 => None,
+        bits => Some(Fault::Axis { axis, errors: AxisError::from_bits(bits) }),
+    }
+}
+
+fn check_error_read(axis: AxisID, response: &CANResponse) -> Option<Fault> {
+    match response.cmd {
+        ODriveCommand::Read(ReadComm::MotorError) => match u64::from_le_bytes(response.data) {
+            0 => None,
+            bits => Some(Fault::Motor { axis, errors: MotorError::from_bits(bits) }),
+        },
+        _ => match u32::from_le_bytes(response.data[0..4].try_into().unwrap()) {
+            0 => None,
+            bits => Some(Fault::Encoder { axis, errors: EncoderError::from_bits(bits) }),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::{
+        canproxy::CANProxy,
+        error::{AxisError, MotorError},
+        simulator::{ODriveSimulator, SimulationParams},
+        state::AxisState,
+    };
+
+    use super::{Fault, SafeAction, SupervisorConfig};
+
+    fn closed_loop_simulator(node_ids: &[u32]) -> ODriveSimulator {
+        let params = SimulationParams {
+            precalibrated: true,
+            heartbeat_interval: Some(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let simulator = ODriveSimulator::with_params(node_ids, params);
+        for id in node_ids {
+            simulator.with_node(*id, |node| node.state = AxisState::ClosedLoop);
+        }
+        simulator
+    }
+
+    #[test]
+    fn test_estop_on_motor_error() {
+        let simulator = closed_loop_simulator(&[0, 1, 2]);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+        let supervisor = can_proxy.register_supervisor("supervisor", SupervisorConfig::new(&[0, 1, 2])).unwrap();
+        let stop_proxy = can_proxy.begin();
+
+        thread::sleep(Duration::from_millis(100));
+        assert!(!supervisor.is_tripped());
+        simulator.with_node(1, |node| node.motor_error = MotorError::CurrentLimitViolation as u64);
+
+        let fault = supervisor.faults().recv_timeout(Duration::from_secs(1)).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        stop_proxy().unwrap();
+
+        assert_eq!(fault, Fault::Motor { axis: 1, errors: vec![MotorError::CurrentLimitViolation] });
+        assert!(supervisor.is_tripped());
+        // The e-stop itself sets errors, but the supervisor only reports the first fault
+        assert!(supervisor.faults().try_recv().is_err());
+        for id in [0, 1, 2] {
+            let node = simulator.node(id).unwrap();
+            assert_eq!(node.state, AxisState::Idle);
+            assert_eq!(node.axis_error & AxisError::EStopRequested as u32, AxisError::EStopRequested as u32);
+        }
+    }
+
+    #[test]
+    fn test_safe_state_on_lost_heartbeat() {
+        let simulator = closed_loop_simulator(&[0, 1]);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+        let config = SupervisorConfig {
+            heartbeat_timeout: Some(Duration::from_millis(150)),
+            action: SafeAction::State(AxisState::Idle),
+            ..SupervisorConfig::new(&[0, 1, 5])
+        };
+        let supervisor = can_proxy.register_supervisor("supervisor", config).unwrap();
+        let stop_proxy = can_proxy.begin();
+
+        // Axis 5 is not on the bus
+        let fault = supervisor.faults().recv_timeout(Duration::from_secs(1)).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        stop_proxy().unwrap();
+
+        assert_eq!(fault, Fault::HeartbeatLost { axis: 5 });
+        for id in [0, 1] {
+            let node = simulator.node(id).unwrap();
+            assert_eq!(node.state, AxisState::Idle);
+            assert_eq!(node.axis_error, 0);
+        }
+    }
+}
@@ -1,18 +1,21 @@
 use std::{sync::{
     atomic::{AtomicBool, Ordering},
-    mpsc::{Receiver, Sender},
+    mpsc::{channel, Receiver, Sender},
     Arc,
-}, collections::{HashMap, HashSet}};
+}, time::Duration};
 
 use crate::{
     state::{ODriveCommand},
-    canframe::{ODriveCANFrame, ThreadCANFrame, CANRequest}, response::ODriveResponse, state::ReadComm,
+    canframe::{BusEvent, ThreadCANFrame, CANRequest, CANResponse, FrameFilter, Reply, ThreadMessage}, response::{ErrorResponse, ODriveResponse}, state::ReadComm,
+    axis::AxisID,
+    discovery::{self, DiscoveredNode, Inventory, DEFAULT_DISCOVERY_WINDOW},
+    provisioning, Error,
 };
 
 pub(crate) trait CANThreadCommunicator {
     fn new(
         thread_name: &'static str,
-        requester: Sender<ThreadCANFrame>,
+        requester: Sender<ThreadMessage>,
         receiver: Receiver<ODriveResponse>,
         threads_alive: Arc<AtomicBool>,
     ) -> Self;
@@ -26,27 +29,63 @@ pub(crate) trait CANThreadCommunicator {
         let can_recv = self.get_receiver();
 
         match can_recv.recv() {
-            Ok(response) => return response,
+            Ok(response) => response,
             Err(error) => panic!("Thread {} disconnected: \n{}", self.thread_name(), error),
         }
     }
 
-    fn thread_to_proxy(&self, frame: CANRequest) {
+    fn thread_to_proxy(&self, frame: CANRequest, timeout: Option<Duration>) {
         let can_send = self.get_requester();
 
         // take the message and send it over the channel
-        match can_send.send(ThreadCANFrame {
-            thread_name: self.thread_name(),
-            body: frame,
-        }) {
+        match can_send.send(ThreadMessage::Request(ThreadCANFrame::new(self.thread_name(), frame, timeout), Reply::Thread)) {
             Ok(()) => {}
             Err(error) => panic!("Lost connection to CANManager thread: \n{}", error),
         }
     }
 
+    /// This sends a CANFrame without waiting for the response. The response is
+    /// delivered to the returned handle instead of the thread's response channel
+    fn submit(&self, msg: CANRequest, timeout: Option<Duration>) -> PendingRequest {
+        let (sender, receiver) = channel();
+        let frame = ThreadCANFrame::new(self.thread_name(), msg, timeout);
+        let id = frame.id;
+
+        match self.get_requester().send(ThreadMessage::Request(frame, Reply::Channel(sender))) {
+            Ok(()) => PendingRequest {
+                request: msg,
+                id,
+                requester: self.get_requester().clone(),
+                receiver,
+                response: None,
+            },
+            Err(error) => panic!("Lost connection to CANManager thread: \n{}", error),
+        }
+    }
+
+    /// This asks the proxy to forward every received frame matching the filter and
+    /// returns the receiving end. Dropping the receiver ends the subscription.
+    fn subscribe(&self, filter: FrameFilter) -> Receiver<CANResponse> {
+        let (sender, receiver) = channel();
+        match self.get_requester().send(ThreadMessage::Subscribe { filter, sender }) {
+            Ok(()) => receiver,
+            Err(error) => panic!("Lost connection to CANManager thread: \n{}", error),
+        }
+    }
+
+    /// Asks the proxy for a copy of every frame it writes to or reads from the CAN bus.
+    /// The proxy stops sending events once the returned receiver is dropped
+    fn monitor(&self) -> Receiver<BusEvent> {
+        let (sender, receiver) = channel();
+        match self.get_requester().send(ThreadMessage::Monitor(sender)) {
+            Ok(()) => receiver,
+            Err(error) => panic!("Lost connection to CANManager thread: \n{}", error),
+        }
+    }
+
     /// This returns the Sender portion of the communication channel to the CANManager
     /// This thread ---> CANManager (aka sends requests)
-    fn get_requester(&self) -> &Sender<ThreadCANFrame>;
+    fn get_requester(&self) -> &Sender<ThreadMessage>;
 
     /// This returns the receive portion of the communication channel from the CANManager
     /// This thread <--- CANManager (aka receives requests)
@@ -54,60 +93,113 @@ pub(crate) trait CANThreadCommunicator {
 
     /// This sends all the messages specified and waits until responses have been
     /// received for all of them. Responses are returned in the order they were
-    /// sent
-    fn request_many(&self, requests: Vec<CANRequest>) -> Vec<ODriveResponse> {
-        // Check if there are duplicate requests. If there are, panic
-        let requests_set = HashSet::<&CANRequest>::from_iter(requests.iter());
-        assert!(requests_set.len() == requests.len(), "Duplicate requests contained in call");
-
-        // Send off all the messages
-        let num_messages = requests.len();
-        for req in requests.iter() {
-            self.thread_to_proxy(req.to_owned());
-        }
-
-        let mut responses = Vec::new();
+    /// sent. The same request may appear more than once
+    ///
+    /// If `timeout` is `None`, the timeout configured on the `CANProxy` is used
+    fn request_many(&self, requests: Vec<CANRequest>, timeout: Option<Duration>) -> Vec<ODriveResponse> {
+        // Send off all the messages. Each one gets its own handle, so identical requests
+        // can't be confused with each other
+        let pending: Vec<PendingRequest> = requests
+            .into_iter()
+            .map(|req| self.submit(req, timeout))
+            .collect();
 
         // Wait until you have gotten all of the responses
-        while responses.len() < num_messages {
-            responses.push(self.proxy_to_thread());
-        }
+        pending.into_iter().map(PendingRequest::wait).collect()
+    }
+
+    /// This sends a CANFrame and waits for a response back
+    ///
+    /// If `timeout` is `None`, the timeout configured on the `CANProxy` is used
+    fn request(&self, msg: CANRequest, timeout: Option<Duration>) -> ODriveResponse {
+        self.thread_to_proxy(msg, timeout);
+        self.proxy_to_thread()
+    }
+}
+
+/// A handle to a request that was sent with `submit()` and may not have been responded to yet.
+///
+/// The thread can keep working while the request is in flight and check back on it later
+/// with [`PendingRequest::poll()`], block on it with [`PendingRequest::wait()`] or
+/// [`PendingRequest::wait_timeout()`], or take it back with [`PendingRequest::cancel()`].
+/// Dropping the handle discards the response once it arrives.
+///
+/// ## Example
+/// ```
+/// use rustodrive::canproxy::CANProxy;
+/// use rustodrive::state::ReadComm;
+///
+/// let mut can_proxy = CANProxy::new("can0").unwrap();
+/// can_proxy.register_ro("thread 1", |can_read| {
+///     // Ask for the next cycle's estimates while the current one is computed
+///     let mut estimates = can_read.submit(1, ReadComm::GetEncoderEstimates);
+///     // ...
+///     let response = estimates.wait();
+/// }).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct PendingRequest {
+    request: CANRequest,
+    id: u64,
+    requester: Sender<ThreadMessage>,
+    receiver: Receiver<ODriveResponse>,
+    response: Option<ODriveResponse>,
+}
 
-        // Order the responses based on the order they were sent
-        let mut responses_map = HashMap::new();
-        for resp in responses {
-            let assoc_request = match resp.clone() {
-                Ok(res_type) => res_type.request(),
-                Err(err_type) => err_type.request,
-            };
-            responses_map.insert(assoc_request, resp);
+impl PendingRequest {
+    /// The request that was sent
+    pub fn request(&self) -> CANRequest {
+        self.request
+    }
+
+    /// Returns the response if it has arrived, without blocking
+    pub fn poll(&mut self) -> Option<ODriveResponse> {
+        if self.response.is_none() {
+            self.response = self.receiver.try_recv().ok();
         }
-        let mut ordered_responses = Vec::new();
-        for req in requests.iter() {
-            ordered_responses.push(responses_map.remove(&req).unwrap());
+        self.response.clone()
+    }
+
+    /// Blocks until the response arrives for at most `timeout`. This returns `None`
+    /// if it did not arrive in time, in which case the request is still pending
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Option<ODriveResponse> {
+        if self.response.is_none() {
+            self.response = self.receiver.recv_timeout(timeout).ok();
         }
+        self.response.clone()
+    }
 
-        ordered_responses
+    /// Blocks until the response arrives
+    pub fn wait(self) -> ODriveResponse {
+        match self.response {
+            Some(response) => response,
+            None => match self.receiver.recv() {
+                Ok(response) => response,
+                Err(error) => panic!("Lost connection to CANManager thread: \n{}", error),
+            },
+        }
     }
 
-    /// This sends a CANFrame and waits for a response back
-    fn request(&self, msg: CANRequest) -> ODriveResponse {
-        self.thread_to_proxy(msg);
-        self.proxy_to_thread()
+    /// Stops the `CANProxy` from waiting for the response and retrying the request.
+    /// Cancelling a request that was already responded to does nothing
+    pub fn cancel(self) {
+        // If the proxy is gone there is nothing left to cancel
+        self.requester.send(ThreadMessage::Cancel(self.id)).unwrap_or(());
     }
 }
 
 pub struct ReadWriteCANThread {
     thread_name: &'static str,
-    requester: Sender<ThreadCANFrame>,
+    requester: Sender<ThreadMessage>,
     receiver: Receiver<ODriveResponse>,
     threads_alive: Arc<AtomicBool>,
+    send_errors: (Sender<ErrorResponse>, Receiver<ErrorResponse>),
 }
 
 impl CANThreadCommunicator for ReadWriteCANThread {
     fn new(
         thread_name: &'static str,
-        requester: Sender<ThreadCANFrame>,
+        requester: Sender<ThreadMessage>,
         receiver: Receiver<ODriveResponse>,
         threads_alive: Arc<AtomicBool>,
     ) -> Self {
@@ -116,6 +208,7 @@ impl CANThreadCommunicator for ReadWriteCANThread {
             requester,
             receiver,
             threads_alive,
+            send_errors: channel(),
         }
     }
 
@@ -123,7 +216,7 @@ impl CANThreadCommunicator for ReadWriteCANThread {
         self.thread_name
     }
 
-    fn get_requester(&self) -> &Sender<ThreadCANFrame> {
+    fn get_requester(&self) -> &Sender<ThreadMessage> {
         &self.requester
     }
 
@@ -135,18 +228,145 @@ impl CANThreadCommunicator for ReadWriteCANThread {
 impl ReadWriteCANThread {
     pub fn new(
         thread_name: &'static str,
-        requester: Sender<ThreadCANFrame>,
+        requester: Sender<ThreadMessage>,
         receiver: Receiver<ODriveResponse>,
         threads_alive: Arc<AtomicBool>,
     ) -> Self {
         CANThreadCommunicator::new(thread_name, requester, receiver, threads_alive)
     }
     pub fn request(&self, msg: CANRequest) -> ODriveResponse {
-        CANThreadCommunicator::request(self, msg)
+        CANThreadCommunicator::request(self, msg, None)
     }
 
     pub fn request_many(&self, messages: Vec<CANRequest>) -> Vec<ODriveResponse> {
-        CANThreadCommunicator::request_many(self, messages)
+        CANThreadCommunicator::request_many(self, messages, None)
+    }
+
+    /// Sends the request without blocking and returns a handle to its response
+    pub fn submit(&self, msg: CANRequest) -> PendingRequest {
+        CANThreadCommunicator::submit(self, msg, None)
+    }
+
+    /// Hands the request to the `CANProxy` and returns right away, without waiting for or
+    /// keeping track of a response. This is meant for `Write` requests sent at a high rate,
+    /// such as setpoints, where only a failure to send matters. Such failures are reported
+    /// on [`ReadWriteCANThread::send_errors()`] once the proxy gives up on the request.
+    ///
+    /// The response to a `Read` request sent this way is discarded, but it is still
+    /// reported if no response arrives.
+    ///
+    /// ## Example
+    /// ```
+    /// use rustodrive::axis::Axis;
+    /// use rustodrive::canproxy::CANProxy;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_rw("thread 1", |can_rw| {
+    ///     for step in 0..100 {
+    ///         can_rw.send(Axis::new(0).motor.set_input_pos(step as f32 * 0.01));
+    ///         // ...
+    ///     }
+    ///     for error in can_rw.send_errors().try_iter() {
+    ///         println!("failed to send {:?}", error.request);
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn send(&self, msg: CANRequest) {
+        let frame = ThreadCANFrame::new(self.thread_name, msg, None);
+        match self.requester.send(ThreadMessage::Request(frame, Reply::Errors(self.send_errors.0.clone()))) {
+            Ok(()) => {}
+            Err(error) => panic!("Lost connection to CANManager thread: \n{}", error),
+        }
+    }
+
+    /// Same as [`ReadWriteCANThread::send()`] for every request. They are written to the
+    /// CAN bus in the order they are given
+    pub fn send_many(&self, messages: Vec<CANRequest>) {
+        for msg in messages {
+            self.send(msg);
+        }
+    }
+
+    /// Returns the receiver for the errors of requests made with [`ReadWriteCANThread::send()`]
+    /// and [`ReadWriteCANThread::send_many()`], in the order the proxy gave up on them
+    pub fn send_errors(&self) -> &Receiver<ErrorResponse> {
+        &self.send_errors.1
+    }
+
+    /// Same as [`ReadWriteCANThread::request()`], but `Read` requests are answered with
+    /// [`ODriveError::NoResponse`](crate::response::ODriveError::NoResponse) if no response
+    /// arrives within `timeout` (and any retries configured on the `CANProxy`)
+    pub fn request_with_timeout(&self, msg: CANRequest, timeout: Duration) -> ODriveResponse {
+        CANThreadCommunicator::request(self, msg, Some(timeout))
+    }
+
+    /// Same as [`ReadWriteCANThread::request_many()`], but with the timeout
+    /// applied to every request individually
+    pub fn request_many_with_timeout(&self, messages: Vec<CANRequest>, timeout: Duration) -> Vec<ODriveResponse> {
+        CANThreadCommunicator::request_many(self, messages, Some(timeout))
+    }
+
+    /// Returns a receiver for every frame from the CAN bus that matches the filter,
+    /// including the ones the ODrives broadcast on their own such as heartbeats.
+    /// The subscription ends when the receiver is dropped.
+    pub fn subscribe(&self, filter: FrameFilter) -> Receiver<CANResponse> {
+        CANThreadCommunicator::subscribe(self, filter)
+    }
+
+    /// Returns a receiver for a copy of every frame sent to or received from the CAN bus
+    pub fn monitor(&self) -> Receiver<BusEvent> {
+        CANThreadCommunicator::monitor(self)
+    }
+
+    /// Listens for heartbeats for the duration of `window` and returns every axis that
+    /// sent one, along with its decoded state and error. See [`ReadOnlyCANThread::discover()`]
+    pub fn discover(&self, window: Duration) -> Inventory {
+        discovery::discover(self, window)
+    }
+
+    /// Moves the axis at the node ID `current` to the node ID `new`, so fresh boards can be
+    /// commissioned without odrivetool. This returns the axis as it was heard at its new ID.
+    ///
+    /// Node IDs above 0x3F do not fit in a CAN ID and are rejected with [`Error::NodeIdOutOfRange`].
+    /// The bus is first listened to for heartbeats to check that the axis exists and that no
+    /// other axis already uses the new ID, returning [`Error::NodeNotFound`] or
+    /// [`Error::NodeIdTaken`] before anything is sent. The axis must then send a heartbeat
+    /// with the new ID, otherwise [`Error::NodeNotFound`] is returned.
+    ///
+    /// The new ID is lost when the ODrive reboots unless `persist` is set. Persisting saves
+    /// the configuration, which **reboots the whole ODrive**, including its other axis.
+    /// The axis must then go silent and come back with the new ID, not the old one, otherwise
+    /// [`Error::NodeIdNotSaved`] is returned. Saving over CAN requires firmware v0.6 or newer.
+    ///
+    /// ```
+    /// use std::sync::mpsc::channel;
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::simulator::ODriveSimulator;
+    ///
+    /// let mut can_proxy = CANProxy::with_transport(ODriveSimulator::new(&[0]));
+    /// let (done, finished) = channel();
+    /// can_proxy.register_rw("provisioning", move |can_rw| {
+    ///     let node = can_rw.assign_node_id(0, 4, true).unwrap();
+    ///     done.send(node.axis_id).unwrap();
+    /// }).unwrap();
+    /// let stop_proxy = can_proxy.begin();
+    /// assert_eq!(finished.recv().unwrap(), 4);
+    /// stop_proxy().unwrap();
+    /// ```
+    pub fn assign_node_id(&self, current: AxisID, new: AxisID, persist: bool) -> Result<DiscoveredNode, Error> {
+        provisioning::assign_node_id(self, current, new, persist, DEFAULT_DISCOVERY_WINDOW)
+    }
+
+    /// Same as [`ReadWriteCANThread::assign_node_id()`], but listens for heartbeats for
+    /// `window` instead of [`DEFAULT_DISCOVERY_WINDOW`]
+    pub fn assign_node_id_with_window(
+        &self,
+        current: AxisID,
+        new: AxisID,
+        persist: bool,
+        window: Duration,
+    ) -> Result<DiscoveredNode, Error> {
+        provisioning::assign_node_id(self, current, new, persist, window)
     }
 
     /// This should look at the shared reference of whether the threads should be running,
@@ -157,7 +377,7 @@ impl ReadWriteCANThread {
 
 pub struct ReadOnlyCANThread {
     thread_name: &'static str,
-    requester: Sender<ThreadCANFrame>,
+    requester: Sender<ThreadMessage>,
     receiver: Receiver<ODriveResponse>,
     threads_alive: Arc<AtomicBool>,
 }
@@ -165,7 +385,7 @@ pub struct ReadOnlyCANThread {
 impl CANThreadCommunicator for ReadOnlyCANThread {
     fn new(
         thread_name: &'static str,
-        requester: Sender<ThreadCANFrame>,
+        requester: Sender<ThreadMessage>,
         receiver: Receiver<ODriveResponse>,
         threads_alive: Arc<AtomicBool>,
     ) -> Self {
@@ -181,7 +401,7 @@ impl CANThreadCommunicator for ReadOnlyCANThread {
         self.thread_name
     }
 
-    fn get_requester(&self) -> &Sender<ThreadCANFrame> {
+    fn get_requester(&self) -> &Sender<ThreadMessage> {
         &self.requester
     }
 
@@ -193,33 +413,118 @@ impl CANThreadCommunicator for ReadOnlyCANThread {
 impl ReadOnlyCANThread {
     pub fn new(
         thread_name: &'static str,
-        requester: Sender<ThreadCANFrame>,
+        requester: Sender<ThreadMessage>,
         receiver: Receiver<ODriveResponse>,
         threads_alive: Arc<AtomicBool>,
     ) -> Self {
         CANThreadCommunicator::new(thread_name, requester, receiver, threads_alive)
     }
     pub fn request(&self, axis: u32, cmd: ReadComm) -> ODriveResponse {
-        CANThreadCommunicator::request(
-            self,
-            CANRequest {
-                axis,
-                cmd: ODriveCommand::Read(cmd),
-                data: [0; 8],
-            },
-        )
+        CANThreadCommunicator::request(self, Self::read_request(axis, cmd), None)
     }
 
     pub fn request_many(&self, messages: Vec<(u32, ReadComm)>) -> Vec<ODriveResponse> {
-        let requests = messages
-            .iter()
-            .map(|(axis, cmd)| ODriveCANFrame {
-                axis: *axis,
-                cmd: ODriveCommand::Read(*cmd),
-                data: [0; 8],
-            })
-            .collect();
-        CANThreadCommunicator::request_many(self, requests)
+        CANThreadCommunicator::request_many(self, Self::read_requests(messages), None)
+    }
+
+    /// Same as [`ReadOnlyCANThread::request()`], but the request is answered with
+    /// [`ODriveError::NoResponse`](crate::response::ODriveError::NoResponse) if no response
+    /// arrives within `timeout` (and any retries configured on the `CANProxy`)
+    pub fn request_with_timeout(&self, axis: u32, cmd: ReadComm, timeout: Duration) -> ODriveResponse {
+        CANThreadCommunicator::request(self, Self::read_request(axis, cmd), Some(timeout))
+    }
+
+    /// Same as [`ReadOnlyCANThread::request_many()`], but with the timeout
+    /// applied to every request individually
+    pub fn request_many_with_timeout(&self, messages: Vec<(u32, ReadComm)>, timeout: Duration) -> Vec<ODriveResponse> {
+        CANThreadCommunicator::request_many(self, Self::read_requests(messages), Some(timeout))
+    }
+
+    /// Sends the request without blocking and returns a handle to its response
+    pub fn submit(&self, axis: u32, cmd: ReadComm) -> PendingRequest {
+        CANThreadCommunicator::submit(self, Self::read_request(axis, cmd), None)
+    }
+
+    fn read_request(axis: u32, cmd: ReadComm) -> CANRequest {
+        CANRequest {
+            axis,
+            cmd: ODriveCommand::Read(cmd),
+            data: [0; 8],
+        }
+    }
+
+    fn read_requests(messages: Vec<(u32, ReadComm)>) -> Vec<CANRequest> {
+        messages
+            .into_iter()
+            .map(|(axis, cmd)| Self::read_request(axis, cmd))
+            .collect()
+    }
+
+    /// Returns a receiver for every frame from the CAN bus that matches the filter,
+    /// including the ones the ODrives broadcast on their own such as heartbeats.
+    /// The subscription ends when the receiver is dropped.
+    ///
+    /// ```
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::canframe::FrameFilter;
+    /// use rustodrive::casts::Heartbeat;
+    /// use rustodrive::state::ReadComm;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_ro("heartbeat listener", |can_read| {
+    ///     let heartbeats = can_read.subscribe(FrameFilter::cmd(ReadComm::GetHeartbeat));
+    ///     while can_read.is_alive() {
+    ///         if let Ok(frame) = heartbeats.try_recv() {
+    ///             let heartbeat: Heartbeat = frame.try_into().unwrap();
+    ///         }
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn subscribe(&self, filter: FrameFilter) -> Receiver<CANResponse> {
+        CANThreadCommunicator::subscribe(self, filter)
+    }
+
+    /// Returns a receiver for a copy of every frame sent to or received from the CAN bus,
+    /// including the commands sent by the read-write thread. This lets a read-only thread,
+    /// such as a GUI, observe all traffic without taking part in it.
+    ///
+    /// ```
+    /// use rustodrive::canproxy::CANProxy;
+    /// use rustodrive::canframe::Direction;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_ro("bus monitor", |can_read| {
+    ///     let bus_events = can_read.monitor();
+    ///     while can_read.is_alive() {
+    ///         if let Ok(event) = bus_events.try_recv() {
+    ///             println!("{:?} {:?} {:?}", event.direction, event.thread_name, event.frame);
+    ///         }
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn monitor(&self) -> Receiver<BusEvent> {
+        CANThreadCommunicator::monitor(self)
+    }
+
+    /// Listens for heartbeats for the duration of `window` and returns every axis that
+    /// sent one, along with its decoded state and error. This lets a group be built without
+    /// knowing the node IDs in advance. The window should be longer than the heartbeat
+    /// interval of the ODrives.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use rustodrive::canproxy::CANProxy;
+    ///
+    /// let mut can_proxy = CANProxy::new("can0").unwrap();
+    /// can_proxy.register_ro("discovery", |can_read| {
+    ///     let inventory = can_read.discover(Duration::from_millis(300));
+    ///     for node in inventory.nodes() {
+    ///         println!("axis {}: {:?}", node.axis_id, node.heartbeat);
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn discover(&self, window: Duration) -> Inventory {
+        discovery::discover(self, window)
     }
 
     /// This should look at the mutex of whether the threads should be running,
@@ -234,7 +539,7 @@ mod tests {
 
     use crate::{
         state::{ODriveCommand, ReadComm},
-        canframe::{ThreadCANFrame, CANRequest},
+        canframe::{ThreadMessage, CANRequest},
         tests::ThreadStub,
         threads::CANThreadCommunicator, response::{ErrorResponse, ODriveError},
     };
@@ -247,18 +552,19 @@ mod tests {
 
         let can_frame = CANRequest {
             axis: 1,
-            cmd: ODriveCommand::Read(ReadComm::Heartbeat),
+            cmd: ODriveCommand::Read(ReadComm::GetHeartbeat),
             data: [0, 0, 0, 0, 0, 0, 0, 0],
         };
-        let expected_msg = ThreadCANFrame {
-            thread_name: "test",
-            body: can_frame,
-        };
-
-        thread.rw_communicator.thread_to_proxy(can_frame);
-
-        let data_received = thread.proxy_receiver.recv().unwrap();
-        assert_eq!(data_received, expected_msg);
+        thread.rw_communicator.thread_to_proxy(can_frame, None);
+
+        match thread.proxy_receiver.recv().unwrap() {
+            ThreadMessage::Request(data_received, _) => {
+                assert_eq!(data_received.thread_name, "test");
+                assert_eq!(data_received.body, can_frame);
+                assert_eq!(data_received.timeout, None);
+            }
+            other => panic!("Expected a request, got {:?}", other),
+        }
     }
 
     #[test]
@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    axis::{Axis, AxisID},
+    canframe::CANRequest,
+    Error,
+};
+
+/// The state of a trajectory at a point in time. Positions are in turns and time in seconds
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Sample {
+    pub position: f32,
+    pub velocity: f32,
+    pub acceleration: f32,
+}
+
+impl Sample {
+    /// A sample at rest at the position
+    pub fn at_rest(position: f32) -> Self {
+        Self { position, ..Default::default() }
+    }
+}
+
+/// A motion that can be sampled at any time between 0 and its duration in seconds.
+/// Sampling before the start or after the end gives the first or last sample.
+pub trait Trajectory {
+    fn duration(&self) -> f32;
+    fn sample(&self, t: f32) -> Sample;
+}
+
+/// Moves from one position to another as fast as the velocity and acceleration limits allow,
+/// starting and ending at rest. If the distance is too short to reach `max_velocity`, the
+/// velocity profile is a triangle instead of a trapezoid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trapezoidal {
+    start: f32,
+    direction: f32,
+    acceleration: f32,
+    peak_velocity: f32,
+    accel_time: f32,
+    cruise_time: f32,
+}
+
+impl Trapezoidal {
+    /// `max_velocity` and `max_acceleration` are magnitudes and have to be positive
+    pub fn new(start: f32, end: f32, max_velocity: f32, max_acceleration: f32) -> Self {
+        let distance = (end - start).abs();
+        let (accel_time, cruise_time) = match distance * max_acceleration <= max_velocity * max_velocity {
+            true => ((distance / max_acceleration).sqrt(), 0.0),
+            false => (max_velocity / max_acceleration, distance / max_velocity - max_velocity / max_acceleration),
+        };
+
+        Self {
+            start,
+            direction: if end < start { -1.0 } else { 1.0 },
+            acceleration: max_acceleration,
+            peak_velocity: max_acceleration * accel_time,
+            accel_time,
+            cruise_time,
+        }
+    }
+
+    /// Builds the profile that covers the distance in exactly `duration` seconds, spending
+    /// `accel_fraction` of it accelerating and the same decelerating. `accel_fraction` has to
+    /// be more than 0 and at most 0.5, where 0.5 gives a triangular profile
+    pub fn with_duration(start: f32, end: f32, duration: f32, accel_fraction: f32) -> Self {
+        let accel_time = duration * accel_fraction;
+        let cruise_time = duration - 2.0 * accel_time;
+        let peak_velocity = (end - start).abs() / (accel_time + cruise_time);
+
+        Self {
+            start,
+            direction: if end < start { -1.0 } else { 1.0 },
+            acceleration: match accel_time > 0.0 {
+                true => peak_velocity / accel_time,
+                false => 0.0,
+            },
+            peak_velocity,
+            accel_time,
+            cruise_time,
+        }
+    }
+}
+
+impl Trajectory for Trapezoidal {
+    fn duration(&self) -> f32 {
+        2.0 * self.accel_time + self.cruise_time
+    }
+
+    fn sample(&self, t: f32) -> Sample {
+        let t = t.clamp(0.0, self.duration());
+        let accel_distance = 0.5 * self.acceleration * self.accel_time * self.accel_time;
+
+        let (distance, velocity, acceleration) = if t < self.accel_time {
+            (0.5 * self.acceleration * t * t, self.acceleration * t, self.acceleration)
+        } else if t < self.accel_time + self.cruise_time {
+            (accel_distance + self.peak_velocity * (t - self.accel_time), self.peak_velocity, 0.0)
+        } else if t < self.duration() {
+            let remaining = self.duration() - t;
+            let total = 2.0 * accel_distance + self.peak_velocity * self.cruise_time;
+            (total - 0.5 * self.acceleration * remaining * remaining, self.acceleration * remaining, -self.acceleration)
+        } else {
+            (2.0 * accel_distance + self.peak_velocity * self.cruise_time, 0.0, 0.0)
+        };
+
+        Sample {
+            position: self.start + self.direction * distance,
+            velocity: self.direction * velocity,
+            acceleration: self.direction * acceleration,
+        }
+    }
+}
+
+/// A polynomial in time that meets the given position and velocity (cubic), or position,
+/// velocity and acceleration (quintic) at its start and end
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polynomial {
+    /// Coefficients of t^0 to t^5
+    coefficients: [f32; 6],
+    duration: f32,
+}
+
+impl Polynomial {
+    /// A cubic polynomial. The accelerations of `start` and `end` are ignored
+    pub fn cubic(start: Sample, end: Sample, duration: f32) -> Self {
+        let (p0, v0, p1, v1, t) = (start.position, start.velocity, end.position, end.velocity, duration);
+        Self {
+            coefficients: [
+                p0,
+                v0,
+                (3.0 * (p1 - p0) - (2.0 * v0 + v1) * t) / (t * t),
+                (2.0 * (p0 - p1) + (v0 + v1) * t) / (t * t * t),
+                0.0,
+                0.0,
+            ],
+            duration,
+        }
+    }
+
+    /// A quintic polynomial, which also has continuous acceleration at its ends
+    pub fn quintic(start: Sample, end: Sample, duration: f32) -> Self {
+        let (p0, v0, a0) = (start.position, start.velocity, start.acceleration);
+        let (v1, a1) = (end.velocity, end.acceleration);
+        let (t, distance) = (duration, end.position - start.position);
+        Self {
+            coefficients: [
+                p0,
+                v0,
+                a0 / 2.0,
+                (20.0 * distance - (8.0 * v1 + 12.0 * v0) * t - (3.0 * a0 - a1) * t * t) / (2.0 * t.powi(3)),
+                (-30.0 * distance + (14.0 * v1 + 16.0 * v0) * t + (3.0 * a0 - 2.0 * a1) * t * t) / (2.0 * t.powi(4)),
+                (12.0 * distance - 6.0 * (v1 + v0) * t + (a1 - a0) * t * t) / (2.0 * t.powi(5)),
+            ],
+            duration,
+        }
+    }
+}
+
+impl Trajectory for Polynomial {
+    fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    fn sample(&self, t: f32) -> Sample {
+        let t = t.clamp(0.0, self.duration);
+        let c = &self.coefficients;
+        Sample {
+            position: c[0] + t * (c[1] + t * (c[2] + t * (c[3] + t * (c[4] + t * c[5])))),
+            velocity: c[1] + t * (2.0 * c[2] + t * (3.0 * c[3] + t * (4.0 * c[4] + t * 5.0 * c[5]))),
+            acceleration: 2.0 * c[2] + t * (6.0 * c[3] + t * (12.0 * c[4] + t * 20.0 * c[5])),
+        }
+    }
+}
+
+/// A cubic spline through waypoints, with continuous velocity and acceleration.
+/// It starts and ends at rest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spline {
+    /// Start time of every segment, relative to the first waypoint
+    starts: Vec<f32>,
+    segments: Vec<Polynomial>,
+}
+
+impl Spline {
+    /// Builds the spline through `(time, position)` waypoints. The times are in seconds and
+    /// have to be strictly increasing. The spline starts at the time of the first waypoint.
+    ///
+    /// This returns [`Error::InvalidWaypoints`] if there are fewer than two waypoints or
+    /// their times are not increasing.
+    pub fn through(waypoints: &[(f32, f32)]) -> Result<Self, Error> {
+        if waypoints.len() < 2 || waypoints.windows(2).any(|pair| pair[1].0 <= pair[0].0) {
+            return Err(Error::InvalidWaypoints);
+        }
+
+        let velocities = knot_velocities(waypoints);
+        let mut starts = vec![];
+        let mut segments = vec![];
+        for (i, pair) in waypoints.windows(2).enumerate() {
+            let ((t0, p0), (t1, p1)) = (pair[0], pair[1]);
+            let start = Sample { position: p0, velocity: velocities[i], acceleration: 0.0 };
+            let end = Sample { position: p1, velocity: velocities[i + 1], acceleration: 0.0 };
+            starts.push(t0 - waypoints[0].0);
+            segments.push(Polynomial::cubic(start, end, t1 - t0));
+        }
+
+        Ok(Self { starts, segments })
+    }
+}
+
+impl Trajectory for Spline {
+    fn duration(&self) -> f32 {
+        self.starts.last().unwrap() + self.segments.last().unwrap().duration
+    }
+
+    fn sample(&self, t: f32) -> Sample {
+        let index = self.starts.iter().rposition(|start| t >= *start).unwrap_or(0);
+        self.segments[index].sample(t - self.starts[index])
+    }
+}
+
+/// Solves for the velocities at the waypoints that make the acceleration continuous,
+/// with the first and last velocity at zero
+fn knot_velocities(waypoints: &[(f32, f32)]) -> Vec<f32> {
+    let n = waypoints.len();
+    let mut velocities = vec![0.0; n];
+    if n < 3 {
+        return velocities;
+    }
+
+    let h: Vec<f32> = waypoints.windows(2).map(|pair| pair[1].0 - pair[0].0).collect();
+    let slope: Vec<f32> = waypoints.windows(2).map(|pair| pair[1].1 - pair[0].1).collect();
+
+    // Tridiagonal system for the interior velocities, solved with the Thomas algorithm
+    let interior = n - 2;
+    let mut diagonal = vec![0.0; interior];
+    let mut upper = vec![0.0; interior];
+    let mut rhs = vec![0.0; interior];
+    for k in 0..interior {
+        let (h0, h1) = (h[k], h[k + 1]);
+        diagonal[k] = 2.0 * (h0 + h1);
+        upper[k] = h0;
+        rhs[k] = 3.0 * (h1 * slope[k] / h0 + h0 * slope[k + 1] / h1);
+    }
+
+    for k in 1..interior {
+        let factor = h[k + 1] / diagonal[k - 1];
+        diagonal[k] -= factor * upper[k - 1];
+        rhs[k] -= factor * rhs[k - 1];
+    }
+    // The last velocity is zero, so the last interior one does not depend on it
+    for k in (0..interior).rev() {
+        velocities[k + 1] = (rhs[k] - upper[k] * velocities[k + 2]) / diagonal[k];
+    }
+
+    velocities
+}
+
+/// A trajectory for a single axis of a [`TrajectoryPlayer`]
+struct Track {
+    trajectory: Box<dyn Trajectory>,
+    inertia: f32,
+}
+
+/// Plays trajectories on several axes at once, all starting at the same time. Each axis is sent
+/// its position with the velocity, and the torque needed to accelerate its inertia, as feedforward.
+/// See [`ODriveGroup::play()`](crate::odrivegroup::ODriveGroup::play).
+#[derive(Default)]
+pub struct TrajectoryPlayer {
+    tracks: BTreeMap<AxisID, Track>,
+}
+
+impl TrajectoryPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the trajectory for an axis, replacing any it had before. `inertia` in Nm/(turn/s²)
+    /// turns the acceleration into a torque feedforward. With an inertia of 0 no torque
+    /// feedforward is sent
+    pub fn add_track(&mut self, axis_id: AxisID, trajectory: impl Trajectory + 'static, inertia: f32) {
+        self.tracks.insert(axis_id, Track { trajectory: Box::new(trajectory), inertia });
+    }
+
+    /// Returns the IDs of the axes that have a trajectory in ascending order
+    pub fn axis_ids(&self) -> Vec<AxisID> {
+        self.tracks.keys().copied().collect()
+    }
+
+    /// Duration of the longest trajectory in seconds
+    pub fn duration(&self) -> f32 {
+        self.tracks.values().map(|track| track.trajectory.duration()).fold(0.0, f32::max)
+    }
+
+    /// Returns the setpoint of every axis at time `t` in seconds. Axes whose trajectory
+    /// already ended hold their last position
+    pub fn setpoints(&self, t: f32) -> Vec<CANRequest> {
+        self.tracks
+            .iter()
+            .map(|(axis_id, track)| {
+                let sample = track.trajectory.sample(t);
+                Axis::new(*axis_id).motor.set_input_pos_ff(sample.position, sample.velocity, track.inertia * sample.acceleration)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use crate::{
+        canproxy::CANProxy,
+        odrivegroup::ODriveGroup,
+        simulator::ODriveSimulator,
+        streaming::StreamConfig,
+        tests::wait_for_msgs,
+        Error,
+    };
+
+    use super::{Polynomial, Sample, Spline, Trajectory, TrajectoryPlayer, Trapezoidal};
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn test_trapezoidal() {
+        // Long enough to cruise: 0.5s accelerating, 1.5s cruising, 0.5s decelerating
+        let trapezoid = Trapezoidal::new(1.0, -3.0, 2.0, 4.0);
+        assert_close(trapezoid.duration(), 2.5);
+        assert_close(trapezoid.sample(0.25).velocity, -1.0);
+        assert_close(trapezoid.sample(1.0).velocity, -2.0);
+        assert_close(trapezoid.sample(1.0).position, 1.0 - 0.5 - 1.0);
+        assert_eq!(trapezoid.sample(3.0), Sample::at_rest(-3.0));
+
+        // Too short to reach the velocity limit
+        let triangle = Trapezoidal::new(0.0, 1.0, 10.0, 4.0);
+        assert_close(triangle.duration(), 1.0);
+        assert_close(triangle.sample(0.5).position, 0.5);
+        assert_close(triangle.sample(0.5).velocity, 2.0);
+
+        let timed = Trapezoidal::with_duration(0.0, 3.0, 4.0, 0.25);
+        assert_close(timed.duration(), 4.0);
+        assert_close(timed.sample(2.0).velocity, 1.0);
+        assert_close(timed.sample(4.0).position, 3.0);
+    }
+
+    #[test]
+    fn test_polynomials() {
+        let start = Sample { position: 0.0, velocity: 1.0, acceleration: 0.5 };
+        let end = Sample { position: 2.0, velocity: -0.5, acceleration: -1.0 };
+
+        let cubic = Polynomial::cubic(start, end, 2.0);
+        assert_close(cubic.sample(0.0).velocity, 1.0);
+        assert_close(cubic.sample(2.0).position, 2.0);
+        assert_close(cubic.sample(2.0).velocity, -0.5);
+
+        let quintic = Polynomial::quintic(start, end, 2.0);
+        for (actual, expected) in [(quintic.sample(0.0), start), (quintic.sample(2.0), end)] {
+            assert_close(actual.position, expected.position);
+            assert_close(actual.velocity, expected.velocity);
+            assert_close(actual.acceleration, expected.acceleration);
+        }
+    }
+
+    #[test]
+    fn test_spline() {
+        let waypoints = [(1.0, 0.0), (2.0, 1.0), (2.5, 0.5), (4.0, 2.0)];
+        let spline = Spline::through(&waypoints).unwrap();
+        assert_close(spline.duration(), 3.0);
+        for (t, position) in waypoints {
+            assert_close(spline.sample(t - 1.0).position, position);
+        }
+        assert_close(spline.sample(0.0).velocity, 0.0);
+        assert_close(spline.sample(3.0).velocity, 0.0);
+
+        // Velocity and acceleration are continuous where the segments meet
+        for t in [1.0, 1.5] {
+            let (before, after) = (spline.sample(t - 1e-4), spline.sample(t + 1e-4));
+            assert!((before.velocity - after.velocity).abs() < 1e-2);
+            assert!((before.acceleration - after.acceleration).abs() < 1e-2);
+        }
+
+        assert!(matches!(Spline::through(&[(0.0, 1.0)]), Err(Error::InvalidWaypoints)));
+        assert!(matches!(Spline::through(&[(0.0, 1.0), (0.0, 2.0)]), Err(Error::InvalidWaypoints)));
+    }
+
+    #[test]
+    fn test_play_trajectories() {
+        let simulator = ODriveSimulator::new(&[0, 1]);
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+            let mut player = TrajectoryPlayer::new();
+            player.add_track(0, Trapezoidal::new(0.0, 0.5, 2.0, 10.0), 0.1);
+            player.add_track(1, Spline::through(&[(0.0, 0.0), (0.1, -0.2), (0.2, -0.1)]).unwrap(), 0.0);
+            let unknown = {
+                let mut player = TrajectoryPlayer::new();
+                player.add_track(3, Trapezoidal::new(0.0, 1.0, 1.0, 1.0), 0.0);
+                odrives.play(&player, &StreamConfig::with_frequency(200.0).unwrap())
+            };
+            let stats = odrives.play(&player, &StreamConfig::with_frequency(200.0).unwrap()).unwrap();
+            send.send((stats, unknown.err())).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let (stats, unknown) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        // Ticks may be skipped on a busy machine, but the last sample is always sent
+        assert!(stats.cycles > 1, "{}", stats);
+        assert_eq!(stats.send_errors, 0);
+        assert!(matches!(unknown, Some(Error::UnknownAxis(3))));
+
+        let node_0 = simulator.node(0).unwrap();
+        assert_close(node_0.input_pos, 0.5);
+        assert_eq!((node_0.input_vel, node_0.input_torque), (0.0, 0.0));
+        assert_close(simulator.node(1).unwrap().input_pos, -0.1);
+    }
+}
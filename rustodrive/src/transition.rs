@@ -0,0 +1,203 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    axis::{Axis, AxisID},
+    canframe::{CANResponse, FrameFilter},
+    error::AxisError,
+    response::ErrorResponse,
+    state::{AxisState, ReadComm},
+    threads::{CANThreadCommunicator, ReadWriteCANThread},
+};
+
+/// How a single axis responded to a requested state, as watched through its heartbeats
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransitionOutcome {
+    /// The axis entered the requested state. For sequences that end on their own, such as
+    /// `FullCalibrationSequence`, this means the axis ran the sequence and returned to `Idle`
+    Reached,
+    /// The axis reported an error. `state` is the state it was in at the time
+    Failed { state: AxisState, errors: Vec<AxisError> },
+    /// The axis did not finish the transition before the timeout.
+    /// This holds the last state it reported, if it reported any
+    TimedOut(Option<AxisState>),
+    /// The state request could not be sent to the axis
+    NotSent(ErrorResponse),
+}
+
+/// Returns true if the axis leaves the state on its own and goes back to `Idle` once it is done
+fn is_sequence(state: &AxisState) -> bool {
+    matches!(
+        state,
+        AxisState::StartupSequence
+            | AxisState::FullCalibrationSequence
+            | AxisState::MotorCalibration
+            | AxisState::EncoderIndexSearch
+            | AxisState::EncoderOffsetCalib
+            | AxisState::EncoderDirFind
+            | AxisState::Homing
+            | AxisState::EncoderHallPolarityCalib
+            | AxisState::EncoderHallPhaseCalib
+    )
+}
+
+/// Tracks the heartbeats of one axis until the transition is over
+struct Watcher {
+    target: AxisState,
+    entered_target: bool,
+    last_state: Option<AxisState>,
+}
+
+impl Watcher {
+    fn new(target: AxisState) -> Self {
+        Self { target, entered_target: false, last_state: None }
+    }
+
+    /// Returns the outcome once a heartbeat decides it
+    fn update(&mut self, heartbeat: &CANResponse) -> Option<TransitionOutcome> {
+        let error_bits = u32::from_le_bytes(heartbeat.data[0..4].try_into().unwrap());
+        let state = AxisState::try_from(heartbeat.data[4]).ok()?;
+        self.last_state = Some(state.clone());
+
+        if error_bits != 0 {
+            return Some(TransitionOutcome::Failed { state, errors: AxisError::from_bits(error_bits) });
+        }
+
+        if state == self.target {
+            self.entered_target = true;
+            if !is_sequence(&self.target) {
+                return Some(TransitionOutcome::Reached);
+            }
+        } else if state == AxisState::Idle && self.entered_target {
+            return Some(TransitionOutcome::Reached);
+        }
+        None
+    }
+}
+
+/// Requests `state` on every axis and watches their heartbeats until each one reaches it,
+/// reports an error, or `timeout` elapses.
+pub(crate) fn transition(
+    can: &ReadWriteCANThread,
+    axis_ids: &[AxisID],
+    state: AxisState,
+    timeout: Duration,
+) -> BTreeMap<AxisID, TransitionOutcome> {
+    let heartbeats = CANThreadCommunicator::subscribe(can, FrameFilter::cmd(ReadComm::GetHeartbeat));
+    let requests = axis_ids.iter().map(|id| Axis::new(*id).set_state(state.clone())).collect();
+    let responses = can.request_many(requests);
+    let deadline = Instant::now() + timeout;
+
+    let mut outcomes = BTreeMap::new();
+    let mut watchers = BTreeMap::new();
+    for (id, response) in axis_ids.iter().zip(responses) {
+        match response {
+            Ok(_) => {
+                watchers.insert(*id, Watcher::new(state.clone()));
+            }
+            Err(err) => {
+                outcomes.insert(*id, TransitionOutcome::NotSent(err));
+            }
+        }
+    }
+
+    // Heartbeats that arrived before the requests were sent still show the old state
+    heartbeats.try_iter().for_each(drop);
+
+    while !watchers.is_empty() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        let heartbeat = match heartbeats.recv_timeout(remaining) {
+            Ok(heartbeat) => heartbeat,
+            Err(_) => continue,
+        };
+        let id = heartbeat.axis as AxisID;
+        if let Some(outcome) = watchers.get_mut(&id).and_then(|watcher| watcher.update(&heartbeat)) {
+            watchers.remove(&id);
+            outcomes.insert(id, outcome);
+        }
+    }
+
+    for (id, watcher) in watchers {
+        outcomes.insert(id, TransitionOutcome::TimedOut(watcher.last_state));
+    }
+    outcomes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, time::Duration};
+
+    use crate::{
+        canproxy::CANProxy,
+        error::AxisError,
+        odrivegroup::ODriveGroup,
+        simulator::{ODriveSimulator, SimulationParams},
+        state::AxisState::*,
+        tests::wait_for_msgs,
+    };
+
+    use super::TransitionOutcome;
+
+    fn fast_calibration() -> SimulationParams {
+        SimulationParams {
+            calibration_time: Duration::from_millis(150),
+            heartbeat_interval: Some(Duration::from_millis(20)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_calibrate_then_closed_loop() {
+        let simulator = ODriveSimulator::with_params(&[0, 1], fast_calibration());
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let odrives = ODriveGroup::new(can_rw, &[0, 1]);
+            let calibrated = odrives.transition_all(FullCalibrationSequence, Duration::from_secs(2));
+            let closed_loop = odrives.transition_axis(&1, ClosedLoop, Duration::from_secs(1));
+            send.send((calibrated, closed_loop)).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let (calibrated, closed_loop) = wait_for_msgs(rcv);
+        stop_proxy().unwrap();
+
+        assert_eq!(calibrated.len(), 2);
+        assert!(calibrated.values().all(|outcome| *outcome == TransitionOutcome::Reached));
+        assert_eq!(closed_loop.unwrap(), TransitionOutcome::Reached);
+        assert_eq!(simulator.node(1).unwrap().state, ClosedLoop);
+        assert_eq!(simulator.node(0).unwrap().state, Idle);
+    }
+
+    #[test]
+    fn test_transition_error_and_timeout() {
+        let simulator = ODriveSimulator::with_params(&[0, 1], fast_calibration());
+        let mut can_proxy = CANProxy::with_transport(simulator);
+
+        let (send, rcv) = channel();
+        can_proxy.register_rw("thread 1", move |can_rw| {
+            let mut odrives = ODriveGroup::new(can_rw, &[0, 1, 2]);
+            odrives.define_subgroup("left", &[0, 2]).unwrap();
+            // The axes are not calibrated, so closed loop control fails
+            let outcomes = odrives.transition_subgroup("left", ClosedLoop, Duration::from_millis(300));
+            send.send(outcomes).unwrap();
+        }).unwrap();
+        let stop_proxy = can_proxy.begin();
+        let outcomes = wait_for_msgs(rcv).unwrap();
+        stop_proxy().unwrap();
+
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(
+            outcomes[&0],
+            TransitionOutcome::Failed { state: Idle, errors: vec![AxisError::MissingEstimate] }
+        );
+        // Axis 2 is not on the bus
+        assert_eq!(outcomes[&2], TransitionOutcome::TimedOut(None));
+    }
+}
@@ -1,15 +1,12 @@
 use std::fmt;
 
 
+/// Response Manipulation
+/// 
+/// Collection of functions for manipulating data sent to / from the CAN bus
 pub struct ResponseManip {}
 impl ResponseManip {
-    /// Response Manipulation
-    /// 
-    /// Collection of functions for manipulating data sent to / from the CAN bus
-    /// 
-
-
-    /// Combines two data entries into a bigger data entry array
+    // Combines two data entries into a bigger data entry array
   /*   fn combine<'a>(data1: &[u8], data2: &[u8], out_size: Option<usize>) -> Vec<u8> {
         
         let size = out_size.unwrap_or(8);
@@ -29,7 +26,7 @@ impl ResponseManip {
         left.copy_from_slice(data1);
         right.copy_from_slice(data2);
     
-        return dest;
+        dest
     }
 
     /// Combines two 4-byte arrays into a 8-byte array
@@ -56,10 +53,10 @@ impl ResponseManip {
     /// Splits a data entry of into two equal parts
     fn split(data: &[u8]) -> (&[u8], &[u8]) {
         let split_size = data.len()/2;
-        return  (
+        (
                 &data[..split_size], 
                 &data[split_size..],
-                );
+                )
     }
 
     
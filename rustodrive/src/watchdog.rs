@@ -0,0 +1,199 @@
+use std::{
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    axis::AxisID,
+    response::ErrorResponse,
+    state::ReadComm,
+    threads::{PendingRequest, ReadOnlyCANThread},
+};
+
+/// Configures the keepalive service started with
+/// [`CANProxy::register_watchdog()`](crate::canproxy::CANProxy::register_watchdog)
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchdogConfig {
+    pub axes: Vec<AxisID>,
+    /// How often every axis is sent a keepalive. This should be well below
+    /// `axis.config.watchdog_timeout` on the ODrives
+    pub interval: Duration,
+    /// How long the controlling thread may go without calling [`WatchdogHandle::check_in()`]
+    /// before the axes are no longer fed
+    pub check_in_timeout: Duration,
+    /// The request sent as a keepalive. Any message addressed to an axis feeds its watchdog,
+    /// so a read that does not change anything on the ODrive is used
+    pub keepalive: ReadComm,
+}
+
+impl WatchdogConfig {
+    /// Feeds the axes every `interval` for as long as the controlling thread checks in at
+    /// least every three intervals
+    pub fn new(axes: &[AxisID], interval: Duration) -> Self {
+        Self {
+            axes: axes.to_vec(),
+            interval,
+            check_in_timeout: interval * 3,
+            keepalive: ReadComm::GetVBusVoltage,
+        }
+    }
+}
+
+/// Something the keepalive service reports to the application
+#[derive(Clone, Debug, PartialEq)]
+pub enum WatchdogEvent {
+    /// The service sent its keepalives later than scheduled, e.g. because the system was overloaded
+    MissedDeadline { late_by: Duration },
+    /// The controlling thread stopped checking in, so the axes are no longer fed
+    /// and their watchdogs will trip
+    Starved,
+    /// The controlling thread checked in again and the axes are fed again
+    Resumed,
+    /// An axis did not answer its keepalive
+    KeepaliveFailed(ErrorResponse),
+}
+
+/// The controlling thread's side of the keepalive service
+pub struct WatchdogHandle {
+    last_check_in: Arc<Mutex<Instant>>,
+    events: Receiver<WatchdogEvent>,
+}
+
+impl WatchdogHandle {
+    pub(crate) fn new(last_check_in: Arc<Mutex<Instant>>, events: Receiver<WatchdogEvent>) -> Self {
+        Self { last_check_in, events }
+    }
+
+    /// Tells the service that the controlling thread is still running and the axes should
+    /// be kept alive. This has to be called at least every `check_in_timeout`
+    pub fn check_in(&self) {
+        *self.last_check_in.lock().unwrap() = Instant::now();
+    }
+
+    /// Returns the receiver for everything the service reports
+    pub fn events(&self) -> &Receiver<WatchdogEvent> {
+        &self.events
+    }
+}
+
+/// Sends the keepalives until the proxy stops. Called on the service's read-only thread
+pub(crate) fn run(
+    can_read: ReadOnlyCANThread,
+    config: WatchdogConfig,
+    last_check_in: Arc<Mutex<Instant>>,
+    events: Sender<WatchdogEvent>,
+) {
+    let report = |event| {
+        // The application may not be interested in the events
+        events.send(event).unwrap_or(());
+    };
+
+    let mut pending: Vec<PendingRequest> = vec![];
+    let mut starved = false;
+    let mut scheduled = Instant::now();
+    while can_read.is_alive() {
+        let now = Instant::now();
+        if now < scheduled {
+            thread::sleep(scheduled - now);
+            continue;
+        }
+
+        let late_by = now - scheduled;
+        if late_by > config.interval / 2 {
+            report(WatchdogEvent::MissedDeadline { late_by });
+        }
+        scheduled = now.max(scheduled + config.interval);
+
+        // Report the keepalives that were not answered, and keep waiting on the rest
+        pending.retain_mut(|request| match request.poll() {
+            None => true,
+            Some(Ok(_)) => false,
+            Some(Err(err)) => {
+                report(WatchdogEvent::KeepaliveFailed(err));
+                false
+            }
+        });
+
+        let checked_in = last_check_in.lock().unwrap().elapsed() <= config.check_in_timeout;
+        match (checked_in, starved) {
+            (false, false) => report(WatchdogEvent::Starved),
+            (true, true) => report(WatchdogEvent::Resumed),
+            _ => {}
+        }
+        starved = !checked_in;
+        if starved {
+            continue;
+        }
+
+        for axis in &config.axes {
+            pending.push(can_read.submit(*axis as u32, config.keepalive));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc::channel,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        canproxy::CANProxy,
+        error::AxisError,
+        simulator::{ODriveSimulator, SimulationParams},
+        state::AxisState,
+    };
+
+    use super::{WatchdogConfig, WatchdogEvent};
+
+    #[test]
+    fn test_watchdog_keepalive() {
+        let params = SimulationParams {
+            precalibrated: true,
+            watchdog_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let simulator = ODriveSimulator::with_params(&[0, 1], params);
+        for id in [0, 1] {
+            simulator.with_node(id, |node| node.state = AxisState::ClosedLoop);
+        }
+        let mut can_proxy = CANProxy::with_transport(simulator.clone());
+
+        let config = WatchdogConfig::new(&[0, 1], Duration::from_millis(20));
+        let watchdog = can_proxy.register_watchdog("watchdog", config).unwrap();
+        let (done, finished) = channel();
+        can_proxy.register_ro("controller", move |_| {
+            // The controlling thread checks in for a while, then hangs
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(300) {
+                watchdog.check_in();
+                thread::sleep(Duration::from_millis(10));
+            }
+            thread::sleep(Duration::from_millis(300));
+            let events: Vec<_> = watchdog.events().try_iter().collect();
+            done.send(events).unwrap();
+        }).unwrap();
+
+        let stop_proxy = can_proxy.begin();
+        thread::sleep(Duration::from_millis(250));
+        // The axes are still fed, even though nothing else is sent to them
+        let fed = simulator.node(1).unwrap();
+        let events = finished.recv().unwrap();
+        stop_proxy().unwrap();
+
+        assert_eq!(fed.state, AxisState::ClosedLoop);
+        assert!(events.contains(&WatchdogEvent::Starved));
+        assert!(!events.iter().any(|event| matches!(event, WatchdogEvent::KeepaliveFailed(_))));
+        for id in [0, 1] {
+            let node = simulator.node(id).unwrap();
+            assert_eq!(node.state, AxisState::Idle);
+            assert_eq!(node.axis_error, AxisError::WatchdogTimerExpired as u32);
+        }
+    }
+}